@@ -0,0 +1,307 @@
+use std::fmt;
+use crate::crc32::crc32;
+use crate::inflate::{inflate, InflateError};
+
+//Compressed rom containers, recognised by their magic bytes rather than the file extension
+//gzip - 10 byte header with optional extra, name, comment and header crc fields, DEFLATE data,
+//       then CRC32 and size of the uncompressed data
+//zip  - the central directory at the end lists every entry, each pointing at a local header
+//       followed by stored or DEFLATE data
+pub const ROM_EXTENSIONS: [&str; 2] = ["gb", "gbc"];
+
+#[derive(Debug, PartialEq)]
+pub enum ArchiveError {
+    Corrupt(&'static str),
+    Inflate(InflateError),
+    TooLarge(usize),
+    NoRom,
+    EntryNotFound(String),
+    UnsupportedMethod(u16),
+    Checksum { expected: u32, actual: u32 },
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArchiveError::Corrupt(reason) => write!(f, "archive is corrupt: {}", reason),
+            ArchiveError::Inflate(error) => write!(f, "could not decompress: {}", error),
+            ArchiveError::TooLarge(limit) => write!(f, "decompressed ROM is larger than {} bytes", limit),
+            ArchiveError::NoRom => write!(f, "archive has no .gb or .gbc file"),
+            ArchiveError::EntryNotFound(name) => write!(f, "archive has no file named {}", name),
+            ArchiveError::UnsupportedMethod(method) => write!(f, "zip compression method {} is not supported", method),
+            ArchiveError::Checksum { expected, actual } => {
+                write!(f, "decompressed data has CRC32 {:08X}, archive expected {:08X}", actual, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+//What the rom was unpacked from, zip entries carry their name inside the archive
+#[derive(Debug, PartialEq)]
+pub enum Container {
+    Plain,
+    Gzip,
+    Zip(String),
+}
+
+impl From<InflateError> for ArchiveError {
+    fn from(error: InflateError) -> ArchiveError {
+        ArchiveError::Inflate(error)
+    }
+}
+
+//Unpack the rom if data is a gzip or zip archive, anything else is returned as is
+//entry picks a zip entry by name, otherwise the first .gb/.gbc is used
+pub fn extract_rom(data: Vec<u8>, entry: Option<&str>, limit: usize) -> Result<(Vec<u8>, Container), ArchiveError> {
+    if data.starts_with(&[0x1F, 0x8B]) {
+        Ok((gunzip(&data, limit)?, Container::Gzip))
+    }
+    else if data.starts_with(b"PK\x03\x04") || data.starts_with(b"PK\x05\x06") {
+        let (rom, name) = unzip(&data, entry, limit)?;
+        Ok((rom, Container::Zip(name)))
+    }
+    else {
+        Ok((data, Container::Plain))
+    }
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Result<u16, ArchiveError> {
+    let bytes = data.get(offset..offset + 2).ok_or(ArchiveError::Corrupt("unexpected end of file"))?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Result<u32, ArchiveError> {
+    let bytes = data.get(offset..offset + 4).ok_or(ArchiveError::Corrupt("unexpected end of file"))?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn check_crc(data: &[u8], expected: u32) -> Result<(), ArchiveError> {
+    let actual = crc32(data);
+    if actual != expected {
+        return Err(ArchiveError::Checksum { expected, actual })
+    }
+    Ok(())
+}
+
+pub fn gunzip(data: &[u8], limit: usize) -> Result<Vec<u8>, ArchiveError> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    if data.len() < 18 || data[2] != 8 {
+        return Err(ArchiveError::Corrupt("not a DEFLATE gzip file"))
+    }
+    let flags = data[3];
+    let mut position = 10;
+    if flags & FEXTRA != 0 {
+        position += 2 + u16_at(data, position)? as usize;
+    }
+    for flag in [FNAME, FCOMMENT].iter() {
+        if flags & flag != 0 {
+            let end = data.get(position..).and_then(|rest| rest.iter().position(|byte| *byte == 0));
+            position += end.ok_or(ArchiveError::Corrupt("unterminated header string"))? + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        position += 2;
+    }
+
+    let trailer = data.len() - 8;
+    let body = data.get(position..trailer).ok_or(ArchiveError::Corrupt("unexpected end of file"))?;
+    let size = u32_at(data, trailer + 4)? as usize;
+    if size > limit {
+        return Err(ArchiveError::TooLarge(limit))
+    }
+    let output = inflate(body, size)?;
+    if output.len() != size {
+        return Err(ArchiveError::Corrupt("decompressed size doesn't match"))
+    }
+    check_crc(&output, u32_at(data, trailer)?)?;
+    Ok(output)
+}
+
+struct ZipEntry {
+    name: String,
+    method: u16,
+    crc: u32,
+    compressed_size: usize,
+    size: usize,
+    header_offset: usize,
+}
+
+fn zip_entries(data: &[u8]) -> Result<Vec<ZipEntry>, ArchiveError> {
+    //End of central directory record is 22 bytes plus a comment of up to 64 KiB
+    let search_start = data.len().saturating_sub(22 + 0xFFFF);
+    let end = (search_start..data.len().saturating_sub(21)).rev()
+        .find(|offset| data[*offset..].starts_with(b"PK\x05\x06"))
+        .ok_or(ArchiveError::Corrupt("no central directory"))?;
+    let count = u16_at(data, end + 10)? as usize;
+    let mut offset = u32_at(data, end + 16)? as usize;
+
+    let mut entries = Vec::with_capacity(count);
+    for _ in 0..count {
+        if u32_at(data, offset)? != 0x0201_4B50 {
+            return Err(ArchiveError::Corrupt("bad central directory entry"))
+        }
+        let name_length = u16_at(data, offset + 28)? as usize;
+        let extra_length = u16_at(data, offset + 30)? as usize;
+        let comment_length = u16_at(data, offset + 32)? as usize;
+        let name = data.get(offset + 46..offset + 46 + name_length).ok_or(ArchiveError::Corrupt("unexpected end of file"))?;
+        entries.push(ZipEntry {
+            name: String::from_utf8_lossy(name).into_owned(),
+            method: u16_at(data, offset + 10)?,
+            crc: u32_at(data, offset + 16)?,
+            compressed_size: u32_at(data, offset + 20)? as usize,
+            size: u32_at(data, offset + 24)? as usize,
+            header_offset: u32_at(data, offset + 42)? as usize,
+        });
+        offset += 46 + name_length + extra_length + comment_length;
+    }
+    Ok(entries)
+}
+
+//Matches on the full path inside the zip or just the file name, ignoring case
+fn entry_matches(entry: &ZipEntry, name: &str) -> bool {
+    let file_name = entry.name.rsplit('/').next().unwrap_or(&entry.name);
+    entry.name.eq_ignore_ascii_case(name) || file_name.eq_ignore_ascii_case(name)
+}
+
+fn is_rom(entry: &ZipEntry) -> bool {
+    let extension = entry.name.rsplit('.').next().unwrap_or("");
+    entry.name.contains('.') && ROM_EXTENSIONS.iter().any(|rom| extension.eq_ignore_ascii_case(rom))
+}
+
+//Returns the entry's data and its full name in the archive
+pub fn unzip(data: &[u8], name: Option<&str>, limit: usize) -> Result<(Vec<u8>, String), ArchiveError> {
+    let entries = zip_entries(data)?;
+    let entry = match name {
+        Some(name) => entries.iter().find(|entry| entry_matches(entry, name)).ok_or_else(|| ArchiveError::EntryNotFound(name.to_string()))?,
+        None => entries.iter().find(|entry| is_rom(entry)).ok_or(ArchiveError::NoRom)?,
+    };
+    if entry.size > limit {
+        return Err(ArchiveError::TooLarge(limit))
+    }
+
+    //Local header repeats the name and has its own extra field length
+    let header = entry.header_offset;
+    if u32_at(data, header)? != 0x0403_4B50 {
+        return Err(ArchiveError::Corrupt("bad local header"))
+    }
+    let start = header + 30 + u16_at(data, header + 26)? as usize + u16_at(data, header + 28)? as usize;
+    let compressed = data.get(start..start + entry.compressed_size).ok_or(ArchiveError::Corrupt("unexpected end of file"))?;
+
+    let output = match entry.method {
+        0 => compressed.to_vec(),
+        8 => inflate(compressed, entry.size)?,
+        method => return Err(ArchiveError::UnsupportedMethod(method)),
+    };
+    if output.len() != entry.size {
+        return Err(ArchiveError::Corrupt("decompressed size doesn't match"))
+    }
+    check_crc(&output, entry.crc)?;
+    Ok((output, entry.name.clone()))
+}
+
+
+//Test archive from (name, method, compressed data, uncompressed data)
+#[cfg(test)]
+pub(crate) fn zip(files: &[(&str, u16, Vec<u8>, &[u8])]) -> Vec<u8> {
+    let mut zip = Vec::new();
+    let mut directory = Vec::new();
+    for (name, method, compressed, data) in files {
+        let offset = zip.len() as u32;
+        let mut common = Vec::new();
+        common.extend_from_slice(&[20, 0, 0, 0]);
+        common.extend_from_slice(&method.to_le_bytes());
+        common.extend_from_slice(&[0, 0, 0, 0]);
+        common.extend_from_slice(&crc32(data).to_le_bytes());
+        common.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        common.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        common.extend_from_slice(&[0, 0]);
+
+        zip.extend_from_slice(b"PK\x03\x04");
+        zip.extend_from_slice(&common);
+        zip.extend_from_slice(name.as_bytes());
+        zip.extend_from_slice(compressed);
+
+        directory.extend_from_slice(b"PK\x01\x02");
+        directory.extend_from_slice(&[20, 0]);
+        directory.extend_from_slice(&common);
+        directory.extend_from_slice(&[0; 10]);
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+    }
+    let directory_offset = zip.len() as u32;
+    zip.extend_from_slice(&directory);
+    zip.extend_from_slice(b"PK\x05\x06\x00\x00\x00\x00");
+    zip.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    zip.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    zip.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    zip.extend_from_slice(&directory_offset.to_le_bytes());
+    zip.extend_from_slice(&[0, 0]);
+    zip
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Single stored DEFLATE block
+    fn stored(data: &[u8]) -> Vec<u8> {
+        let mut block = vec![0x01];
+        block.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        block.extend_from_slice(&(!(data.len() as u16)).to_le_bytes());
+        block.extend_from_slice(data);
+        block
+    }
+
+    fn gzip(data: &[u8], name: &[u8]) -> Vec<u8> {
+        let mut gzip = vec![0x1F, 0x8B, 0x08, 0x08, 0, 0, 0, 0, 0x00, 0x03];
+        gzip.extend_from_slice(name);
+        gzip.push(0);
+        gzip.extend(stored(data));
+        gzip.extend_from_slice(&crc32(data).to_le_bytes());
+        gzip.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        gzip
+    }
+
+    #[test]
+    fn test_plain_data_passes_through() {
+        assert_eq!(extract_rom(vec![1, 2, 3], None, 100).unwrap(), (vec![1, 2, 3], Container::Plain));
+    }
+
+    #[test]
+    fn test_gzip() {
+        let data = gzip(b"game data", b"game.gb");
+        assert_eq!(extract_rom(data.clone(), None, 100).unwrap(), (b"game data".to_vec(), Container::Gzip));
+        assert_eq!(extract_rom(data.clone(), None, 4), Err(ArchiveError::TooLarge(4)));
+
+        let mut damaged = data;
+        let crc_offset = damaged.len() - 8;
+        damaged[crc_offset] ^= 0xFF;
+        assert!(matches!(extract_rom(damaged, None, 100), Err(ArchiveError::Checksum { .. })));
+    }
+
+    #[test]
+    fn test_zip_picks_rom_entry() {
+        let fixed = vec![0xF3, 0x48, 0xCD, 0xC9, 0xC9, 0xD7, 0x51, 0xF0, 0x40, 0xA1, 0xDC, 0x13, 0x73, 0x53, 0x15, 0x9C, 0xF2, 0x2B, 0x15, 0x01];
+        let archive = zip(&[
+            ("readme.txt", 0, b"hi".to_vec(), b"hi"),
+            ("roms/First.GB", 8, fixed, b"Hello, Hello, Hello, Game Boy!"),
+            ("second.gbc", 0, b"second".to_vec(), b"second"),
+        ]);
+        let first = (b"Hello, Hello, Hello, Game Boy!".to_vec(), Container::Zip("roms/First.GB".to_string()));
+        assert_eq!(extract_rom(archive.clone(), None, 100).unwrap(), first);
+        assert_eq!(extract_rom(archive.clone(), Some("second.gbc"), 100).unwrap(), (b"second".to_vec(), Container::Zip("second.gbc".to_string())));
+        assert_eq!(extract_rom(archive.clone(), Some("first.gb"), 100).unwrap(), first);
+        assert_eq!(extract_rom(archive, Some("third.gb"), 100), Err(ArchiveError::EntryNotFound("third.gb".to_string())));
+
+        let no_rom = zip(&[("readme.txt", 0, b"hi".to_vec(), b"hi")]);
+        assert_eq!(extract_rom(no_rom, None, 100), Err(ArchiveError::NoRom));
+    }
+}
@@ -0,0 +1,369 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::image::{GrayImage, ImageError, IMAGE_EXTENSIONS};
+use super::{Cartridge, rom_bank_read, ram_bank_offset};
+
+//Pocket Camera (type 0xFC) registers
+//0000-1FFF RAM enable - 0x0A enables writes, ram can always be read
+//2000-3FFF 6 bit rom bank number, bank 0 can be mapped
+//4000-5FFF RAM bank - 0x00-0x0F selects one of 16 ram banks,
+//          bit 4 set maps the sensor registers at A000-A07F instead (mirrored through BFFF)
+//Sensor registers
+//A000      bit 0 start capture, reads 1 while busy
+//A001      bit 7 N, bits 5-6 VH edge mode, bits 0-4 gain
+//A002-A003 exposure time, MSB first
+//A004      bits 4-6 edge ratio, bit 3 invert, bits 0-2 output reference (not modelled)
+//A005      zero point and offset calibration (not modelled)
+//A006-A035 4x4 matrix of 3 thresholds each, used for dithering and contrast
+//A captured 128x112 image is written to ram bank 0 at A100 as 16x14 tiles
+pub const SENSOR_WIDTH: usize = 128;
+pub const SENSOR_HEIGHT: usize = 112;
+const IMAGE_OFFSET: usize = 0x100;
+const REGISTER_COUNT: usize = 0x36;
+const MATRIX_START: usize = 0x06;
+
+//Where the sensor image comes from, frames are SENSOR_WIDTH x SENSOR_HEIGHT greyscale
+pub trait ImageSource {
+    fn capture(&mut self) -> Vec<u8>;
+}
+
+//A still image, or a directory of images that are shown one per capture in name order
+pub struct ImageFiles {
+    frames: Vec<Vec<u8>>,
+    next: usize,
+}
+
+impl ImageFiles {
+    pub fn open(path: &Path) -> Result<ImageFiles, ImageError> {
+        let directory = path.is_dir();
+        let paths: Vec<PathBuf> = if directory {
+            let mut paths: Vec<PathBuf> = fs::read_dir(path)
+                .map_err(|error| ImageError::Io(path.to_path_buf(), error))?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|entry| {
+                    entry.extension()
+                        .and_then(|extension| extension.to_str())
+                        .is_some_and(|extension| IMAGE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str()))
+                })
+                .collect();
+            paths.sort();
+            paths
+        }
+        else {
+            vec![path.to_path_buf()]
+        };
+
+        //A file in a directory that can't be decoded is skipped instead of failing the whole source
+        let mut frames = Vec::new();
+        for image_path in paths {
+            match GrayImage::load(&image_path) {
+                Ok(image) => frames.push(image.resize(SENSOR_WIDTH, SENSOR_HEIGHT)),
+                Err(error) if directory => println!("Skipping camera image: {}", error),
+                Err(error) => return Err(error),
+            }
+        }
+        if frames.is_empty() {
+            let error = io::Error::new(io::ErrorKind::NotFound, "directory has no images");
+            return Err(ImageError::Io(path.to_path_buf(), error))
+        }
+        Ok(ImageFiles { frames, next: 0 })
+    }
+}
+
+impl ImageSource for ImageFiles {
+    fn capture(&mut self) -> Vec<u8> {
+        let frame = self.frames[self.next].clone();
+        self.next = (self.next + 1) % self.frames.len();
+        frame
+    }
+}
+
+pub struct PocketCamera {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+    registers: [u8; REGISTER_COUNT],
+    capture_cycles: Option<u32>,
+    source: Option<Box<dyn ImageSource>>,
+}
+
+impl PocketCamera {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> PocketCamera {
+        PocketCamera {
+            rom,
+            ram: vec![0; ram_size],
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            registers: [0; REGISTER_COUNT],
+            capture_cycles: None,
+            source: None,
+        }
+    }
+
+    fn registers_mapped(&self) -> bool {
+        self.ram_bank & 0x10 != 0
+    }
+
+    //Capture time in M-cycles, the sensor is read out for a fixed time plus the exposure
+    fn capture_time(&self) -> u32 {
+        let exposure = u16::from_be_bytes([self.registers[2], self.registers[3]]) as u32;
+        let n_time = if self.registers[1] & 0x80 != 0 {0} else {512};
+        32446 + n_time + 16 * exposure
+    }
+
+    fn finish_capture(&mut self) {
+        let sensor = match &mut self.source {
+            Some(source) => source.capture(),
+            None => vec![0x80; SENSOR_WIDTH * SENSOR_HEIGHT],
+        };
+        let tiles = self.process(&sensor);
+        if let Some(ram) = self.ram.get_mut(IMAGE_OFFSET..IMAGE_OFFSET + tiles.len()) {
+            ram.copy_from_slice(&tiles);
+        }
+        self.registers[0] &= !0x01;
+    }
+
+    //Run the sensor image through exposure, gain, edge enhancement and the threshold matrix,
+    //producing 2bpp tile data
+    fn process(&self, sensor: &[u8]) -> Vec<u8> {
+        let exposure = u16::from_be_bytes([self.registers[2], self.registers[3]]) as f32 / 0x1000 as f32;
+        //Roughly 1.5 dB per gain step
+        let gain = 2f32.powf((self.registers[1] & 0x1F) as f32 / 4.0);
+        let edge_ratio = [0.5, 0.75, 1.0, 1.25, 2.0, 3.0, 4.0, 5.0][(self.registers[4] >> 4) as usize & 0x07];
+        let edge_mode = (self.registers[1] >> 5) & 0x03;
+        let invert = self.registers[4] & 0x08 != 0;
+
+        let exposed: Vec<f32> = sensor.iter().map(|pixel| *pixel as f32 * exposure * gain).collect();
+        let at = |x: i32, y: i32| -> f32 {
+            let x = x.clamp(0, SENSOR_WIDTH as i32 - 1) as usize;
+            let y = y.clamp(0, SENSOR_HEIGHT as i32 - 1) as usize;
+            exposed[y * SENSOR_WIDTH + x]
+        };
+
+        let mut tiles = vec![0u8; SENSOR_WIDTH * SENSOR_HEIGHT / 4];
+        for y in 0..SENSOR_HEIGHT {
+            for x in 0..SENSOR_WIDTH {
+                let (ix, iy) = (x as i32, y as i32);
+                let mut value = at(ix, iy);
+                //Sharpen against the neighbours on the enabled axes
+                let (vertical, horizontal) = (at(ix, iy - 1) + at(ix, iy + 1), at(ix - 1, iy) + at(ix + 1, iy));
+                value += edge_ratio * match edge_mode {
+                    1 => 2.0 * value - vertical,
+                    2 => 2.0 * value - horizontal,
+                    3 => 4.0 * value - vertical - horizontal,
+                    _ => 0.0,
+                };
+                let mut value = value.clamp(0.0, 255.0) as u8;
+                if invert {
+                    value = 255 - value;
+                }
+
+                let matrix = MATRIX_START + ((y & 3) * 4 + (x & 3)) * 3;
+                let thresholds = &self.registers[matrix..matrix + 3];
+                let color = if value < thresholds[0] {3}
+                    else if value < thresholds[1] {2}
+                    else if value < thresholds[2] {1}
+                    else {0};
+
+                let offset = ((y / 8) * (SENSOR_WIDTH / 8) + x / 8) * 16 + (y % 8) * 2;
+                let bit = 7 - (x % 8);
+                tiles[offset] |= (color & 0x01) << bit;
+                tiles[offset + 1] |= (color >> 1) << bit;
+            }
+        }
+        tiles
+    }
+}
+
+impl Cartridge for PocketCamera {
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        rom_bank_read(&self.rom, bank, address)
+    }
+
+    fn write_rom(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = data & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = data & 0x3F,
+            0x4000..=0x5FFF => self.ram_bank = data & 0x1F,
+            _ => (),
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if self.registers_mapped() {
+            //Only the control register reads back
+            return match address & 0x7F {
+                0x00 => self.registers[0] & 0x07,
+                _ => 0x00,
+            }
+        }
+        match ram_bank_offset(&self.ram, self.ram_bank as usize & 0x0F, address) {
+            Some(offset) => self.ram[offset],
+            None => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, data: u8) {
+        if self.registers_mapped() {
+            let register = (address & 0x7F) as usize;
+            if register == 0x00 {
+                match (data & 0x01 != 0, self.capture_cycles.is_some()) {
+                    (true, false) => self.capture_cycles = Some(self.capture_time()),
+                    (false, true) => self.capture_cycles = None,
+                    _ => (),
+                }
+                self.registers[0] = data & 0x07;
+            }
+            else if register < REGISTER_COUNT {
+                self.registers[register] = data;
+            }
+            return
+        }
+        if !self.ram_enabled {
+            return
+        }
+        if let Some(offset) = ram_bank_offset(&self.ram, self.ram_bank as usize & 0x0F, address) {
+            self.ram[offset] = data;
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        if let Some(remaining) = self.capture_cycles {
+            if remaining <= cycles {
+                self.capture_cycles = None;
+                self.finish_capture();
+            }
+            else {
+                self.capture_cycles = Some(remaining - cycles);
+            }
+        }
+    }
+
+    fn set_image_source(&mut self, source: Box<dyn ImageSource>) {
+        self.source = Some(source);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Left half black, right half white
+    struct SplitImage;
+
+    impl ImageSource for SplitImage {
+        fn capture(&mut self) -> Vec<u8> {
+            (0..SENSOR_WIDTH * SENSOR_HEIGHT).map(|index| if index % SENSOR_WIDTH < SENSOR_WIDTH / 2 {0} else {255}).collect()
+        }
+    }
+
+    fn camera() -> PocketCamera {
+        let mut camera = PocketCamera::new(vec![0; 0x100000], 0x20000);
+        camera.set_image_source(Box::new(SplitImage));
+        camera.write_rom(0x4000, 0x10);
+        //Unity exposure and gain, same thresholds for every matrix cell
+        camera.write_ram(0xA002, 0x10);
+        camera.write_ram(0xA003, 0x00);
+        for cell in 0..16 {
+            camera.write_ram(0xA006 + cell * 3, 0x40);
+            camera.write_ram(0xA007 + cell * 3, 0x80);
+            camera.write_ram(0xA008 + cell * 3, 0xC0);
+        }
+        camera
+    }
+
+    #[test]
+    fn test_register_bank_select() {
+        let mut camera = PocketCamera::new(vec![0; 0x100000], 0x20000);
+        camera.write_ram(0xA000, 0x55);
+        assert_eq!(camera.read_ram(0xA000), 0x00);
+        //Ram reads don't need the enable, only writes
+        camera.write_rom(0x0000, 0x0A);
+        camera.write_ram(0xA000, 0x55);
+        camera.write_rom(0x0000, 0x00);
+        assert_eq!(camera.read_ram(0xA000), 0x55);
+
+        camera.write_rom(0x4000, 0x10);
+        camera.write_ram(0xA001, 0x1F);
+        assert_eq!(camera.read_ram(0xA001), 0x00);
+        assert_eq!(camera.registers[1], 0x1F);
+        camera.write_rom(0x4000, 0x00);
+        assert_eq!(camera.read_ram(0xA000), 0x55);
+    }
+
+    #[test]
+    fn test_capture_busy_until_done() {
+        let mut camera = camera();
+        camera.write_ram(0xA000, 0x01);
+        assert_eq!(camera.read_ram(0xA000), 0x01);
+        let time = camera.capture_time();
+        camera.tick(time - 1);
+        assert_eq!(camera.read_ram(0xA000), 0x01);
+        camera.tick(1);
+        assert_eq!(camera.read_ram(0xA000), 0x00);
+    }
+
+    #[test]
+    fn test_capture_dithers_into_tiles() {
+        let mut camera = camera();
+        camera.write_ram(0xA000, 0x01);
+        camera.tick(camera.capture_time());
+
+        //First tile is black, both bit planes set
+        assert_eq!(camera.ram[IMAGE_OFFSET], 0xFF);
+        assert_eq!(camera.ram[IMAGE_OFFSET + 1], 0xFF);
+        //Last tile of the first row is white
+        assert_eq!(camera.ram[IMAGE_OFFSET + 15 * 16], 0x00);
+        assert_eq!(camera.ram[IMAGE_OFFSET + 15 * 16 + 1], 0x00);
+
+        //Half the exposure drops white below the middle threshold, color 2
+        camera.write_ram(0xA002, 0x08);
+        camera.write_ram(0xA000, 0x01);
+        camera.tick(camera.capture_time());
+        assert_eq!(camera.ram[IMAGE_OFFSET + 15 * 16], 0x00);
+        assert_eq!(camera.ram[IMAGE_OFFSET + 15 * 16 + 1], 0xFF);
+    }
+
+    #[test]
+    fn test_image_sequence_cycles() {
+        let directory = std::env::temp_dir().join(format!("rusty-camera-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("a.pgm"), b"P2 1 1 255 0").unwrap();
+        fs::write(directory.join("b.pgm"), b"P2 1 1 255 255").unwrap();
+        fs::write(directory.join("notes.txt"), b"ignored").unwrap();
+        //Images that don't decode are skipped, not fatal
+        fs::write(directory.join("c.pnm"), b"P4 1 1\n\x80").unwrap();
+        fs::write(directory.join("d.bmp"), b"BM").unwrap();
+
+        let mut files = ImageFiles::open(&directory).unwrap();
+        assert_eq!(files.capture()[0], 0);
+        assert_eq!(files.capture()[0], 255);
+        assert_eq!(files.capture()[0], 0);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_single_bad_image_fails() {
+        let path = std::env::temp_dir().join(format!("rusty-camera-{}.pbm", std::process::id()));
+        fs::write(&path, b"P1 1 1 1").unwrap();
+        assert!(ImageFiles::open(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,217 @@
+//93LC56 serial EEPROM, 128 16-bit words, as wired to the MBC7
+//Commands are clocked in on DI on the rising edge of CLK while CS is high:
+//a start bit, a 2 bit opcode and an 8 bit address (the top bit is ignored)
+//10 READ  - DO shifts out a dummy 0, then words from the address onward, MSB first
+//01 WRITE - 16 data bits follow
+//11 ERASE - word is set to FFFF
+//00 with the top 2 address bits selecting
+//   11 EWEN enable writes, 00 EWDS disable writes, 10 ERAL erase all, 01 WRAL write 16 data bits to every word
+//Writes and erases are ignored until EWEN, which is reset on power up
+pub const EEPROM_SIZE: usize = 0x100;
+const WORD_COUNT: u8 = 0x80;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum EepromState {
+    Idle,
+    Command,
+    Read { address: u8, value: u16, bits: u8 },
+    Write { address: Option<u8> },
+    Done,
+}
+
+pub struct Eeprom {
+    pub data: [u8; EEPROM_SIZE],
+    cs: bool,
+    clk: bool,
+    di: bool,
+    output: bool,
+    write_enabled: bool,
+    shift: u16,
+    bit_count: u8,
+    state: EepromState,
+}
+
+impl Eeprom {
+    pub fn new() -> Eeprom {
+        Eeprom {
+            data: [0xFF; EEPROM_SIZE],
+            cs: false,
+            clk: false,
+            di: false,
+            output: true,
+            write_enabled: false,
+            shift: 0,
+            bit_count: 0,
+            state: EepromState::Idle,
+        }
+    }
+
+    //Words are stored little endian, matching the .sav layout other emulators use
+    fn word(&self, address: u8) -> u16 {
+        let offset = (address % WORD_COUNT) as usize * 2;
+        u16::from_le_bytes([self.data[offset], self.data[offset + 1]])
+    }
+
+    fn set_word(&mut self, address: u8, value: u16) {
+        let offset = (address % WORD_COUNT) as usize * 2;
+        self.data[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    //Bit 7 CS, bit 6 CLK, bit 1 DI, bit 0 DO
+    pub fn read(&self) -> u8 {
+        (self.cs as u8) << 7 | (self.clk as u8) << 6 | (self.di as u8) << 1 | self.output as u8
+    }
+
+    pub fn write(&mut self, data: u8) {
+        let cs = data & 0x80 != 0;
+        let clk = data & 0x40 != 0;
+        self.di = data & 0x02 != 0;
+
+        if !cs {
+            //Dropping CS ends the command, DO then shows ready
+            self.state = EepromState::Idle;
+            self.output = true;
+        }
+        else if clk && !self.clk && self.cs {
+            self.clock_in(self.di);
+        }
+        self.cs = cs;
+        self.clk = clk;
+    }
+
+    fn clock_in(&mut self, bit: bool) {
+        match self.state {
+            EepromState::Idle => {
+                if bit {
+                    self.state = EepromState::Command;
+                    self.shift = 0;
+                    self.bit_count = 0;
+                }
+            }
+            EepromState::Command => {
+                self.shift = self.shift << 1 | bit as u16;
+                self.bit_count += 1;
+                if self.bit_count == 10 {
+                    self.run_command((self.shift >> 8) as u8 & 0x03, self.shift as u8);
+                }
+            }
+            EepromState::Read { address, value, bits } => {
+                self.output = value & (0x8000 >> bits) != 0;
+                self.state = if bits == 15 {
+                    //Sequential reads carry on into the next word
+                    let next = (address + 1) % WORD_COUNT;
+                    EepromState::Read { address: next, value: self.word(next), bits: 0 }
+                }
+                else {
+                    EepromState::Read { address, value, bits: bits + 1 }
+                };
+            }
+            EepromState::Write { address } => {
+                self.shift = self.shift << 1 | bit as u16;
+                self.bit_count += 1;
+                if self.bit_count == 16 {
+                    if self.write_enabled {
+                        match address {
+                            Some(address) => self.set_word(address, self.shift),
+                            None => (0..WORD_COUNT).for_each(|address| self.set_word(address, self.shift)),
+                        }
+                    }
+                    self.state = EepromState::Done;
+                }
+            }
+            EepromState::Done => (),
+        }
+    }
+
+    fn run_command(&mut self, opcode: u8, address_bits: u8) {
+        let address = address_bits & 0x7F;
+        self.shift = 0;
+        self.bit_count = 0;
+        self.state = EepromState::Done;
+        match opcode {
+            0b10 => {
+                self.output = false;
+                self.state = EepromState::Read { address, value: self.word(address), bits: 0 };
+            }
+            0b01 => self.state = EepromState::Write { address: Some(address) },
+            0b11 => {
+                if self.write_enabled {
+                    self.set_word(address, 0xFFFF);
+                }
+            }
+            _ => match address_bits >> 6 {
+                0b11 => self.write_enabled = true,
+                0b00 => self.write_enabled = false,
+                0b10 => {
+                    if self.write_enabled {
+                        self.data = [0xFF; EEPROM_SIZE];
+                    }
+                }
+                _ => self.state = EepromState::Write { address: None },
+            },
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn send_bits(eeprom: &mut Eeprom, value: u32, count: u8) {
+        for bit in (0..count).rev() {
+            let di = if value & (1 << bit) != 0 {0x02} else {0x00};
+            eeprom.write(0x80 | di);
+            eeprom.write(0xC0 | di);
+        }
+    }
+
+    fn command(eeprom: &mut Eeprom, value: u32, count: u8) {
+        eeprom.write(0x00);
+        send_bits(eeprom, value, count);
+    }
+
+    fn read_word(eeprom: &mut Eeprom, address: u8) -> u16 {
+        command(eeprom, 0b110 << 8 | address as u32, 11);
+        assert_eq!(eeprom.read() & 0x01, 0, "dummy bit");
+        let mut value = 0;
+        for _ in 0..16 {
+            send_bits(eeprom, 0, 1);
+            value = value << 1 | (eeprom.read() & 0x01) as u16;
+        }
+        value
+    }
+
+    #[test]
+    fn test_write_needs_enable() {
+        let mut eeprom = Eeprom::new();
+        command(&mut eeprom, 0b101 << 24 | 0x05 << 16 | 0x1234, 27);
+        assert_eq!(read_word(&mut eeprom, 0x05), 0xFFFF);
+
+        command(&mut eeprom, 0b100_1100_0000, 11);
+        command(&mut eeprom, 0b101 << 24 | 0x05 << 16 | 0x1234, 27);
+        assert_eq!(read_word(&mut eeprom, 0x05), 0x1234);
+        assert_eq!(&eeprom.data[0x0A..0x0C], &[0x34, 0x12]);
+
+        //Erase puts the word back to FFFF
+        command(&mut eeprom, 0b111 << 8 | 0x05, 11);
+        assert_eq!(read_word(&mut eeprom, 0x05), 0xFFFF);
+    }
+
+    #[test]
+    fn test_write_all_and_sequential_read() {
+        let mut eeprom = Eeprom::new();
+        command(&mut eeprom, 0b100_1100_0000, 11);
+        command(&mut eeprom, 0b100_0100_0000 << 16 | 0xABCD, 27);
+        assert_eq!(read_word(&mut eeprom, 0x7F), 0xABCD);
+
+        eeprom.set_word(0x11, 0x1111);
+        //Reading past the first word keeps going without a new command
+        let mut value = read_word(&mut eeprom, 0x10) as u32;
+        for _ in 0..16 {
+            send_bits(&mut eeprom, 0, 1);
+            value = value << 1 | (eeprom.read() & 0x01) as u32;
+        }
+        assert_eq!(value, 0xABCD_1111);
+    }
+}
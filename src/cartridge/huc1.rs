@@ -0,0 +1,130 @@
+use super::{Cartridge, rom_bank_read, ram_bank_offset};
+use super::infrared::{Infrared, read_ir, write_ir};
+
+//HuC1 registers
+//0000-1FFF A000-BFFF mode - 0x0E maps the IR port, anything else maps ram
+//2000-3FFF 6 bit rom bank number, 0 is treated as 1
+//4000-5FFF 2 bit ram bank number
+//There is no ram enable, ram is always accessible in ram mode
+pub struct HuC1 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ir_mode: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+    infrared: Option<Box<dyn Infrared>>,
+}
+
+impl HuC1 {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> HuC1 {
+        HuC1 {
+            rom,
+            ram: vec![0; ram_size],
+            ir_mode: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            infrared: None,
+        }
+    }
+}
+
+impl Cartridge for HuC1 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        rom_bank_read(&self.rom, bank, address)
+    }
+
+    fn write_rom(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ir_mode = data & 0x0F == 0x0E,
+            0x2000..=0x3FFF => {
+                self.rom_bank = data & 0x3F;
+                if self.rom_bank == 0 {
+                    self.rom_bank = 1;
+                }
+            }
+            0x4000..=0x5FFF => self.ram_bank = data & 0x03,
+            _ => (),
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if self.ir_mode {
+            return read_ir(&self.infrared)
+        }
+        match ram_bank_offset(&self.ram, self.ram_bank as usize, address) {
+            Some(offset) => self.ram[offset],
+            None => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, data: u8) {
+        if self.ir_mode {
+            write_ir(&mut self.infrared, data);
+            return
+        }
+        if let Some(offset) = ram_bank_offset(&self.ram, self.ram_bank as usize, address) {
+            self.ram[offset] = data;
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn set_infrared(&mut self, port: Box<dyn Infrared>) {
+        self.infrared = Some(port);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::banked_rom;
+    use crate::cartridge::infrared::InfraredPort;
+
+    #[test]
+    fn test_banking() {
+        let mut huc1 = HuC1::new(banked_rom(64), 0x8000);
+        huc1.write_rom(0x2000, 0x3F);
+        assert_eq!(huc1.read_rom(0x4000), 0x3F);
+        huc1.write_rom(0x2000, 0x00);
+        assert_eq!(huc1.read_rom(0x4000), 0x01);
+
+        huc1.write_rom(0x4000, 0x02);
+        huc1.write_ram(0xA000, 0x42);
+        huc1.write_rom(0x4000, 0x00);
+        assert_eq!(huc1.read_ram(0xA000), 0x00);
+        huc1.write_rom(0x4000, 0x02);
+        assert_eq!(huc1.read_ram(0xA000), 0x42);
+    }
+
+    #[test]
+    fn test_ir_between_two_carts() {
+        let (a, b) = InfraredPort::pair();
+        let mut sender = HuC1::new(banked_rom(2), 0x2000);
+        let mut receiver = HuC1::new(banked_rom(2), 0x2000);
+        sender.set_infrared(Box::new(a));
+        receiver.set_infrared(Box::new(b));
+        sender.write_rom(0x0000, 0x0E);
+        receiver.write_rom(0x0000, 0x0E);
+
+        assert_eq!(receiver.read_ram(0xA000), 0xC0);
+        sender.write_ram(0xA000, 0x01);
+        assert_eq!(receiver.read_ram(0xA000), 0xC1);
+        sender.write_ram(0xA000, 0x00);
+        assert_eq!(receiver.read_ram(0xA000), 0xC0);
+
+        //Switching back to ram mode leaves ram untouched by IR writes
+        sender.write_rom(0x0000, 0x0A);
+        assert_eq!(sender.read_ram(0xA000), 0x00);
+    }
+}
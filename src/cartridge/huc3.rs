@@ -0,0 +1,331 @@
+use std::collections::VecDeque;
+use super::{Cartridge, CartridgeEvent, rom_bank_read, ram_bank_offset};
+use super::infrared::{Infrared, read_ir, write_ir};
+use super::rtc::Clock;
+
+//HuC3 registers
+//0000-1FFF A000-BFFF mode
+//          0x0 ram, read only
+//          0xA ram, read/write
+//          0xB write an rtc command
+//          0xC read the result of the last rtc command
+//          0xD rtc ready flag, commands complete instantly so this always reads 1
+//          0xE IR port
+//2000-3FFF 7 bit rom bank number, 0 is treated as 1
+//4000-5FFF 2 bit ram bank number
+//RTC commands are a command nibble and an argument nibble, working on 256 nibbles of clock memory
+//1x Read the nibble at the address into the result, then increment the address
+//3x Write x at the address, then increment the address
+//4x Set the low nibble of the address
+//5x Set the high nibble of the address
+//6x Extended - 0 copies the clock into memory 00-05, 1 sets the clock from memory 00-05,
+//   2 status check which reads back 1, E beeps the speaker with the tone stored at 26
+//The clock is minute of the day (00-02) and a day counter (03-05), 12 bits each, low nibble first
+
+//Clock block appended to .sav files, u64 seconds counter and u64 unix timestamp, little endian
+pub const HUC3_RTC_SAVE_SIZE: usize = 16;
+const DAYS: u64 = 0x1000;
+
+pub struct HuC3Clock {
+    clock: Box<dyn Clock>,
+    seconds: u64,
+    last_update: u64,
+}
+
+impl HuC3Clock {
+    pub fn new(clock: Box<dyn Clock>) -> HuC3Clock {
+        let last_update = clock.now();
+        HuC3Clock {
+            clock,
+            seconds: 0,
+            last_update,
+        }
+    }
+
+    pub fn update(&mut self) {
+        let now = self.clock.now();
+        if now > self.last_update {
+            self.seconds = (self.seconds + now - self.last_update) % (DAYS * 86400);
+        }
+        self.last_update = now;
+    }
+
+    pub fn minutes(&self) -> u16 {
+        (self.seconds / 60 % 1440) as u16
+    }
+
+    pub fn days(&self) -> u16 {
+        (self.seconds / 86400) as u16
+    }
+
+    //Setting the clock restarts the current minute
+    pub fn set(&mut self, minutes: u16, days: u16) {
+        self.update();
+        self.seconds = (days as u64 % DAYS) * 86400 + (minutes as u64 % 1440) * 60;
+    }
+
+    pub fn save(&mut self) -> Vec<u8> {
+        self.update();
+        let mut data = Vec::with_capacity(HUC3_RTC_SAVE_SIZE);
+        data.extend_from_slice(&self.seconds.to_le_bytes());
+        data.extend_from_slice(&self.last_update.to_le_bytes());
+        data
+    }
+
+    pub fn load(&mut self, data: &[u8]) {
+        if data.len() < HUC3_RTC_SAVE_SIZE {
+            return
+        }
+        let mut seconds = [0u8; 8];
+        let mut timestamp = [0u8; 8];
+        seconds.copy_from_slice(&data[0..8]);
+        timestamp.copy_from_slice(&data[8..16]);
+        self.seconds = u64::from_le_bytes(seconds) % (DAYS * 86400);
+        //Time spent with the emulator closed is added on the next update
+        self.last_update = u64::from_le_bytes(timestamp);
+        self.update();
+    }
+}
+
+pub struct HuC3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    mode: u8,
+    rom_bank: u8,
+    ram_bank: u8,
+    clock: HuC3Clock,
+    clock_memory: [u8; 0x100],
+    address: u8,
+    command: u8,
+    result: u8,
+    infrared: Option<Box<dyn Infrared>>,
+    events: VecDeque<CartridgeEvent>,
+}
+
+impl HuC3 {
+    pub fn new(rom: Vec<u8>, ram_size: usize, clock: Box<dyn Clock>) -> HuC3 {
+        HuC3 {
+            rom,
+            ram: vec![0; ram_size],
+            mode: 0,
+            rom_bank: 1,
+            ram_bank: 0,
+            clock: HuC3Clock::new(clock),
+            clock_memory: [0; 0x100],
+            address: 0,
+            command: 0,
+            result: 0,
+            infrared: None,
+            events: VecDeque::new(),
+        }
+    }
+
+    fn run_command(&mut self, command: u8) {
+        self.command = command;
+        let argument = command & 0x0F;
+        match command >> 4 {
+            0x1 => {
+                self.result = self.clock_memory[self.address as usize];
+                self.address = self.address.wrapping_add(1);
+            }
+            0x3 => {
+                self.clock_memory[self.address as usize] = argument;
+                self.address = self.address.wrapping_add(1);
+            }
+            0x4 => self.address = (self.address & 0xF0) | argument,
+            0x5 => self.address = (self.address & 0x0F) | (argument << 4),
+            0x6 => self.run_extended(argument),
+            _ => (),
+        }
+    }
+
+    fn run_extended(&mut self, argument: u8) {
+        match argument {
+            0x0 => {
+                self.clock.update();
+                let (minutes, days) = (self.clock.minutes(), self.clock.days());
+                for nibble in 0..3 {
+                    self.clock_memory[nibble] = (minutes >> (nibble * 4)) as u8 & 0x0F;
+                    self.clock_memory[nibble + 3] = (days >> (nibble * 4)) as u8 & 0x0F;
+                }
+            }
+            0x1 => {
+                let nibbles = |start: usize| -> u16 {
+                    (0..3).map(|nibble| (self.clock_memory[start + nibble] as u16) << (nibble * 4)).sum()
+                };
+                let (minutes, days) = (nibbles(0), nibbles(3));
+                self.clock.set(minutes, days);
+            }
+            0x2 => self.result = 0x01,
+            0xE => self.events.push_back(CartridgeEvent::Tone(self.clock_memory[0x26])),
+            _ => (),
+        }
+    }
+}
+
+impl Cartridge for HuC3 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        rom_bank_read(&self.rom, bank, address)
+    }
+
+    fn write_rom(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000..=0x1FFF => self.mode = data & 0x0F,
+            0x2000..=0x3FFF => {
+                self.rom_bank = data & 0x7F;
+                if self.rom_bank == 0 {
+                    self.rom_bank = 1;
+                }
+            }
+            0x4000..=0x5FFF => self.ram_bank = data & 0x03,
+            _ => (),
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        match self.mode {
+            0x0 | 0xA => {
+                match ram_bank_offset(&self.ram, self.ram_bank as usize, address) {
+                    Some(offset) => self.ram[offset],
+                    None => 0xFF,
+                }
+            }
+            0xC => (self.command & 0xF0) | self.result,
+            0xD => 0xFF,
+            0xE => read_ir(&self.infrared),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, data: u8) {
+        match self.mode {
+            0xA => {
+                if let Some(offset) = ram_bank_offset(&self.ram, self.ram_bank as usize, address) {
+                    self.ram[offset] = data;
+                }
+            }
+            0xB => self.run_command(data),
+            0xE => write_ir(&mut self.infrared, data),
+            _ => (),
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn save_rtc(&mut self) -> Option<Vec<u8>> {
+        Some(self.clock.save())
+    }
+
+    fn load_rtc(&mut self, data: &[u8]) {
+        self.clock.load(data);
+    }
+
+    fn poll_event(&mut self) -> Option<CartridgeEvent> {
+        self.events.pop_front()
+    }
+
+    fn set_infrared(&mut self, port: Box<dyn Infrared>) {
+        self.infrared = Some(port);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::infrared::InfraredPort;
+    use crate::cartridge::rtc::FakeClock;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    fn fake_huc3(start: u64) -> (HuC3, Rc<Cell<u64>>) {
+        let time = Rc::new(Cell::new(start));
+        let huc3 = HuC3::new(vec![0; 0x8000], 0x8000, Box::new(FakeClock { time: time.clone() }));
+        (huc3, time)
+    }
+
+    //Read 3 nibbles starting at address through the command interface
+    fn read_value(huc3: &mut HuC3, address: u8) -> u16 {
+        huc3.write_rom(0x0000, 0x0B);
+        huc3.write_ram(0xA000, 0x40 | (address & 0x0F));
+        huc3.write_ram(0xA000, 0x50 | (address >> 4));
+        let mut value = 0;
+        for nibble in 0..3 {
+            huc3.write_rom(0x0000, 0x0B);
+            huc3.write_ram(0xA000, 0x10);
+            huc3.write_rom(0x0000, 0x0C);
+            value |= ((huc3.read_ram(0xA000) & 0x0F) as u16) << (nibble * 4);
+        }
+        value
+    }
+
+    #[test]
+    fn test_clock_read_through_commands() {
+        let (mut huc3, time) = fake_huc3(0);
+        time.set(86400 * 3 + 60 * 125 + 30);
+        huc3.write_rom(0x0000, 0x0B);
+        huc3.write_ram(0xA000, 0x60);
+        assert_eq!(read_value(&mut huc3, 0x00), 125);
+        assert_eq!(read_value(&mut huc3, 0x03), 3);
+
+        huc3.write_rom(0x0000, 0x0D);
+        assert_eq!(huc3.read_ram(0xA000) & 0x01, 0x01);
+    }
+
+    #[test]
+    fn test_clock_set_and_persist() {
+        let (mut huc3, _) = fake_huc3(1000);
+        //Write minute 0x123 and day 0x002
+        huc3.write_rom(0x0000, 0x0B);
+        for command in [0x40, 0x50, 0x33, 0x32, 0x31, 0x32, 0x30, 0x30, 0x61].iter() {
+            huc3.write_ram(0xA000, *command);
+        }
+        let saved = huc3.save_rtc().unwrap();
+        assert_eq!(saved.len(), HUC3_RTC_SAVE_SIZE);
+
+        //Emulator closed for an hour
+        let (mut loaded, _) = fake_huc3(1000 + 3600);
+        loaded.load_rtc(&saved);
+        loaded.write_rom(0x0000, 0x0B);
+        loaded.write_ram(0xA000, 0x60);
+        assert_eq!(read_value(&mut loaded, 0x00), 0x123 + 60);
+        assert_eq!(read_value(&mut loaded, 0x03), 2);
+    }
+
+    #[test]
+    fn test_ram_modes() {
+        let (mut huc3, _) = fake_huc3(0);
+        huc3.write_rom(0x0000, 0x0A);
+        huc3.write_ram(0xA000, 0x12);
+        //Mode 0 reads ram but ignores writes
+        huc3.write_rom(0x0000, 0x00);
+        huc3.write_ram(0xA000, 0x34);
+        assert_eq!(huc3.read_ram(0xA000), 0x12);
+    }
+
+    #[test]
+    fn test_ir_loopback_and_speaker() {
+        let (mut huc3, _) = fake_huc3(0);
+        huc3.set_infrared(Box::new(InfraredPort::loopback()));
+        huc3.write_rom(0x0000, 0x0E);
+        assert_eq!(huc3.read_ram(0xA000), 0xC0);
+        huc3.write_ram(0xA000, 0x01);
+        assert_eq!(huc3.read_ram(0xA000), 0xC1);
+
+        huc3.write_rom(0x0000, 0x0B);
+        for command in [0x46, 0x52, 0x33, 0x6E].iter() {
+            huc3.write_ram(0xA000, *command);
+        }
+        assert_eq!(huc3.poll_event(), Some(CartridgeEvent::Tone(3)));
+    }
+}
@@ -0,0 +1,78 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+//Infrared LED and receiver, implemented by anything a cartridge can talk IR to
+pub trait Infrared {
+    fn set_led(&mut self, on: bool);
+    //Whether light is reaching our receiver
+    fn receiving(&self) -> bool;
+}
+
+//One end of an IR link, either facing another port or reflecting back into itself
+pub struct InfraredPort {
+    led: Rc<Cell<bool>>,
+    receiver: Rc<Cell<bool>>,
+}
+
+impl InfraredPort {
+    pub fn loopback() -> InfraredPort {
+        let light = Rc::new(Cell::new(false));
+        InfraredPort { led: light.clone(), receiver: light }
+    }
+
+    //Each port's LED shines into the other's receiver
+    #[cfg(test)]
+    pub fn pair() -> (InfraredPort, InfraredPort) {
+        let (a, b) = (Rc::new(Cell::new(false)), Rc::new(Cell::new(false)));
+        (InfraredPort { led: a.clone(), receiver: b.clone() }, InfraredPort { led: b, receiver: a })
+    }
+}
+
+impl Infrared for InfraredPort {
+    fn set_led(&mut self, on: bool) {
+        self.led.set(on);
+    }
+
+    fn receiving(&self) -> bool {
+        self.receiver.get()
+    }
+}
+
+//IR register as HuC carts expose it, bit 0 reads the receiver and writes the LED
+pub fn read_ir(port: &Option<Box<dyn Infrared>>) -> u8 {
+    match port {
+        Some(port) if port.receiving() => 0xC1,
+        _ => 0xC0,
+    }
+}
+
+pub fn write_ir(port: &mut Option<Box<dyn Infrared>>, data: u8) {
+    if let Some(port) = port {
+        port.set_led(data & 0x01 != 0);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pair_links_both_ways() {
+        let (mut a, mut b) = InfraredPort::pair();
+        a.set_led(true);
+        assert!(b.receiving());
+        assert!(!a.receiving());
+        a.set_led(false);
+        b.set_led(true);
+        assert!(a.receiving());
+        assert!(!b.receiving());
+    }
+
+    #[test]
+    fn test_loopback() {
+        let mut port = InfraredPort::loopback();
+        port.set_led(true);
+        assert!(port.receiving());
+    }
+}
@@ -0,0 +1,181 @@
+use super::{Cartridge, rom_bank_read, ram_bank_offset};
+
+//MBC1 registers
+//0000-1FFF RAM enable - 0x0A in lower nibble enables
+//2000-3FFF BANK1 - 5 bit rom bank number, 0 is treated as 1
+//4000-5FFF BANK2 - 2 bit ram bank number, or bits 5-6 of the rom bank on 1 MiB+ carts
+//6000-7FFF Mode - 0 maps bank 0 at 0000-3FFF and ram bank 0,
+//          1 applies BANK2 to 0000-3FFF and to ram as well
+//MBC1M multicarts wire BANK2 to bits 4-5 instead, so only 4 bits of BANK1 are used
+pub struct Mbc1 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    bank1: u8,
+    bank2: u8,
+    mode: bool,
+    multicart: bool,
+}
+
+impl Mbc1 {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> Mbc1 {
+        let multicart = Mbc1::is_multicart(&rom);
+        Mbc1 {
+            rom,
+            ram: vec![0; ram_size],
+            ram_enabled: false,
+            bank1: 1,
+            bank2: 0,
+            mode: false,
+            multicart,
+        }
+    }
+
+    //MBC1M carts are 1 MiB and each 256 KiB game has its own header,
+    //so the Nintendo logo shows up again at the start of bank 0x10
+    fn is_multicart(rom: &[u8]) -> bool {
+        rom.len() == 0x100000 && rom[0x104..0x134] == rom[0x40104..0x40134]
+    }
+
+    fn bank2_shift(&self) -> u8 {
+        if self.multicart {4} else {5}
+    }
+
+    fn bank1_mask(&self) -> u8 {
+        if self.multicart {0x0F} else {0x1F}
+    }
+
+    fn ram_bank(&self) -> usize {
+        if self.mode {self.bank2 as usize} else {0}
+    }
+}
+
+impl Cartridge for Mbc1 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let upper = (self.bank2 as usize) << self.bank2_shift();
+        let bank = match address {
+            0x0000..=0x3FFF => if self.mode {upper} else {0},
+            _ => upper | (self.bank1 & self.bank1_mask()) as usize,
+        };
+        rom_bank_read(&self.rom, bank, address)
+    }
+
+    fn write_rom(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = data & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                self.bank1 = data & 0x1F;
+                //Zero check is done on all 5 bits, even on multicarts
+                if self.bank1 == 0 {
+                    self.bank1 = 1;
+                }
+            }
+            0x4000..=0x5FFF => self.bank2 = data & 0x03,
+            _ => self.mode = data & 0x01 == 0x01,
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF
+        }
+        match ram_bank_offset(&self.ram, self.ram_bank(), address) {
+            Some(offset) => self.ram[offset],
+            None => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, data: u8) {
+        if !self.ram_enabled {
+            return
+        }
+        if let Some(offset) = ram_bank_offset(&self.ram, self.ram_bank(), address) {
+            self.ram[offset] = data;
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::banked_rom;
+
+    #[test]
+    fn test_rom_bank_select() {
+        let mut mbc = Mbc1::new(banked_rom(32), 0);
+        assert_eq!(mbc.read_rom(0x4000), 1);
+        mbc.write_rom(0x2000, 0x05);
+        assert_eq!(mbc.read_rom(0x4000), 5);
+        mbc.write_rom(0x2000, 0x00);
+        assert_eq!(mbc.read_rom(0x4000), 1);
+        //Upper bits past the rom size are ignored
+        mbc.write_rom(0x2000, 0xE3);
+        assert_eq!(mbc.read_rom(0x4000), 3);
+        //Writes never change rom
+        assert_eq!(mbc.read_rom(0x0000), 0);
+    }
+
+    #[test]
+    fn test_large_rom_banking() {
+        let mut mbc = Mbc1::new(banked_rom(128), 0);
+        mbc.write_rom(0x2000, 0x00);
+        mbc.write_rom(0x4000, 0x01);
+        //Bank 0x20 can't be selected directly, 0 -> 1 happens before BANK2 is applied
+        assert_eq!(mbc.read_rom(0x4000), 0x21);
+        mbc.write_rom(0x2000, 0x04);
+        mbc.write_rom(0x4000, 0x03);
+        assert_eq!(mbc.read_rom(0x4000), 0x64);
+        //Mode 1 maps BANK2 into 0000-3FFF too
+        assert_eq!(mbc.read_rom(0x0000), 0x00);
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_rom(0x0000), 0x60);
+    }
+
+    #[test]
+    fn test_ram_enable_and_banking() {
+        let mut mbc = Mbc1::new(banked_rom(4), 0x8000);
+        mbc.write_ram(0xA000, 0x12);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_ram(0xA000, 0x12);
+        assert_eq!(mbc.read_ram(0xA000), 0x12);
+
+        //Ram banking only applies in mode 1
+        mbc.write_rom(0x4000, 0x02);
+        assert_eq!(mbc.read_ram(0xA000), 0x12);
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_ram(0xA000), 0x00);
+        mbc.write_ram(0xA000, 0x34);
+        mbc.write_rom(0x6000, 0x00);
+        assert_eq!(mbc.read_ram(0xA000), 0x12);
+
+        mbc.write_rom(0x0000, 0x00);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn test_multicart() {
+        let mut rom = banked_rom(64);
+        for offset in 0x104..0x134 {
+            rom[offset] = 0xCE;
+            rom[0x40000 + offset] = 0xCE;
+        }
+        let mut mbc = Mbc1::new(rom, 0);
+        assert!(mbc.multicart);
+        mbc.write_rom(0x4000, 0x01);
+        mbc.write_rom(0x2000, 0x12);
+        assert_eq!(mbc.read_rom(0x4000), 0x12);
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_rom(0x0000), 0x10);
+    }
+}
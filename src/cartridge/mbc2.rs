@@ -0,0 +1,112 @@
+use super::{Cartridge, rom_bank_read};
+
+//MBC2 has one register range at 0000-3FFF, split by address bit 8
+//Bit 8 clear - RAM enable, 0x0A in lower nibble enables
+//Bit 8 set   - 4 bit rom bank number, 0 is treated as 1
+//Built in ram is 512 half-bytes at A000-A1FF, echoed through the rest of A000-BFFF
+//Only the lower nibble is stored, the upper nibble reads back as 1s
+pub const MBC2_RAM_SIZE: usize = 0x200;
+
+pub struct Mbc2 {
+    rom: Vec<u8>,
+    ram: [u8; MBC2_RAM_SIZE],
+    ram_enabled: bool,
+    rom_bank: u8,
+}
+
+impl Mbc2 {
+    pub fn new(rom: Vec<u8>) -> Mbc2 {
+        Mbc2 {
+            rom,
+            ram: [0; MBC2_RAM_SIZE],
+            ram_enabled: false,
+            rom_bank: 1,
+        }
+    }
+}
+
+impl Cartridge for Mbc2 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        rom_bank_read(&self.rom, bank, address)
+    }
+
+    fn write_rom(&mut self, address: u16, data: u8) {
+        if address > 0x3FFF {
+            return
+        }
+        if address & 0x0100 == 0 {
+            self.ram_enabled = data & 0x0F == 0x0A;
+        }
+        else {
+            self.rom_bank = data & 0x0F;
+            if self.rom_bank == 0 {
+                self.rom_bank = 1;
+            }
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF
+        }
+        self.ram[address as usize & (MBC2_RAM_SIZE - 1)] | 0xF0
+    }
+
+    fn write_ram(&mut self, address: u16, data: u8) {
+        if self.ram_enabled {
+            self.ram[address as usize & (MBC2_RAM_SIZE - 1)] = data & 0x0F;
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::banked_rom;
+
+    #[test]
+    fn test_address_bit_8_decoding() {
+        let mut mbc = Mbc2::new(banked_rom(16));
+        //Bit 8 clear, treated as ram enable even with a bank-like value
+        mbc.write_rom(0x2000, 0x05);
+        assert_eq!(mbc.read_rom(0x4000), 1);
+        mbc.write_rom(0x2100, 0x05);
+        assert_eq!(mbc.read_rom(0x4000), 5);
+        mbc.write_rom(0x0100, 0x00);
+        assert_eq!(mbc.read_rom(0x4000), 1);
+
+        mbc.write_rom(0x3E00, 0x0A);
+        assert!(mbc.ram_enabled);
+        //Writes above 3FFF don't reach the register
+        mbc.write_rom(0x4000, 0x00);
+        assert!(mbc.ram_enabled);
+    }
+
+    #[test]
+    fn test_half_byte_ram_echo() {
+        let mut mbc = Mbc2::new(banked_rom(2));
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_ram(0xA001, 0xAB);
+        assert_eq!(mbc.read_ram(0xA001), 0xFB);
+        assert_eq!(mbc.read_ram(0xA201), 0xFB);
+        assert_eq!(mbc.read_ram(0xBE01), 0xFB);
+        mbc.write_ram(0xBFFF, 0x03);
+        assert_eq!(mbc.read_ram(0xA1FF), 0xF3);
+
+        mbc.write_rom(0x0000, 0x00);
+        assert_eq!(mbc.read_ram(0xA001), 0xFF);
+    }
+}
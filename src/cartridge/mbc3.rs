@@ -0,0 +1,165 @@
+use super::{Cartridge, rom_bank_read, ram_bank_offset};
+use super::rtc::{Clock, Rtc};
+
+//MBC3 registers
+//0000-1FFF RAM and timer enable - 0x0A in lower nibble enables
+//2000-3FFF 7 bit rom bank number, 0 is treated as 1
+//4000-5FFF 0x00-0x03 selects a ram bank, 0x08-0x0C maps an rtc register into A000-BFFF
+//6000-7FFF Writing 0x00 then 0x01 latches the rtc
+//MBC30 (4 MiB rom, 64 KiB ram) widens the rom bank to 8 bits and the ram bank to 3 bits
+pub struct Mbc3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    rtc: Option<Rtc>,
+    ram_enabled: bool,
+    rom_bank: u8,
+    ram_select: u8,
+    mbc30: bool,
+}
+
+impl Mbc3 {
+    pub fn new(rom: Vec<u8>, ram_size: usize, clock: Option<Box<dyn Clock>>) -> Mbc3 {
+        let mbc30 = rom.len() > 0x200000 || ram_size > 0x8000;
+        Mbc3 {
+            rom,
+            ram: vec![0; ram_size],
+            rtc: clock.map(Rtc::new),
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_select: 0,
+            mbc30,
+        }
+    }
+
+    fn rtc_selected(&self) -> bool {
+        (0x08..=0x0C).contains(&self.ram_select)
+    }
+
+    fn ram_bank(&self) -> usize {
+        (self.ram_select & if self.mbc30 {0x07} else {0x03}) as usize
+    }
+}
+
+impl Cartridge for Mbc3 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        rom_bank_read(&self.rom, bank, address)
+    }
+
+    fn write_rom(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = data & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                self.rom_bank = if self.mbc30 {data} else {data & 0x7F};
+                if self.rom_bank == 0 {
+                    self.rom_bank = 1;
+                }
+            }
+            0x4000..=0x5FFF => self.ram_select = data & 0x0F,
+            _ => {
+                if let Some(rtc) = &mut self.rtc {
+                    rtc.write_latch(data);
+                }
+            }
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF
+        }
+        if self.rtc_selected() {
+            return match &self.rtc {
+                Some(rtc) => rtc.read(self.ram_select),
+                None => 0xFF,
+            }
+        }
+        match ram_bank_offset(&self.ram, self.ram_bank(), address) {
+            Some(offset) => self.ram[offset],
+            None => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, data: u8) {
+        if !self.ram_enabled {
+            return
+        }
+        if self.rtc_selected() {
+            if let Some(rtc) = &mut self.rtc {
+                rtc.write(self.ram_select, data);
+            }
+            return
+        }
+        if let Some(offset) = ram_bank_offset(&self.ram, self.ram_bank(), address) {
+            self.ram[offset] = data;
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn save_rtc(&mut self) -> Option<Vec<u8>> {
+        self.rtc.as_mut().map(|rtc| rtc.save())
+    }
+
+    fn load_rtc(&mut self, data: &[u8]) {
+        if let Some(rtc) = &mut self.rtc {
+            rtc.load(data);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::banked_rom;
+    use crate::cartridge::rtc::FakeClock;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_rom_and_ram_banking() {
+        let mut mbc = Mbc3::new(banked_rom(128), 0x8000, None);
+        mbc.write_rom(0x2000, 0x00);
+        assert_eq!(mbc.read_rom(0x4000), 1);
+        mbc.write_rom(0x2000, 0x7F);
+        assert_eq!(mbc.read_rom(0x4000), 0x7F);
+
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, 0x03);
+        mbc.write_ram(0xA000, 0x42);
+        mbc.write_rom(0x4000, 0x00);
+        assert_eq!(mbc.read_ram(0xA000), 0x00);
+        mbc.write_rom(0x4000, 0x03);
+        assert_eq!(mbc.read_ram(0xA000), 0x42);
+    }
+
+    #[test]
+    fn test_rtc_registers() {
+        let time = Rc::new(Cell::new(0));
+        let mut mbc = Mbc3::new(banked_rom(4), 0x2000, Some(Box::new(FakeClock { time: time.clone() })));
+        mbc.write_rom(0x0000, 0x0A);
+        time.set(3661);
+        mbc.write_rom(0x6000, 0x00);
+        mbc.write_rom(0x6000, 0x01);
+        mbc.write_rom(0x4000, 0x08);
+        assert_eq!(mbc.read_ram(0xA000), 1);
+        mbc.write_rom(0x4000, 0x09);
+        assert_eq!(mbc.read_ram(0xA000), 1);
+        mbc.write_rom(0x4000, 0x0A);
+        assert_eq!(mbc.read_ram(0xA000), 1);
+
+        //Timer disabled along with ram
+        mbc.write_rom(0x0000, 0x00);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::VecDeque;
+use super::{Cartridge, CartridgeEvent, rom_bank_read, ram_bank_offset};
+
+//MBC5 registers
+//0000-1FFF RAM enable - 0x0A enables
+//2000-2FFF Lower 8 bits of rom bank number, bank 0 can be mapped
+//3000-3FFF Bit 8 of rom bank number
+//4000-5FFF 4 bit ram bank number
+//Rumble carts wire bit 3 of the ram bank register to the motor instead, leaving 8 ram banks
+pub struct Mbc5 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    rom_bank: u16,
+    ram_bank: u8,
+    rumble: Option<bool>,
+    events: VecDeque<CartridgeEvent>,
+}
+
+impl Mbc5 {
+    pub fn new(rom: Vec<u8>, ram_size: usize, rumble: bool) -> Mbc5 {
+        Mbc5 {
+            rom,
+            ram: vec![0; ram_size],
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            rumble: if rumble {Some(false)} else {None},
+            events: VecDeque::new(),
+        }
+    }
+}
+
+impl Cartridge for Mbc5 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        rom_bank_read(&self.rom, bank, address)
+    }
+
+    fn write_rom(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = data == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | data as u16,
+            0x3000..=0x3FFF => self.rom_bank = (self.rom_bank & 0xFF) | ((data as u16 & 0x01) << 8),
+            0x4000..=0x5FFF => {
+                match self.rumble {
+                    Some(motor) => {
+                        self.ram_bank = data & 0x07;
+                        let on = data & 0x08 != 0;
+                        if on != motor {
+                            self.rumble = Some(on);
+                            self.events.push_back(CartridgeEvent::Rumble(on));
+                        }
+                    }
+                    None => self.ram_bank = data & 0x0F,
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF
+        }
+        match ram_bank_offset(&self.ram, self.ram_bank as usize, address) {
+            Some(offset) => self.ram[offset],
+            None => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, data: u8) {
+        if !self.ram_enabled {
+            return
+        }
+        if let Some(offset) = ram_bank_offset(&self.ram, self.ram_bank as usize, address) {
+            self.ram[offset] = data;
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn poll_event(&mut self) -> Option<CartridgeEvent> {
+        self.events.pop_front()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::{banked_rom, ROM_BANK_SIZE};
+
+    //Bank numbers go up to 511, the second byte of each bank holds the upper bit
+    fn wide_banked_rom(banks: usize) -> Vec<u8> {
+        let mut rom = banked_rom(banks);
+        for bank in 0..banks {
+            rom[bank * ROM_BANK_SIZE + 1] = (bank >> 8) as u8;
+        }
+        rom
+    }
+
+    #[test]
+    fn test_nine_bit_rom_bank() {
+        let mut mbc = Mbc5::new(wide_banked_rom(512), 0, false);
+        mbc.write_rom(0x2000, 0x00);
+        assert_eq!(mbc.read_rom(0x4000), 0);
+        mbc.write_rom(0x2000, 0x23);
+        mbc.write_rom(0x3000, 0x01);
+        assert_eq!(mbc.read_rom(0x4000), 0x23);
+        assert_eq!(mbc.read_rom(0x4001), 0x01);
+    }
+
+    #[test]
+    fn test_sixteen_ram_banks() {
+        let mut mbc = Mbc5::new(wide_banked_rom(4), 0x20000, false);
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, 0x0F);
+        mbc.write_ram(0xA000, 0x55);
+        mbc.write_rom(0x4000, 0x07);
+        assert_eq!(mbc.read_ram(0xA000), 0x00);
+        mbc.write_rom(0x4000, 0x0F);
+        assert_eq!(mbc.read_ram(0xA000), 0x55);
+    }
+
+    #[test]
+    fn test_rumble_events() {
+        let mut mbc = Mbc5::new(wide_banked_rom(4), 0x8000, true);
+        mbc.write_rom(0x4000, 0x08);
+        mbc.write_rom(0x4000, 0x09);
+        mbc.write_rom(0x4000, 0x01);
+        assert_eq!(mbc.poll_event(), Some(CartridgeEvent::Rumble(true)));
+        assert_eq!(mbc.poll_event(), Some(CartridgeEvent::Rumble(false)));
+        assert_eq!(mbc.poll_event(), None);
+        //Motor bit isn't part of the ram bank
+        assert_eq!(mbc.ram_bank, 1);
+    }
+}
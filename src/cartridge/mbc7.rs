@@ -0,0 +1,276 @@
+use std::cell::Cell;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+use super::{Cartridge, rom_bank_read};
+use super::eeprom::Eeprom;
+
+//MBC7 registers
+//0000-1FFF RAM enable 1 - 0x0A enables
+//2000-3FFF rom bank number
+//4000-5FFF RAM enable 2 - 0x40 enables
+//A000-AFFF is only mapped with both enables set, registers are picked by address bits 4-7
+//Ax0x write 0x55 to reset the accelerometer latch
+//Ax1x write 0xAA after the reset to latch the accelerometer
+//Ax2x-Ax5x latched X low, X high, Y low, Y high
+//Ax8x EEPROM pins - bit 7 CS, bit 6 CLK, bit 1 DI, bit 0 DO
+//Everything else reads 0xFF
+pub const TILT_CENTER: u16 = 0x81D0;
+//Latched value moves by about this much for 1g
+pub const TILT_RANGE: f32 = 0x70 as f32;
+
+//Where tilt comes from, in g along the X (right positive) and Y (down positive) axes
+pub trait TiltSource {
+    fn tilt(&mut self) -> (f32, f32);
+}
+
+//Tilt set from outside, the frontend writes it from keyboard or analog stick each frame
+pub struct LiveTilt {
+    pub value: Rc<Cell<(f32, f32)>>,
+}
+
+impl TiltSource for LiveTilt {
+    fn tilt(&mut self) -> (f32, f32) {
+        self.value.get()
+    }
+}
+
+//Tilt values read from a script, one "x y" pair per line, advancing on every latch
+//Once the script runs out the last value is held
+pub struct ScriptedTilt {
+    values: Vec<(f32, f32)>,
+    next: usize,
+}
+
+impl ScriptedTilt {
+    pub fn new(values: Vec<(f32, f32)>) -> ScriptedTilt {
+        ScriptedTilt { values, next: 0 }
+    }
+
+    pub fn load(path: &Path) -> io::Result<ScriptedTilt> {
+        let text = fs::read_to_string(path)?;
+        let mut values = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            let fields: Vec<f32> = line.split_whitespace().filter_map(|field| field.parse().ok()).collect();
+            match fields[..] {
+                [x, y] => values.push((x, y)),
+                _ => {
+                    let message = format!("line {} should be an x and y value", number + 1);
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, message))
+                }
+            }
+        }
+        Ok(ScriptedTilt::new(values))
+    }
+}
+
+impl TiltSource for ScriptedTilt {
+    fn tilt(&mut self) -> (f32, f32) {
+        let value = self.values.get(self.next).or_else(|| self.values.last()).copied().unwrap_or((0.0, 0.0));
+        if self.next < self.values.len() {
+            self.next += 1;
+        }
+        value
+    }
+}
+
+pub struct Mbc7 {
+    rom: Vec<u8>,
+    ram_enable1: bool,
+    ram_enable2: bool,
+    rom_bank: u8,
+    eeprom: Eeprom,
+    latch_ready: bool,
+    x: u16,
+    y: u16,
+    tilt: Option<Box<dyn TiltSource>>,
+}
+
+impl Mbc7 {
+    pub fn new(rom: Vec<u8>) -> Mbc7 {
+        Mbc7 {
+            rom,
+            ram_enable1: false,
+            ram_enable2: false,
+            rom_bank: 1,
+            eeprom: Eeprom::new(),
+            latch_ready: false,
+            x: 0x8000,
+            y: 0x8000,
+            tilt: None,
+        }
+    }
+
+    fn mapped(&self, address: u16) -> bool {
+        self.ram_enable1 && self.ram_enable2 && address < 0xB000
+    }
+
+    fn latch(&mut self) {
+        let (x, y) = match &mut self.tilt {
+            Some(tilt) => tilt.tilt(),
+            None => (0.0, 0.0),
+        };
+        let axis = |g: f32| (TILT_CENTER as f32 + g * TILT_RANGE).clamp(0.0, 0xFFFF as f32) as u16;
+        self.x = axis(x);
+        self.y = axis(y);
+    }
+}
+
+impl Cartridge for Mbc7 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        rom_bank_read(&self.rom, bank, address)
+    }
+
+    fn write_rom(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                self.ram_enable1 = data == 0x0A;
+                if !self.ram_enable1 {
+                    self.ram_enable2 = false;
+                }
+            }
+            0x2000..=0x3FFF => self.rom_bank = data & 0x7F,
+            0x4000..=0x5FFF => self.ram_enable2 = self.ram_enable1 && data == 0x40,
+            _ => (),
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.mapped(address) {
+            return 0xFF
+        }
+        match (address >> 4) & 0x0F {
+            0x2 => self.x as u8,
+            0x3 => (self.x >> 8) as u8,
+            0x4 => self.y as u8,
+            0x5 => (self.y >> 8) as u8,
+            0x6 => 0x00,
+            0x8 => self.eeprom.read(),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, data: u8) {
+        if !self.mapped(address) {
+            return
+        }
+        match (address >> 4) & 0x0F {
+            0x0 if data == 0x55 => {
+                self.latch_ready = true;
+                self.x = 0x8000;
+                self.y = 0x8000;
+            }
+            0x1 if self.latch_ready && data == 0xAA => {
+                self.latch_ready = false;
+                self.latch();
+            }
+            0x8 => self.eeprom.write(data),
+            _ => (),
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.eeprom.data
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.eeprom.data
+    }
+
+    fn set_tilt_source(&mut self, source: Box<dyn TiltSource>) {
+        self.tilt = Some(source);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled_mbc7() -> Mbc7 {
+        let mut mbc = Mbc7::new(vec![0; 0x8000]);
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, 0x40);
+        mbc
+    }
+
+    fn latch(mbc: &mut Mbc7) -> (u16, u16) {
+        mbc.write_ram(0xA000, 0x55);
+        mbc.write_ram(0xA010, 0xAA);
+        let x = mbc.read_ram(0xA020) as u16 | (mbc.read_ram(0xA030) as u16) << 8;
+        let y = mbc.read_ram(0xA040) as u16 | (mbc.read_ram(0xA050) as u16) << 8;
+        (x, y)
+    }
+
+    #[test]
+    fn test_needs_both_enables() {
+        let mut mbc = Mbc7::new(vec![0; 0x8000]);
+        mbc.write_rom(0x4000, 0x40);
+        mbc.write_rom(0x0000, 0x0A);
+        assert_eq!(mbc.read_ram(0xA060), 0xFF);
+        mbc.write_rom(0x4000, 0x40);
+        assert_eq!(mbc.read_ram(0xA060), 0x00);
+        //B000-BFFF is never mapped
+        assert_eq!(mbc.read_ram(0xB060), 0xFF);
+    }
+
+    #[test]
+    fn test_live_tilt_latch() {
+        let value = Rc::new(Cell::new((0.0, 0.0)));
+        let mut mbc = enabled_mbc7();
+        mbc.set_tilt_source(Box::new(LiveTilt { value: value.clone() }));
+        assert_eq!(latch(&mut mbc), (TILT_CENTER, TILT_CENTER));
+
+        value.set((1.0, -0.5));
+        //Latch holds until the next reset and latch
+        assert_eq!(mbc.read_ram(0xA020), TILT_CENTER as u8);
+        assert_eq!(latch(&mut mbc), (TILT_CENTER + 0x70, TILT_CENTER - 0x38));
+
+        //0xAA without a 0x55 first doesn't latch
+        value.set((0.0, 0.0));
+        mbc.write_ram(0xA010, 0xAA);
+        assert_eq!(mbc.read_ram(0xA020), (TILT_CENTER + 0x70) as u8);
+    }
+
+    #[test]
+    fn test_scripted_tilt() {
+        let path = std::env::temp_dir().join(format!("rusty-tilt-{}.txt", std::process::id()));
+        fs::write(&path, "# x y\n0.5 0\n\n-1 1\n").unwrap();
+        let mut mbc = enabled_mbc7();
+        mbc.set_tilt_source(Box::new(ScriptedTilt::load(&path).unwrap()));
+        assert_eq!(latch(&mut mbc), (TILT_CENTER + 0x38, TILT_CENTER));
+        assert_eq!(latch(&mut mbc), (TILT_CENTER - 0x70, TILT_CENTER + 0x70));
+        assert_eq!(latch(&mut mbc), (TILT_CENTER - 0x70, TILT_CENTER + 0x70));
+
+        fs::write(&path, "1\n").unwrap();
+        assert!(ScriptedTilt::load(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_eeprom_is_save_ram() {
+        let mut mbc = enabled_mbc7();
+        assert_eq!(mbc.ram().len(), 0x100);
+        mbc.load_ram(&[0x80; 0x100]);
+        //Start bit and READ of word 0 gives the dummy bit, then the first data bit
+        let mut clock = |bit: u8| {
+            mbc.write_ram(0xA080, 0x80 | bit << 1);
+            mbc.write_ram(0xA080, 0xC0 | bit << 1);
+            mbc.read_ram(0xA080) & 0x01
+        };
+        for bit in [1, 1, 0, 0, 0, 0, 0, 0, 0, 0].iter() {
+            clock(*bit);
+        }
+        assert_eq!(clock(0), 0x00);
+        assert_eq!(clock(0), 0x01);
+    }
+}
@@ -0,0 +1,174 @@
+use crate::header::{CartridgeHeader, Mapper};
+
+pub mod camera;
+pub mod eeprom;
+pub mod huc1;
+pub mod huc3;
+pub mod infrared;
+pub mod mbc1;
+pub mod mbc2;
+pub mod mbc3;
+pub mod mbc5;
+pub mod mbc7;
+pub mod rtc;
+
+use camera::{ImageSource, PocketCamera};
+use huc1::HuC1;
+use huc3::HuC3;
+use infrared::Infrared;
+use mbc1::Mbc1;
+use mbc2::Mbc2;
+use mbc3::Mbc3;
+use mbc5::Mbc5;
+use mbc7::{Mbc7, TiltSource};
+use rtc::SystemClock;
+
+pub const ROM_BANK_SIZE: usize = 0x4000;
+pub const RAM_BANK_SIZE: usize = 0x2000;
+
+//Things the cartridge hardware does that the frontend needs to act on
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum CartridgeEvent {
+    Rumble(bool), //Motor switched on or off
+    Tone(u8),     //Speaker beep, with the cart specific tone number
+}
+
+//Everything on the cartridge side of the bus
+//Memory delegates 0000-7FFF and A000-BFFF here
+pub trait Cartridge {
+    //0000-7FFF
+    fn read_rom(&self, address: u16) -> u8;
+    //0000-7FFF, writes go to the bank controller registers instead of rom
+    fn write_rom(&mut self, address: u16, data: u8);
+    //A000-BFFF
+    fn read_ram(&self, address: u16) -> u8;
+    //A000-BFFF
+    fn write_ram(&mut self, address: u16, data: u8);
+
+    //External ram contents, in the raw layout .sav files use
+    fn ram(&self) -> &[u8];
+
+    fn ram_mut(&mut self) -> &mut [u8];
+
+    fn load_ram(&mut self, data: &[u8]) {
+        let ram = self.ram_mut();
+        let length = ram.len().min(data.len());
+        ram[..length].copy_from_slice(&data[..length]);
+    }
+
+    //Clock state for carts with a timer, stored after the ram in the save file
+    fn save_rtc(&mut self) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn load_rtc(&mut self, _data: &[u8]) {}
+
+    //Pop the oldest event the cartridge has raised
+    fn poll_event(&mut self) -> Option<CartridgeEvent> {
+        None
+    }
+
+    //Advance hardware on the cartridge that runs on its own time, by M-cycles
+    fn tick(&mut self, _cycles: u32) {}
+
+    //Sensor input for camera carts, ignored by everything else
+    fn set_image_source(&mut self, _source: Box<dyn ImageSource>) {}
+
+    //IR port for carts with an infrared LED and receiver
+    fn set_infrared(&mut self, _port: Box<dyn Infrared>) {}
+
+    //Accelerometer input for tilt sensing carts
+    fn set_tilt_source(&mut self, _source: Box<dyn TiltSource>) {}
+}
+
+//Pick the bank controller from the cartridge type byte at 0x147
+pub fn new_cartridge(rom: Vec<u8>, header: &CartridgeHeader) -> Box<dyn Cartridge> {
+    let ram_size = header.ram_size().unwrap_or(0);
+
+    match header.cartridge_type.mapper {
+        Mapper::RomOnly => Box::new(RomOnly::new(rom, ram_size)),
+        Mapper::Mbc1 => Box::new(Mbc1::new(rom, ram_size)),
+        Mapper::Mbc2 => Box::new(Mbc2::new(rom)),
+        Mapper::Mbc3 => {
+            let clock: Option<Box<dyn rtc::Clock>> = if header.cartridge_type.timer {Some(Box::new(SystemClock))} else {None};
+            Box::new(Mbc3::new(rom, ram_size, clock))
+        }
+        Mapper::Mbc5 => Box::new(Mbc5::new(rom, ram_size, header.cartridge_type.rumble)),
+        Mapper::Mbc7 => Box::new(Mbc7::new(rom)),
+        Mapper::PocketCamera => Box::new(PocketCamera::new(rom, ram_size)),
+        Mapper::HuC1 => Box::new(HuC1::new(rom, ram_size)),
+        Mapper::HuC3 => Box::new(HuC3::new(rom, ram_size, Box::new(SystemClock))),
+        mapper => {
+            println!("Cartridge type {:?} is not supported, running as ROM only", mapper);
+            Box::new(RomOnly::new(rom, ram_size))
+        }
+    }
+}
+
+//32 KiB rom with no bank controller, optionally with up to 8 KiB of ram
+pub struct RomOnly {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+}
+
+impl RomOnly {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> RomOnly {
+        RomOnly {
+            rom,
+            ram: vec![0; ram_size.min(RAM_BANK_SIZE)],
+        }
+    }
+}
+
+impl Cartridge for RomOnly {
+    fn read_rom(&self, address: u16) -> u8 {
+        self.rom.get(address as usize).copied().unwrap_or(0xFF)
+    }
+
+    fn write_rom(&mut self, _address: u16, _data: u8) {}
+
+    fn read_ram(&self, address: u16) -> u8 {
+        self.ram.get((address - 0xA000) as usize).copied().unwrap_or(0xFF)
+    }
+
+    fn write_ram(&mut self, address: u16, data: u8) {
+        if let Some(byte) = self.ram.get_mut((address - 0xA000) as usize) {
+            *byte = data;
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+}
+
+//Read a byte from rom given a bank number and the offset within that bank
+//Bank numbers past the end of the rom wrap, like the unused upper address lines would
+pub fn rom_bank_read(rom: &[u8], bank: usize, address: u16) -> u8 {
+    let bank_count = (rom.len() / ROM_BANK_SIZE).max(1);
+    let offset = (bank % bank_count) * ROM_BANK_SIZE + (address as usize & (ROM_BANK_SIZE - 1));
+    rom.get(offset).copied().unwrap_or(0xFF)
+}
+
+//Offset into external ram for a bank number and an address in A000-BFFF
+pub fn ram_bank_offset(ram: &[u8], bank: usize, address: u16) -> Option<usize> {
+    if ram.is_empty() {
+        return None
+    }
+    //Carts with 2 KiB of ram mirror it across the whole 8 KiB window
+    Some((bank * RAM_BANK_SIZE + (address as usize & (RAM_BANK_SIZE - 1))) % ram.len())
+}
+
+//Test rom where every bank starts with its own bank number so reads show which bank is mapped
+#[cfg(test)]
+pub(crate) fn banked_rom(banks: usize) -> Vec<u8> {
+    let mut rom = vec![0u8; banks * ROM_BANK_SIZE];
+    for bank in 0..banks {
+        rom[bank * ROM_BANK_SIZE] = bank as u8;
+    }
+    rom
+}
@@ -0,0 +1,291 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+//Source of wall clock time for cartridge clocks, in seconds since the unix epoch
+pub trait Clock {
+    fn now(&self) -> u64;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0)
+    }
+}
+
+//Clock that only moves when told to, for tests
+#[cfg(test)]
+pub struct FakeClock {
+    pub time: std::rc::Rc<std::cell::Cell<u64>>,
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn now(&self) -> u64 {
+        self.time.get()
+    }
+}
+
+//Size of the rtc block BGB and VBA-M append to the end of .sav files
+pub const RTC_SAVE_SIZE: usize = 48;
+
+//MBC3 real time clock registers
+//08 Seconds 0-59
+//09 Minutes 0-59
+//0A Hours 0-23
+//0B Lower 8 bits of day counter
+//0C Bit 0 - bit 8 of day counter, bit 6 - halt, bit 7 - day counter carry
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub struct RtcRegisters {
+    pub seconds: u8,
+    pub minutes: u8,
+    pub hours: u8,
+    pub days: u16,
+    pub halt: bool,
+    pub carry: bool,
+}
+
+impl RtcRegisters {
+    pub fn read(&self, register: u8) -> u8 {
+        match register {
+            0x08 => self.seconds,
+            0x09 => self.minutes,
+            0x0A => self.hours,
+            0x0B => self.days as u8,
+            0x0C => self.day_high(),
+            _ => 0xFF,
+        }
+    }
+
+    fn day_high(&self) -> u8 {
+        let mut data = ((self.days >> 8) as u8) & 0x01;
+        if self.halt {data |= 1 << 6;}
+        if self.carry {data |= 1 << 7;}
+        data
+    }
+
+    fn write(&mut self, register: u8, data: u8) {
+        match register {
+            0x08 => self.seconds = data & 0x3F,
+            0x09 => self.minutes = data & 0x3F,
+            0x0A => self.hours = data & 0x1F,
+            0x0B => self.days = (self.days & 0x100) | data as u16,
+            0x0C => {
+                self.days = (self.days & 0xFF) | ((data as u16 & 0x01) << 8);
+                self.halt = data & (1 << 6) != 0;
+                self.carry = data & (1 << 7) != 0;
+            }
+            _ => (),
+        }
+    }
+
+    fn valid(&self) -> bool {
+        self.seconds < 60 && self.minutes < 60 && self.hours < 24
+    }
+
+    //Counters are only 6/6/5 bits wide, so a game can write an out of range value
+    //They then count up to the top of their range and wrap to 0 without carrying
+    fn tick(&mut self) {
+        if self.seconds != 59 {
+            self.seconds = (self.seconds + 1) & 0x3F;
+            return
+        }
+        self.seconds = 0;
+        if self.minutes != 59 {
+            self.minutes = (self.minutes + 1) & 0x3F;
+            return
+        }
+        self.minutes = 0;
+        if self.hours != 23 {
+            self.hours = (self.hours + 1) & 0x1F;
+            return
+        }
+        self.hours = 0;
+        self.add_days(1);
+    }
+
+    fn add_days(&mut self, days: u64) {
+        let total = self.days as u64 + days;
+        if total > 0x1FF {
+            self.carry = true;
+        }
+        self.days = (total & 0x1FF) as u16;
+    }
+
+    fn advance(&mut self, mut seconds: u64) {
+        while seconds > 0 && !self.valid() {
+            self.tick();
+            seconds -= 1;
+        }
+        let time_of_day = self.seconds as u64 + self.minutes as u64 * 60 + self.hours as u64 * 3600 + seconds;
+        self.seconds = (time_of_day % 60) as u8;
+        self.minutes = (time_of_day / 60 % 60) as u8;
+        self.hours = (time_of_day / 3600 % 24) as u8;
+        self.add_days(time_of_day / 86400);
+    }
+}
+
+pub struct Rtc {
+    pub clock: Box<dyn Clock>,
+    pub registers: RtcRegisters,
+    pub latched: RtcRegisters,
+    last_update: u64,
+    latch_ready: bool,
+}
+
+impl Rtc {
+    pub fn new(clock: Box<dyn Clock>) -> Rtc {
+        let last_update = clock.now();
+        Rtc {
+            clock,
+            registers: RtcRegisters::default(),
+            latched: RtcRegisters::default(),
+            last_update,
+            latch_ready: false,
+        }
+    }
+
+    //Catch the counters up with however much wall clock time has passed
+    pub fn update(&mut self) {
+        let now = self.clock.now();
+        if !self.registers.halt && now > self.last_update {
+            self.registers.advance(now - self.last_update);
+        }
+        self.last_update = now;
+    }
+
+    //Writing 0x00 then 0x01 to 6000-7FFF copies the counters into the readable registers
+    pub fn write_latch(&mut self, data: u8) {
+        if self.latch_ready && data == 0x01 {
+            self.update();
+            self.latched = self.registers;
+        }
+        self.latch_ready = data == 0x00;
+    }
+
+    pub fn read(&self, register: u8) -> u8 {
+        self.latched.read(register)
+    }
+
+    pub fn write(&mut self, register: u8, data: u8) {
+        self.update();
+        self.registers.write(register, data);
+        self.latched.write(register, data);
+    }
+
+    //BGB/VBA-M layout, all little endian
+    //5 x u32 current registers, 5 x u32 latched registers, u64 unix timestamp
+    pub fn save(&mut self) -> Vec<u8> {
+        self.update();
+        let mut data = Vec::with_capacity(RTC_SAVE_SIZE);
+        for registers in [self.registers, self.latched].iter() {
+            for register in 0x08..=0x0C {
+                data.extend_from_slice(&(registers.read(register) as u32).to_le_bytes());
+            }
+        }
+        data.extend_from_slice(&self.last_update.to_le_bytes());
+        data
+    }
+
+    pub fn load(&mut self, data: &[u8]) {
+        if data.len() < RTC_SAVE_SIZE {
+            return
+        }
+        let word = |index: usize| data[index * 4];
+        for (index, register) in (0x08..=0x0C).enumerate() {
+            self.registers.write(register, word(index));
+            self.latched.write(register, word(index + 5));
+        }
+        let mut timestamp = [0u8; 8];
+        timestamp.copy_from_slice(&data[40..48]);
+        //Time spent with the emulator closed is added on the next update
+        self.last_update = u64::from_le_bytes(timestamp);
+        self.update();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    fn fake_rtc(start: u64) -> (Rtc, Rc<Cell<u64>>) {
+        let time = Rc::new(Cell::new(start));
+        let rtc = Rtc::new(Box::new(FakeClock { time: time.clone() }));
+        (rtc, time)
+    }
+
+    fn latch(rtc: &mut Rtc) {
+        rtc.write_latch(0x00);
+        rtc.write_latch(0x01);
+    }
+
+    #[test]
+    fn test_rtc_advances_with_clock() {
+        let (mut rtc, time) = fake_rtc(1000);
+        time.set(1000 + 86400 + 3600 * 2 + 60 * 3 + 4);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(0x08), 4);
+        assert_eq!(rtc.read(0x09), 3);
+        assert_eq!(rtc.read(0x0A), 2);
+        assert_eq!(rtc.read(0x0B), 1);
+        assert_eq!(rtc.read(0x0C), 0);
+    }
+
+    #[test]
+    fn test_rtc_latch_holds_value() {
+        let (mut rtc, time) = fake_rtc(0);
+        time.set(10);
+        latch(&mut rtc);
+        time.set(20);
+        assert_eq!(rtc.read(0x08), 10);
+        //Writing 0x01 without a preceding 0x00 doesn't latch
+        rtc.write_latch(0x01);
+        assert_eq!(rtc.read(0x08), 10);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(0x08), 20);
+    }
+
+    #[test]
+    fn test_rtc_halt_and_carry() {
+        let (mut rtc, time) = fake_rtc(0);
+        rtc.write(0x0C, 0x41);
+        rtc.write(0x0B, 0xFF);
+        time.set(86400);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(0x0B), 0xFF);
+        //Un-halting starts counting from now, and day 511 rolls over with carry set
+        rtc.write(0x0C, 0x01);
+        time.set(86400 * 2);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(0x0B), 0x00);
+        assert_eq!(rtc.read(0x0C), 0x80);
+    }
+
+    #[test]
+    fn test_rtc_invalid_seconds_wrap_without_carry() {
+        let (mut rtc, time) = fake_rtc(0);
+        rtc.write(0x08, 62);
+        time.set(2);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(0x08), 0);
+        assert_eq!(rtc.read(0x09), 0);
+    }
+
+    #[test]
+    fn test_rtc_save_load() {
+        let (mut rtc, _) = fake_rtc(500);
+        rtc.write(0x09, 30);
+        let saved = rtc.save();
+        assert_eq!(saved.len(), RTC_SAVE_SIZE);
+
+        //Emulator closed for an hour
+        let (mut loaded, _) = fake_rtc(500 + 3600);
+        loaded.load(&saved);
+        latch(&mut loaded);
+        assert_eq!(loaded.read(0x09), 30);
+        assert_eq!(loaded.read(0x0A), 1);
+    }
+}
@@ -7,18 +7,65 @@ use std::io::prelude::*;
 
 
 
+//Interrupt master enable
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Ime {
+    Disabled,
+    Pending, //EI was just executed, interrupts are enabled once the next instruction finishes
+    Enabled,
+}
+
+//How an instruction runs once it has been fetched
+pub enum Execute {
+    Always(fn(&mut Cpu)),
+    Branch(fn(&mut Cpu) -> bool), //Returns true when the condition held and the branch was taken
+    Prefix, //Next byte selects from CB_INSTRUCTIONS
+    Illegal, //Locks up real hardware
+}
+
+pub struct Instruction {
+    pub opcode: u8,
+    pub mnemonic: &'static str,
+    pub length: u8, //Bytes including operands
+    pub cycles: u8, //M-cycles, or when not taken for conditional instructions
+    pub taken_cycles: u8, //M-cycles when a conditional instruction is taken
+    pub execute: Execute,
+}
+
+macro_rules! op {
+    ($opcode:expr, $mnemonic:expr, $length:expr, $cycles:expr, $execute:expr) => {
+        Instruction {opcode: $opcode, mnemonic: $mnemonic, length: $length, cycles: $cycles, taken_cycles: $cycles, execute: Execute::Always($execute)}
+    };
+}
+
+macro_rules! branch {
+    ($opcode:expr, $mnemonic:expr, $length:expr, $cycles:expr, $taken_cycles:expr, $execute:expr) => {
+        Instruction {opcode: $opcode, mnemonic: $mnemonic, length: $length, cycles: $cycles, taken_cycles: $taken_cycles, execute: Execute::Branch($execute)}
+    };
+}
+
+//Prefix byte alone, the CB instruction it selects has the full length and cycles
+macro_rules! prefix {
+    ($opcode:expr) => {
+        Instruction {opcode: $opcode, mnemonic: "PREFIX CB", length: 1, cycles: 1, taken_cycles: 1, execute: Execute::Prefix}
+    };
+}
+
+macro_rules! illegal {
+    ($opcode:expr) => {
+        Instruction {opcode: $opcode, mnemonic: "ILLEGAL", length: 1, cycles: 1, taken_cycles: 1, execute: Execute::Illegal}
+    };
+}
+
 pub struct Cpu  {
     pub registers: Registers,
     pub memory: Memory,
     pub halted: bool,
+    pub halt_bug: bool, //Next opcode fetch doesn't increment pc
     pub stopped: bool,
-    pub interrupts_enabled: bool,
+    pub ime: Ime,
     pub debug: bool,
-    
-    //Opcode, Neumonic, Instruction Length
-    pub instructions: [(u8, &'static str, u8); 256],
-
-    pub cb_instructions: [(u8, &'static str); 256],
+    ticked: u8, //M-cycles already run on the bus during the current step
 }
 
 //CHECK WRAPPING
@@ -38,34 +85,34 @@ impl Cpu {
         let mut line: String;
 
         while counter < 0x8000 {
-            let hex = self.memory.memory[counter as usize];
+            let hex = self.memory.read_byte(counter);
             if cb {
-                let cb_tuple = self.cb_instructions[hex as usize];
-                let cb_neumonic = cb_tuple.1;
-                let cb_opcode = cb_tuple.0;
+                let cb_instruction = &CB_INSTRUCTIONS[hex as usize];
+                let cb_neumonic = cb_instruction.mnemonic;
+                let cb_opcode = cb_instruction.opcode;
                 line = format!("PC: {:#04X}\t Op: CB {:#02X}\t {}\n", counter-1, cb_opcode, cb_neumonic);
                 writer.write_all(line.as_bytes()).unwrap();
                 cb = false;
                 counter += 1;
                 continue
             }
-            let tuple = self.instructions[hex as usize];
-            let length = tuple.2;
-            let neumonic = tuple.1;
-            let opcode = tuple.0;
-            if neumonic == "PREFIX CB" {
+            let instruction = &INSTRUCTIONS[hex as usize];
+            let length = instruction.length;
+            let neumonic = instruction.mnemonic;
+            let opcode = instruction.opcode;
+            if let Execute::Prefix = instruction.execute {
                 cb = true;
                 counter += 1;
                 continue
             }
 
             if length == 2 {
-                line = format!("PC: {:#04X}\t Op: {:#02X}\t {}\t ${:#04X}\n", counter, opcode, neumonic, self.memory.memory[(counter+1) as usize]); 
+                line = format!("PC: {:#04X}\t Op: {:#02X}\t {}\t ${:#04X}\n", counter, opcode, neumonic, self.memory.read_byte(counter+1)); 
                 counter += 2;
             }
             else if length == 3 {
-                let lower = self.memory.memory[(counter+1) as usize];
-                let upper = self.memory.memory[(counter+2) as usize];
+                let lower = self.memory.read_byte(counter+1);
+                let upper = self.memory.read_byte(counter+2);
                 line = format!("PC: {:#04X}\t Op: {:#02X}\t {}\t ${:#04X}\n", counter, opcode, neumonic, (upper as u16) << 8 | lower as u16); 
                 counter += 3;
             }
@@ -82,12 +129,12 @@ impl Cpu {
     fn print_current_status(&self, opcode: u8, cb: bool) {
         let neumonic: &str;
         if cb {
-            neumonic = self.cb_instructions[opcode as usize].1;
+            neumonic = CB_INSTRUCTIONS[opcode as usize].mnemonic;
             println!("Program Counter: {:#06X}\t Next Opcode: {:#02X}\t Neumonic {}\t $\n", self.registers.pc - 1, opcode, neumonic)
         }
         else {
-            neumonic = self.instructions[opcode as usize].1;
-            let length = self.instructions[opcode as usize].2;
+            neumonic = INSTRUCTIONS[opcode as usize].mnemonic;
+            let length = INSTRUCTIONS[opcode as usize].length;
             if length == 2 {
                 println!("Program Counter: {:#06X}\t Next Opcode: {:#02X}\t Neumonic {}\t ${:#04X}\n", self.registers.pc - 1,opcode, neumonic, self.memory.read_byte(self.registers.pc));
             }
@@ -102,156 +149,96 @@ impl Cpu {
         //Registers
         println!("Registers\nA: {:#04X}\t B: {:#04X}\t D: {:#04X}\t H: {:#04X}\t SP: {:#04X}\n", self.registers.a, self.registers.b, self.registers.d, self.registers.h, self.registers.sp);
         println!("F: {:#04X}\t C: {:#04X}\t E: {:#04X}\t L: {:#04X}\n", self.registers.f, self.registers.c, self.registers.e, self.registers.l);
-        println!("Current Scan Row: {}\t IME: {}\t LCDC: {:#04X}\t {:#04X}", self.memory.vram.scan_row,self.interrupts_enabled,self.memory.read_byte(0xFF40), self.memory.read_byte(0xFF41));
+        println!("Current Scan Row: {}\t IME: {:?}\t LCDC: {:#04X}\t {:#04X}", self.memory.vram.scan_row,self.ime,self.memory.read_byte(0xFF40), self.memory.read_byte(0xFF41));
         println!("Int Enable {:#04X}\t Int Flag {:#04X}", self.memory.read_byte(0xFFFF), self.memory.read_byte(0xFF0F));
         //Flags
         println!("Carry: {}\t Zero: {}\t Halfcarry: {}\t Add/Sub: {}\n\n", self.registers.check_carry(), self.registers.check_zero(), self.registers.check_halfcarry(), self.registers.check_addsub())
 
     }
 
-    pub fn new() -> Cpu {
+    pub fn new(memory: Memory) -> Cpu {
+        let mut registers = Registers::new();
+
+        //Boot rom starts executing from 0x0000 and hands off to the cartridge at 0x100
+        if memory.bios_flag {
+            registers.pc = 0x0000;
+        }
+
         Cpu {
-            registers: Registers::new(),
-            memory: Memory::new(),
+            registers,
+            memory,
             halted: false,
+            halt_bug: false,
             stopped: false,
-            interrupts_enabled: false,
+            ime: Ime::Disabled,
             debug: false,
+            ticked: 0,
+        }
 
-            instructions: [(0x00, "NOP", 1), (0x01, "LD BC,d16", 3), (0x02, "LD (BC),A", 1),
-            (0x03, "INC BC", 1), (0x04, "INC B", 1), (0x05, "DEC B", 1), (0x06, "LD B,d8", 2),
-            (0x07, "RLCA", 1), (0x08, "LD (a16),SP", 3), (0x09, "ADD HL,BC", 1), (0x0A, "LD A,(BC)", 1),
-            (0x0B, "DEC BC", 1), (0x0C, "INC C", 1), (0x0D, "DEC C", 1), (0x0E, "LD C,d8", 2),
-            (0x0F, "RRCA", 1), (0x10, "STOP 0", 2), (0x11, "LD DE,d16", 3), (0x12, "LD (DE),A", 1),
-            (0x13, "INC DE", 1), (0x14, "INC D", 1), (0x15, "DEC D", 1), (0x16, "LD D,d8", 2),
-            (0x17, "RLA", 1), (0x18, "JR r8", 2), (0x19, "ADD HL,DE", 1), (0x1A, "LD A,(DE)", 1), 
-            (0x1B, "DEC DE", 1), (0x1C, "INC E", 1), (0x1D, "DEC E", 1), (0x1E, "LD E,d8", 2),
-            (0x1F, "RRA", 1), (0x20, "JR NZ,r8", 2), (0x21, "LD HL,d16", 3), (0x22, "LD (HL+),A", 1),
-            (0x23, "INC HL", 1), (0x24, "INC H", 1), (0x25, "DEC H", 1), (0x26, "LD H,d8", 1), (0x27, "DAA", 1), 
-            (0x28, "JR Z,r8", 2), (0x29, "ADD HL,HL", 1), (0x2A, "LD A,(HL+)", 1), (0x2B, "DEC HL", 1),
-            (0x2C, "INC L", 1), (0x2D, "DEC L", 1), (0x2E, "LD L,d8", 2), (0x2F, "CPL", 1), (0x30, "JR NC,r8", 2),
-            (0x31, "LD SP,d16", 3), (0x32, "LD (HL-),A", 1), (0x33, "INC SP", 1), (0x34, "INC (HL)", 1),
-            (0x35, "DEC (HL)", 1), (0x36, "LD (HL),d8", 2), (0x37, "SCF", 1), (0x38, "JR C,r8", 2),
-            (0x39, "ADD HL,SP", 1), (0x3A, "LD A,(HL-)", 1), (0x3B, "DEC SP", 1), (0x3C, "INC A", 1),
-            (0x3D, "DEC A", 1), (0x3E, "LD A,d8", 2), (0x3F, "CCF", 1), (0x40, "LD B,B", 1), (0x41, "LD B,C", 1), (0x42, "LD B,D", 1),
-            (0x43, "LD B,E", 1), (0x44, "LD B,H", 1), (0x45, "LD B,L", 1), (0x46, "LD B,(HL)", 1),
-            (0x47, "LD B,A", 1), (0x48, "LD C,B", 1), (0x49, "LD C,C", 1), (0x4A, "LD C,D", 1),
-            (0x4B, "LD C,E", 1), (0x4C, "LD C,H", 1), (0x4D, "LD C,L", 1), (0x4E, "LD C,(HL)", 1), 
-            (0x4F, "LD C,A", 1), (0x50, "LD D,B", 1), (0x51, "LD D,C", 1), (0x52, "LD D,D", 1), (0x53, "LD D,E", 1),
-            (0x54, "LD D,H", 1), (0x55, "LD D,L", 1), (0x56, "LD D,(HL)", 1), (0x57, "LD D,A", 1),
-            (0x58, "LD E,B", 1), (0x59, "LD E,C", 1), (0x5A, "LD E,D", 1), (0x5B, "LD E,E", 1), (0x5C, "LD E,H", 1),
-            (0x5D, "LD E,L", 1), (0x5E, "LD E,(HL)", 1), (0x5F, "LD E,A", 1), (0x60, "LD H,B", 1), (0x61, "LD H,C", 1),
-            (0x62, "LD H,D", 1), (0x63, "LD H,E", 1), (0x64, "LD H,H", 1), (0x65, "LD H,L", 1), (0x66, "LD H,(HL)", 1),
-            (0x67, "LD H,A", 1), (0x68, "LD L,B", 1), (0x69, "LD L,C", 1), (0x6A, "LD L,D", 1), (0x6B, "LD L,E", 1),
-            (0x6C, "LD L,H", 1), (0x6D, "LD L,L", 1), (0x6E, "LD L,(HL)", 1), (0x6F, "LD L,A", 1), (0x70, "LD (HL),B", 1),
-            (0x71, "LD (HL),C", 1), (0x72, "LD (HL),D", 1), (0x73, "LD (HL),E", 1), (0x74, "LD (HL),H", 1),
-            (0x75, "LD (HL),L", 1), (0x76, "HALT", 1), (0x77, "LD (HL),A", 1), (0x78, "LD A,B", 1), (0x79, "LD A,C", 1),
-            (0x7A, "LD A,D", 1), (0x7B, "LD A,E", 1), (0x7C, "LD A,H", 1), (0x7D, "LD A,L", 1), (0x7E, "LD A,(HL)", 1),
-            (0x7F, "LD A,A", 1), (0x80, "ADD A,B", 1), (0x81, "ADD A,C", 1), (0x82, "ADD A,D", 1), (0x83, "ADD A,E", 1),
-            (0x84, "ADD A,H", 1), (0x85, "ADD A,L", 1), (0x86, "ADD A,(HL)", 1), (0x87, "ADD A,A", 1), (0x88, "ADC A,B", 1),
-            (0x89, "ADC A,C", 1), (0x8A, "ADC A,D", 1), (0x8B, "ADC A,E", 1), (0x8C, "ADC A,H", 1), (0x8D, "ADC A,L", 1),
-            (0x8E, "ADC A,(HL)", 1), (0x8F, "ADC A,A", 1), (0x90, "SUB B", 1), (0x91, "SUB C", 1), (0x92, "SUB D", 1),
-            (0x93, "SUB E", 1), (0x94, "SUB H", 1), (0x95, "SUB L", 1), (0x96, "SUB (HL)", 1), (0x97, "SUB A", 1),
-            (0x98, "SBC A,B", 1), (0x99, "SBC A,C", 1), (0x9A, "SBC A,D", 1), (0x9B, "SBC A,E", 1), (0x9C, "SBC A,H", 1),
-            (0x9D, "SBC A,L", 1), (0x9E, "SBC A,(HL)", 1), (0x9F, "SBC A,A", 1),(0xA0, "AND B", 1), (0xA1, "AND C", 1),
-            (0xA2, "AND D", 1), (0xA3, "AND E", 1), (0xA4, "AND H", 1), (0xA5, "AND L", 1), (0xA6, "AND (HL)", 1), 
-            (0xA7, "AND A", 1), (0xA8, "XOR B", 1), (0xA9, "XOR C", 1), (0xAA, "XOR D", 1), (0xAB, "XOR E", 1), (0xAC, "XOR H", 1),
-            (0xAD, "XOR L", 1), (0xAE, "XOR (H,L)", 1), (0xAF, "XOR A", 1), (0xB0, "OR B", 1), (0xB1, "OR C", 1),
-            (0xB2, "OR D", 1), (0xB3, "OR E", 1), (0xB4, "OR H", 1), (0xB5, "OR L", 1), (0xB6, "OR (HL)", 1), 
-            (0xB7, "OR A", 1), (0xB8, "CP B", 1), (0xB9, "CP C", 1), (0xBA, "CP D", 1), (0xBB, "CP E", 1), (0xBC, "CP H", 1),
-            (0xBD, "CP L", 1), (0xBE, "CP (H,L)", 1), (0xBF, "CP A", 1), (0xC0, "RET NZ", 1), (0xC1, "POP BC", 1), (0xC2, "JP NZ,a16", 3),
-            (0xC3, "JP a16", 3), (0xC4, "CALL NZ,a16", 3), (0xC5, "PUSH BC", 1), (0xC6, "ADD A,d8", 2), (0xC7, "RST 00H", 1),
-            (0xC8, "RET Z", 1), (0xC9, "RET", 1), (0xCA, "JP Z,a16", 3), (0xCB, "PREFIX CB", 1), (0xCC, "CALL Z,a16", 3),
-            (0xCD, "CALL a16", 3), (0xCE, "ADC A,d8", 2), (0xCF, "RST 08H", 1), (0xD0, "RET NC", 1), (0xD1, "POP DE", 1),
-            (0xD2, "JP NC,a16", 3), (0xD3, "?", 1), (0xD4, "CALL NC,a16", 3), (0xD5, "PUSH DE", 1), (0xD6, "SUB d8", 2),
-            (0xD7, "RST 10H", 1), (0xD8, "RET C", 1), (0xD9, "RETI", 1), (0xDA, "JP C,a16", 1), (0xDB, "?", 1),
-            (0xDC, "CALL C,a16", 3), (0xDD, "?", 1), (0xDE, "SBC A,d8", 2), (0xDF, "RST 18H", 1), (0xE0, "LDH (a8),A", 2),
-            (0xE1, "POP HL", 1), (0xE2, "LD (C),A", 1), (0xE3, "?", 1), (0xE4, "?", 1), (0xE5, "PUSH HL", 1), (0xE6, "AND d8", 2),
-            (0xE7, "RST 20H", 1), (0xE8, "ADD SP,r8", 2), (0xE9, "JP (HL)", 1), (0xEA, "LD (a16),A", 3),
-            (0xEB, "?", 1), (0xEC, "?", 1), (0xED, "?", 1), (0xEE, "XOR d8", 2), (0xEF, "RST 28H", 1),
-            (0xF0, "LDH A,(a8)", 2), (0xF1, "POP AF", 1), (0xF2, "LD A,(C)", 1), (0xF3, "DI", 1), (0xF4, "?", 1),
-            (0xF5, "PUSH AF", 1), (0xF6, "OR d8", 2), (0xF7, "RST 30H", 1), (0xF8, "LD HL,SP+r8", 2), (0xF9, "LD SP,HL", 1),
-            (0xFA, "LD A,(a16)", 3), (0xFB, "EI", 1), (0xFC, "?", 1), (0xFD, "?", 1), (0xFE, "CP d8", 2), (0xFF, "RST 38H", 1)],
-
-            cb_instructions: [(0x00, "RLC B"), (0x01, "RLC C"), (0x02, "RLC D"), (0x03, "RLC E"), (0x04, "RLC H"),
-            (0x05, "RLC L"), (0x06, "RLC (HL)"), (0x07, "RLC A"), (0x08, "RRC B"), (0x09, "RRC C"), (0x0A, "RRC D"),
-            (0x0B, "RRC E"), (0x0C, "RRC H"), (0x0D, "RRC L"), (0x0E, "RRC (HL)"), (0x0F, "RRC A"),
-            (0x10, "RL B"), (0x11, "RL C"), (0x12, "RL D"), (0x13, "RL E"), (0x14, "RL H"),
-            (0x15, "RL L"), (0x16, "RL (HL)"), (0x17, "RL A"), (0x18, "RR B"), (0x19, "RR C"), (0x1A, "RR D"),
-            (0x1B, "RR E"), (0x1C, "RR H"), (0x1D, "RR L"), (0x1E, "RR (HL)"), (0x1F, "RR A"),
-            (0x20, "SLA B"), (0x21, "SLA C"), (0x22, "SLA D"), (0x23, "SLA E"), (0x24, "SLA H"),
-            (0x25, "SLA L"), (0x26, "SLA (HL)"), (0x27, "SLA A"), (0x28, "SRA B"), (0x29, "SRA C"), (0x2A, "SRA D"),
-            (0x2B, "SRA E"), (0x2C, "SRA H"), (0x2D, "SRA L"), (0x2E, "SRA (HL)"), (0x2F, "SRA A"),
-            (0x30, "SWAP B"), (0x31, "SWAP C"), (0x32, "SWAP D"), (0x33, "SWAP E"), (0x34, "SWAP H"),
-            (0x35, "SWAP L"), (0x36, "SWAP (HL)"), (0x37, "SWAP A"), (0x38, "SRL B"), (0x39, "SRL C"), (0x3A, "SRL D"),
-            (0x3B, "SRL E"), (0x3C, "SRL H"), (0x3D, "SRL L"), (0x3E, "SRL (HL)"), (0x3F, "SRL A"),
-            (0x40, "BIT 0,B"), (0x41, "BIT 0,C"), (0x42, "BIT 0,D"), (0x43, "BIT 0,E"), (0x44, "BIT 0,H"),
-            (0x45, "BIT 0,L"), (0x46, "BIT 0,(HL)"), (0x47, "BIT 0,A"), (0x48, "BIT 1,B"), (0x49, "BIT 1,C"), (0x4A, "BIT 1,D"),
-            (0x4B, "BIT 1,E"), (0x4C, "BIT 1,H"), (0x4D, "BIT 1,L"), (0x4E, "BIT 1,(HL)"), (0x4F, "BIT 1,A"),
-            (0x50, "BIT 2,B"), (0x51, "BIT 2,C"), (0x52, "BIT 2,D"), (0x53, "BIT 2,E"), (0x54, "BIT 2,H"),
-            (0x55, "BIT 2,L"), (0x56, "BIT 2,(HL)"), (0x57, "BIT 2,A"), (0x58, "BIT 3,B"), (0x59, "BIT 3,C"), (0x5A, "BIT 3,D"),
-            (0x5B, "BIT 3,E"), (0x5C, "BIT 3,H"), (0x5D, "BIT 3,L"), (0x5E, "BIT 3,(HL)"), (0x5F, "BIT 3,A"),
-            (0x60, "BIT 4,B"), (0x61, "BIT 4,C"), (0x62, "BIT 4,D"), (0x63, "BIT 4,E"), (0x64, "BIT 4,H"),
-            (0x65, "BIT 4,L"), (0x66, "BIT 4,(HL)"), (0x67, "BIT 4,A"), (0x68, "BIT 5,B"), (0x69, "BIT 5,C"), (0x6A, "BIT 5,D"),
-            (0x6B, "BIT 5,E"), (0x6C, "BIT 5,H"), (0x6D, "BIT 5,L"), (0x6E, "BIT 5,(HL)"), (0x6F, "BIT 5,A"), 
-            (0x70, "BIT 6,B"), (0x71, "BIT 6,C"), (0x72, "BIT 6,D"), (0x73, "BIT 6,E"), (0x74, "BIT 6,H"),
-            (0x75, "BIT 6,L"), (0x76, "BIT 6,(HL)"), (0x77, "BIT 6,A"), (0x78, "BIT 7,B"), (0x79, "BIT 7,C"), (0x7A, "BIT 7,D"),
-            (0x7B, "BIT 7,E"), (0x7C, "BIT 7,H"), (0x7D, "BIT 7,L"), (0x7E, "BIT 7,(HL)"), (0x7F, "BIT 7,A"), 
-            (0x80, "RES 0,B"), (0x81, "RES 0,C"), (0x82, "RES 0,D"), (0x83, "RES 0,E"), (0x84, "RES 0,H"),
-            (0x85, "RES 0,L"), (0x86, "RES 0,(HL)"), (0x87, "RES 0,A"), (0x88, "RES 1,B"), (0x89, "RES 1,C"), (0x8A, "RES 1,D"),
-            (0x8B, "RES 1,E"), (0x8C, "RES 1,H"), (0x8D, "RES 1,L"), (0x8E, "RES 1,(HL)"), (0x8F, "RES 1,A"),
-            (0x90, "RES 2,B"), (0x91, "RES 2,C"), (0x92, "RES 2,D"), (0x93, "RES 2,E"), (0x94, "RES 2,H"),
-            (0x95, "RES 2,L"), (0x96, "RES 2,(HL)"), (0x97, "RES 2,A"), (0x98, "RES 3,B"), (0x99, "RES 3,C"), (0x9A, "RES 3,D"),
-            (0x9B, "RES 3,E"), (0x9C, "RES 3,H"), (0x9D, "RES 3,L"), (0x9E, "RES 3,(HL)"), (0x9F, "RES 3,A"),
-            (0xA0, "RES 4,B"), (0xA1, "RES 4,C"), (0xA2, "RES 4,D"), (0xA3, "RES 4,E"), (0xA4, "RES 4,H"),
-            (0xA5, "RES 4,L"), (0xA6, "RES 4,(HL)"), (0xA7, "RES 4,A"), (0xA8, "RES 5,B"), (0xA9, "RES 5,C"), (0xAA, "RES 5,D"),
-            (0xAB, "RES 5,E"), (0xAC, "RES 5,H"), (0xAD, "RES 5,L"), (0xAE, "RES 5,(HL)"), (0xAF, "RES 5,A"), 
-            (0xB0, "RES 6,B"), (0xB1, "RES 6,C"), (0xB2, "RES 6,D"), (0xB3, "RES 6,E"), (0xB4, "RES 6,H"),
-            (0xB5, "RES 6,L"), (0xB6, "RES 6,(HL)"), (0xB7, "RES 6,A"), (0xB8, "RES 7,B"), (0xB9, "RES 7,C"), (0xBA, "RES 7,D"),
-            (0xBB, "RES 7,E"), (0xBC, "RES 7,H"), (0xBD, "RES 7,L"), (0xBE, "RES 7,(HL)"), (0xBF, "RES 7,A"),
-            (0xC0, "SET 0,B"), (0xC1, "SET 0,C"), (0xC2, "SET 0,D"), (0xC3, "SET 0,E"), (0xC4, "SET 0,H"),
-            (0xC5, "SET 0,L"), (0xC6, "SET 0,(HL)"), (0xC7, "SET 0,A"), (0xC8, "SET 1,B"), (0xC9, "SET 1,C"), (0xCA, "SET 1,D"),
-            (0xCB, "SET 1,E"), (0xCC, "SET 1,H"), (0xCD, "SET 1,L"), (0xCE, "SET 1,(HL)"), (0xCF, "SET 1,A"),
-            (0xD0, "SET 2,B"), (0xD1, "SET 2,C"), (0xD2, "SET 2,D"), (0xD3, "SET 2,E"), (0xD4, "SET 2,H"),
-            (0xD5, "SET 2,L"), (0xD6, "SET 2,(HL)"), (0xD7, "SET 2,A"), (0xD8, "SET 3,B"), (0xD9, "SET 3,C"), (0xDA, "SET 3,D"),
-            (0xDB, "SET 3,E"), (0xDC, "SET 3,H"), (0xDD, "SET 3,L"), (0xDE, "SET 3,(HL)"), (0xDF, "SET 3,A"),
-            (0xE0, "SET 4,B"), (0xE1, "SET 4,C"), (0xE2, "SET 4,D"), (0xE3, "SET 4,E"), (0xE4, "SET 4,H"),
-            (0xE5, "SET 4,L"), (0xE6, "SET 4,(HL)"), (0xE7, "SET 4,A"), (0xE8, "SET 5,B"), (0xE9, "SET 5,C"), (0xEA, "SET 5,D"),
-            (0xEB, "SET 5,E"), (0xEC, "SET 5,H"), (0xED, "SET 5,L"), (0xEE, "SET 5,(HL)"), (0xEF, "SET 5,A"), 
-            (0xF0, "SET 6,B"), (0xF1, "SET 6,C"), (0xF2, "SET 6,D"), (0xF3, "SET 6,E"), (0xF4, "SET 6,H"),
-            (0xF5, "SET 6,L"), (0xF6, "SET 6,(HL)"), (0xF7, "SET 6,A"), (0xF8, "SET 7,B"), (0xF9, "SET 7,C"), (0xFA, "SET 7,D"),
-            (0xFB, "SET 7,E"), (0xFC, "SET 7,H"), (0xFD, "SET 7,L"), (0xFE, "SET 7,(HL)"), (0xFF, "SET 7,A")],
+    }
 
-        }
+    //Run the rest of the system for one M-cycle
+    fn tick(&mut self) {
+        self.memory.tick(1);
+        self.ticked += 1;
+    }
 
+    //Every bus access takes one M-cycle, the rest of the system is run before it happens
+    fn read_byte(&mut self, address: u16) -> u8 {
+        self.tick();
+        self.memory.read_byte(address)
+    }
+
+    fn write_byte(&mut self, address: u16, data: u8) {
+        self.tick();
+        self.memory.write_byte(address, data);
+    }
+
+    fn read_word(&mut self, address: u16) -> u16 {
+        let lower = self.read_byte(address) as u16;
+        let upper = self.read_byte(address.wrapping_add(1)) as u16;
+        (upper << 8) | lower
+    }
+
+    fn write_word(&mut self, address: u16, data: u16) {
+        self.write_byte(address, data as u8);
+        self.write_byte(address.wrapping_add(1), (data >> 8) as u8);
     }
 
     //Fetch next byte and increase program counter by one
     fn next_byte(&mut self) -> u8 {
-        let data = self.memory.read_byte(self.registers.pc);
-        self.registers.pc += 1;
+        let data = self.read_byte(self.registers.pc);
+        if self.halt_bug {
+            self.halt_bug = false;
+        }
+        else {
+            self.registers.pc += 1;
+        }
         data
     }
 
     fn next_word(&mut self) -> u16 {
-        let data = self.memory.read_word(self.registers.pc);
-        self.registers.pc += 2;
+        let data = self.read_word(self.registers.pc);
+        self.registers.pc = self.registers.pc.wrapping_add(2);
         data
     }
 
     //Stack pointer points to most recent item on stack
 
     //push register pair nn onto stack and decrement stack pointer twice
+    //Takes an internal M-cycle before the upper byte is written
     fn push_word(&mut self, data: u16) {
-        self.registers.sp -= 2;
-        self.memory.write_word(self.registers.sp, data);
-
+        self.tick();
+        self.registers.sp = self.registers.sp.wrapping_sub(1);
+        self.write_byte(self.registers.sp, (data >> 8) as u8);
+        self.registers.sp = self.registers.sp.wrapping_sub(1);
+        self.write_byte(self.registers.sp, data as u8);
     }
 
     //pop word and increment stack pointer twice
     fn pop_word(&mut self) -> u16 {
-        let word = self.memory.read_word(self.registers.sp);
-        self.registers.sp += 2;
+        let word = self.read_word(self.registers.sp);
+        self.registers.sp = self.registers.sp.wrapping_add(2);
         word
     }
 
@@ -280,35 +267,15 @@ impl Cpu {
     }
 
     //Add 8 bit value + carry, set appropriate values
-    fn add8_carry(&mut self, mut data: u8) {
-        if self.registers.check_carry() {
-            if self.registers.a < data {
-                self.registers.a = self.registers.a.wrapping_add(1);
-            }
-            else {
-                data = data.wrapping_add(1);
-            }
-        }
-        if ((self.registers.a & 0xF) + (data & 0xF)) & 0x10 == 0x10 {
-            self.registers.set_halfcarry(1);
-        }
-        else {
-            self.registers.set_halfcarry(0);
-        }
-        if data as u16 + self.registers.a as u16 > 0xFF {
-            self.registers.set_carry(1);
-        }
-        else {
-            self.registers.set_carry(0);
-        }
-        self.registers.a = self.registers.a.wrapping_add(data);
-        if self.registers.a == 0 {
-            self.registers.set_zero(1);
-        }
-        else {
-            self.registers.set_zero(0);
-        }
+    fn add8_carry(&mut self, data: u8) {
+        let carry = self.registers.check_carry() as u8;
+        let result = self.registers.a as u16 + data as u16 + carry as u16;
+        let half = (self.registers.a & 0xF) + (data & 0xF) + carry > 0xF;
+        self.registers.a = result as u8;
+        self.registers.set_zero((self.registers.a == 0) as u8);
         self.registers.set_addsub(0);
+        self.registers.set_halfcarry(half as u8);
+        self.registers.set_carry((result > 0xFF) as u8);
     }
 
     //Sub 8 bit value from register a, set appropriate flags
@@ -336,35 +303,15 @@ impl Cpu {
     }
 
     //Sub 8 bit value and carry, set appropriate flags
-    fn sub8_carry(&mut self, mut data: u8) {
-        if self.registers.check_carry() {
-            if self.registers.a > data {
-                self.registers.a = self.registers.a.wrapping_sub(1);
-            }
-            else {
-                data = data.wrapping_sub(1);
-            }
-        }
-        if data & 0xF > self.registers.a & 0xF {
-            self.registers.set_halfcarry(1);
-        }
-        else {
-            self.registers.set_halfcarry(0);
-        }
-        if data & 0xFF > self.registers.a & 0xFF {
-            self.registers.set_carry(1);
-        }
-        else {
-            self.registers.set_carry(0);
-        }
-        self.registers.a = self.registers.a.wrapping_sub(data);
-        if self.registers.a == 0 {
-            self.registers.set_zero(1);
-        }
-        else {
-            self.registers.set_zero(0);
-        }
+    fn sub8_carry(&mut self, data: u8) {
+        let carry = self.registers.check_carry() as u8;
+        let half = (self.registers.a & 0xF) < (data & 0xF) + carry;
+        let borrow = (self.registers.a as u16) < data as u16 + carry as u16;
+        self.registers.a = self.registers.a.wrapping_sub(data).wrapping_sub(carry);
+        self.registers.set_zero((self.registers.a == 0) as u8);
         self.registers.set_addsub(1);
+        self.registers.set_halfcarry(half as u8);
+        self.registers.set_carry(borrow as u8);
     }
 
     //Bitwise AND with register a, store value in a
@@ -410,15 +357,11 @@ impl Cpu {
         self.registers.set_carry(0);
     }
 
-    //Fix flags and implementation
+    //Compare sets flags like SUB but leaves a alone
     fn cmp(&mut self, data: u8) {
-        if (self.registers.a.wrapping_sub(data)) == 0 {
-            self.registers.set_zero(1);
-        }
-        else {
-            self.registers.set_zero(0);
-        }
-        self.registers.set_addsub(1);
+        let a = self.registers.a;
+        self.sub8(data);
+        self.registers.a = a;
     }
 
     //INC, check for zero and half-carry flag
@@ -458,66 +401,117 @@ impl Cpu {
         data
     }
 
-    
+    //Half carry is from bit 11 and carry from bit 15, zero is left alone
     fn add_hl(&mut self, data: u16) {
-        if self.registers.hl() & 0x07FF + data & 0x07FF > 0x07FF {
-            self.registers.set_halfcarry(1);
-        }
-        else {
-            self.registers.set_halfcarry(0);
-        }
-        if data as u32 + self.registers.a as u32 > 0xFFFF {
-            self.registers.set_carry(1);
-        }
-        else {
-            self.registers.set_carry(0);
-        }
-        self.registers.set_hl(self.registers.hl().wrapping_add(data));
+        let hl = self.registers.hl();
+        let half = (hl & 0x0FFF) + (data & 0x0FFF) > 0x0FFF;
+        let result = hl as u32 + data as u32;
+        self.registers.set_hl(result as u16);
         self.registers.set_addsub(0);
+        self.registers.set_halfcarry(half as u8);
+        self.registers.set_carry((result > 0xFFFF) as u8);
     }
-    
-    fn add_sp(&mut self, data: u16) {
-        if ((self.registers.sp & 0xF0) + (data as u16 & 0xF0)) & 0x100 == 0x100 {
-            self.registers.set_halfcarry(1);
-        }
-        else {
-            self.registers.set_halfcarry(0);
-        }
-        if data as u32 + self.registers.sp as u32 > 0xFFFF {
-            self.registers.set_carry(1);
+
+    //SP plus a signed byte for ADD SP,r8 and LD HL,SP+r8
+    //Flags come from an unsigned add of the low byte
+    fn add_sp(&mut self, data: u8) -> u16 {
+        let sp = self.registers.sp;
+        let half = (sp & 0x0F) + (data as u16 & 0x0F) > 0x0F;
+        let carry = (sp & 0xFF) + data as u16 > 0xFF;
+        self.registers.set_zero(0);
+        self.registers.set_addsub(0);
+        self.registers.set_halfcarry(half as u8);
+        self.registers.set_carry(carry as u8);
+        sp.wrapping_add(data as i8 as u16)
+    }
+
+    //Decimal adjust a after a BCD add or subtract, using N, H and C from that instruction
+    fn daa(&mut self) {
+        let mut a = self.registers.a;
+        let mut carry = self.registers.check_carry();
+        if !self.registers.check_addsub() {
+            if carry || a > 0x99 {
+                a = a.wrapping_add(0x60);
+                carry = true;
+            }
+            if self.registers.check_halfcarry() || a & 0x0F > 0x09 {
+                a = a.wrapping_add(0x06);
+            }
         }
         else {
-            self.registers.set_carry(0);
+            if carry {
+                a = a.wrapping_sub(0x60);
+            }
+            if self.registers.check_halfcarry() {
+                a = a.wrapping_sub(0x06);
+            }
         }
-        self.registers.sp += data as u16;
-        self.registers.set_zero(0);
-        self.registers.set_addsub(0);
+        self.registers.a = a;
+        self.registers.set_zero((a == 0) as u8);
+        self.registers.set_halfcarry(0);
+        self.registers.set_carry(carry as u8);
     }
     
 
 
     
-    pub fn cycle(&mut self) -> u8 {
-
+    //Push the address of the next instruction and jump to the handler, takes 5 M-cycles
+    fn service_interrupt(&mut self) -> u8 {
+        self.ime = Ime::Disabled;
+        let mut pc = self.registers.pc;
+        //EI then HALT with an interrupt pending hits the HALT bug, the handler returns to the HALT
+        if self.halt_bug {
+            self.halt_bug = false;
+            pc = pc.wrapping_sub(1);
+        }
+        self.tick();
+        self.tick();
+        self.registers.sp = self.registers.sp.wrapping_sub(1);
+        self.write_byte(self.registers.sp, (pc >> 8) as u8);
+
+        //The upper byte can land on IE, the interrupt is picked after it's written
+        //If nothing is left pending the cpu ends up at 0x0000
+        let interrupt = self.memory.interrupts.highest();
+        self.registers.sp = self.registers.sp.wrapping_sub(1);
+        self.write_byte(self.registers.sp, pc as u8);
+
+        match interrupt {
+            Some(interrupt) => {
+                self.memory.interrupts.acknowledge(interrupt);
+                self.registers.pc = interrupt.vector();
+            }
+            None => self.registers.pc = 0x0000,
+        }
+        self.tick();
+        5
+    }
 
-        if self.interrupts_enabled {
+    //Run one instruction, interrupt dispatch or halted M-cycle, returns the M-cycles it took
+    //The rest of the system is clocked as the cpu goes so memory accesses land on the right cycle
+    pub fn cycle(&mut self) -> u8 {
+        self.ticked = 0;
 
-            //Push current address to stack and go to vblank interrupt handler
-            if self.memory.vram.vblank_int_enable && self.memory.vram.vblank_int_request {
-                println!("Vblank Request Flags {}", self.memory.vram.vblank_int_request);
-                self.interrupts_enabled = false;
-                self.memory.vram.vblank_int_request = false;
-                self.push_word(self.registers.pc + 1);
-                self.registers.pc = 0x0040;
+        //STOP leaves the cpu and lcd off until a joypad line goes low
+        if self.stopped {
+            if !self.memory.joypad_pressed() {
+                return 0
             }
-            //Push current address to stack and go to lcd stat interrupt handler
-            else if self.memory.vram.lcd_stat_int_enable && self.memory.vram.lcd_stat_int_request {
-                self.interrupts_enabled = false;
-                self.memory.vram.lcd_stat_int_request = false;
-                self.push_word(self.registers.pc + 1);
-                self.registers.pc = 0x0048;
+            self.stopped = false;
+        }
+
+        //While halted nothing is fetched, any pending interrupt wakes the cpu even with IME off
+        if self.halted {
+            if self.memory.interrupts.pending() == 0 {
+                self.tick();
+                return 1
             }
+            self.halted = false;
+        }
+
+        if self.ime == Ime::Enabled && self.memory.interrupts.pending() != 0 {
+            return self.service_interrupt()
         }
+        let enable_interrupts = self.ime == Ime::Pending;
 
         let opcode = self.next_byte();
 
@@ -525,329 +519,85 @@ impl Cpu {
             self.print_current_status(opcode, false);
         }
 
-        let cycles: u8 = match opcode {
-
-            //load n with immediate 8 bit value
-            0x06 => {self.registers.b = self.next_byte(); 2}, //load b with n
-            0x0E => {self.registers.c = self.next_byte(); 2}, //load c with n
-            0x16 => {self.registers.d = self.next_byte(); 2}, //load d with n
-            0x1E => {self.registers.e = self.next_byte(); 2}, //load e with n
-            0x26 => {self.registers.h = self.next_byte(); 2}, //load h with n
-            0x2E => {self.registers.l = self.next_byte(); 2}, //load l with n
-            
-            //put value r2 into r1
-            //r1=a
-            0x7F => {self.registers.a = self.registers.a; 1},
-            0x78 => {self.registers.a = self.registers.b; 1},
-            0x79 => {self.registers.a = self.registers.c; 1}, 
-            0x7A => {self.registers.a = self.registers.d; 1}
-            0x7B => {self.registers.a = self.registers.e; 1},
-            0x7C => {self.registers.a = self.registers.h; 1},
-            0x7D => {self.registers.a = self.registers.l; 1},
-            0x0A => {self.registers.a = self.memory.read_byte(self.registers.bc()); 2},
-            0x1A => {self.registers.a = self.memory.read_byte(self.registers.de()); 2},
-            0x7E => {self.registers.a = self.memory.read_byte(self.registers.hl()); 2},
-            0xFA => {let word = self.next_word(); self.registers.a = self.memory.read_byte(word); 4},
-            0x3E => {self.registers.a = self.next_byte(); 2},
-            //r1=b
-            0x40 => {self.registers.b = self.registers.b; 1},
-            0x41 => {self.registers.b = self.registers.c; 1},
-            0x42 => {self.registers.b = self.registers.d; 1},
-            0x43 => {self.registers.b = self.registers.e; 1},
-            0x44 => {self.registers.b = self.registers.h; 1},
-            0x45 => {self.registers.b = self.registers.l; 1},
-            0x46 => {self.registers.b = self.memory.read_byte(self.registers.hl()); 2},
-            0x47 => {self.registers.b = self.registers.a; 1},
-            //r1=c
-            0x48 => {self.registers.c = self.registers.b; 1},
-            0x49 => {self.registers.c = self.registers.c; 1},
-            0x4A => {self.registers.c = self.registers.d; 1},
-            0x4B => {self.registers.c = self.registers.e; 1},
-            0x4C => {self.registers.c = self.registers.h; 1},
-            0x4D => {self.registers.c = self.registers.l; 1},
-            0x4E => {self.registers.c = self.memory.read_byte(self.registers.hl()); 2},
-            0x4F => {self.registers.c = self.registers.a; 1},
-            //r1=d
-            0x50 => {self.registers.d = self.registers.b; 1},
-            0x51 => {self.registers.d = self.registers.c; 1},
-            0x52 => {self.registers.d = self.registers.d; 1},
-            0x53 => {self.registers.d = self.registers.e; 1},
-            0x54 => {self.registers.d = self.registers.h; 1},
-            0x55 => {self.registers.d = self.registers.l; 1},
-            0x56 => {self.registers.d = self.memory.read_byte(self.registers.hl()); 2},
-            0x57 => {self.registers.d = self.registers.a; 1},
-            //r1=e
-            0x58 => {self.registers.e = self.registers.b; 1},
-            0x59 => {self.registers.e = self.registers.c; 1},
-            0x5A => {self.registers.e = self.registers.d; 1},
-            0x5B => {self.registers.e = self.registers.e; 1},
-            0x5C => {self.registers.e = self.registers.h; 1},
-            0x5D => {self.registers.e = self.registers.l; 1},
-            0x5E => {self.registers.e = self.memory.read_byte(self.registers.hl()); 2},
-            0x5F => {self.registers.e = self.registers.a; 1},
-            //r1=h
-            0x60 => {self.registers.h = self.registers.b; 1},
-            0x61 => {self.registers.h = self.registers.c; 1},
-            0x62 => {self.registers.h = self.registers.d; 1},
-            0x63 => {self.registers.h = self.registers.e; 1},
-            0x64 => {self.registers.h = self.registers.h; 1},
-            0x65 => {self.registers.h = self.registers.l; 1},
-            0x66 => {self.registers.h = self.memory.read_byte(self.registers.hl()); 2},
-            0x67 => {self.registers.h = self.registers.a; 1},
-            //r1=l
-            0x68 => {self.registers.l = self.registers.b; 1},
-            0x69 => {self.registers.l = self.registers.c; 1},
-            0x6A => {self.registers.l = self.registers.d; 1},
-            0x6B => {self.registers.l = self.registers.e; 1},
-            0x6C => {self.registers.l = self.registers.h; 1},
-            0x6D => {self.registers.l = self.registers.l; 1},
-            0x6E => {self.registers.l = self.memory.read_byte(self.registers.hl()); 2},
-            0x6F => {self.registers.l = self.registers.a; 1},
-            //write 8 bits to memory pointed to by HL
-            0x70 => {self.memory.write_byte(self.registers.hl(), self.registers.b); 2},
-            0x71 => {self.memory.write_byte(self.registers.hl(), self.registers.c); 2},
-            0x72 => {self.memory.write_byte(self.registers.hl(), self.registers.d); 2},
-            0x73 => {self.memory.write_byte(self.registers.hl(), self.registers.e); 2},
-            0x74 => {self.memory.write_byte(self.registers.hl(), self.registers.h); 2},
-            0x75 => {self.memory.write_byte(self.registers.hl(), self.registers.l); 2},
-            0x36 => {let byte = self.next_byte(); self.memory.write_byte(self.registers.hl(), byte); 3},
-            //write value of a to memory
-            0x02 => {self.memory.write_byte(self.registers.bc(), self.registers.a); 2},
-            0x12 => {self.memory.write_byte(self.registers.de(), self.registers.a); 2},
-            0x77 => {self.memory.write_byte(self.registers.hl(), self.registers.a); 2},
-            0xEA => {let word = self.next_word(); self.memory.write_byte(word, self.registers.a); 4},
-            //LD A,(C)
-            0xF2 => {self.registers.a = self.memory.read_byte(0xFF00 + self.registers.c as u16); 2},
-            //LD (C), A
-            0xE2 => {self.memory.write_byte(0xFF00 + self.registers.c as u16, self.registers.a); 2},
-            //LD A,(HLD)
-            0x3A => {self.registers.a = self.memory.read_byte(self.registers.hl()); self.registers.set_hl(self.registers.hl().wrapping_sub(1)); 2},
-            //LD (HLD), A
-            0x32 => {self.memory.write_byte(self.registers.hl(), self.registers.a); self.registers.set_hl(self.registers.hl().wrapping_sub(1)); 2},
-            //LD A, (HLI)
-            0x2A => {self.registers.a = self.memory.read_byte(self.registers.hl()); self.registers.set_hl(self.registers.hl().wrapping_add(1)); 2},
-            //LD (HLI), A
-            0x22 => {self.memory.write_byte(self.registers.hl(), self.registers.a); self.registers.set_hl(self.registers.hl().wrapping_add(1)); 2},
-            //LDH (n), A
-            0xE0 => {let byte = self.next_byte(); self.memory.write_byte(0xFF00 + byte as u16, self.registers.a); 3},
-            //LDH A, (n)
-            0xF0 => {let byte = self.next_byte(); self.registers.a = self.memory.read_byte(0xFF00 + byte as u16); 3},
-
-            //16 bit loads
-            0x01 => {let word = self.next_word(); self.registers.set_bc(word); 3},
-            0x11 => {let word = self.next_word(); self.registers.set_de(word); 3},
-            0x21 => {let word = self.next_word(); self.registers.set_hl(word); 3},
-            0x31 => {self.registers.sp = self.next_word(); 3},
-            0xF9 => {self.registers.sp = self.registers.hl(); 2},
-            //Check flags for this one
-            0xF8 => {let byte = self.next_byte(); self.registers.set_hl(self.registers.sp + byte as u16); 3},
-            //
-            0x08 => {let word = self.next_word(); self.memory.write_word(word, self.registers.sp); 5},
-            //PUSH nn
-            0xF5 => {self.push_word(self.registers.af()); 4},
-            0xC5 => {self.push_word(self.registers.bc()); 4},
-            0xD5 => {self.push_word(self.registers.de()); 4},
-            0xE5 => {self.push_word(self.registers.hl()); 4},
-            //POP nn
-            0xF1 => {let word = self.pop_word(); self.registers.set_af(word); 3},
-            0xC1 => {let word = self.pop_word(); self.registers.set_bc(word); 3},
-            0xD1 => {let word = self.pop_word(); self.registers.set_de(word); 3},
-            0xE1 => {let word = self.pop_word(); self.registers.set_hl(word); 3},
-
-            //8 bit ALU - add n to a
-            0x87 => {self.add8(self.registers.a); 1},
-            0x80 => {self.add8(self.registers.b); 1},
-            0x81 => {self.add8(self.registers.c); 1},
-            0x82 => {self.add8(self.registers.d); 1},
-            0x83 => {self.add8(self.registers.e); 1},
-            0x84 => {self.add8(self.registers.h); 1},
-            0x85 => {self.add8(self.registers.l); 1},
-            0x86 => {self.add8(self.memory.read_byte(self.registers.hl())); 2},
-            0xC6 => {let byte = self.next_byte(); self.add8(byte); 2},
-            //8 bit add n + carry flag to A
-            0x8F => {self.add8_carry(self.registers.a); 1},
-            0x88 => {self.add8_carry(self.registers.b); 1},
-            0x89 => {self.add8_carry(self.registers.c); 1},
-            0x8A => {self.add8_carry(self.registers.d); 1},
-            0x8B => {self.add8_carry(self.registers.e); 1},
-            0x8C => {self.add8_carry(self.registers.h); 1},
-            0x8D => {self.add8_carry(self.registers.l); 1},
-            0x8E => {self.add8_carry(self.memory.read_byte(self.registers.hl())); 2},
-            0xCE => {let byte = self.next_byte(); self.add8_carry(byte); 2},
-            //8 bit subtract n from A
-            0x97 => {self.sub8(self.registers.a); 1},
-            0x90 => {self.sub8(self.registers.b); 1},
-            0x91 => {self.sub8(self.registers.c); 1},
-            0x92 => {self.sub8(self.registers.d); 1},
-            0x93 => {self.sub8(self.registers.e); 1},
-            0x94 => {self.sub8(self.registers.h); 1},
-            0x95 => {self.sub8(self.registers.l); 1},
-            0x96 => {self.sub8(self.memory.read_byte(self.registers.hl())); 2},
-            0xD6 => {let byte = self.next_byte(); self.sub8(byte); 2},
-            //8 bit subtract n from A with carry
-            0x9F => {self.sub8_carry(self.registers.a); 1},
-            0x98 => {self.sub8_carry(self.registers.b); 1},
-            0x99 => {self.sub8_carry(self.registers.c); 1},
-            0x9A => {self.sub8_carry(self.registers.d); 1},
-            0x9B => {self.sub8_carry(self.registers.e); 1},
-            0x9C => {self.sub8_carry(self.registers.h); 1},
-            0x9D => {self.sub8_carry(self.registers.l); 1},
-            0x9E => {self.sub8_carry(self.memory.read_byte(self.registers.hl())); 2},
-            0xDE => {let byte = self.next_byte(); self.sub8_carry(byte); 2},
-            //8 bit AND
-            0xA7 => {self.and(self.registers.a); 1},
-            0xA0 => {self.and(self.registers.b); 1},
-            0xA1 => {self.and(self.registers.c); 1},
-            0xA2 => {self.and(self.registers.d); 1},
-            0xA3 => {self.and(self.registers.e); 1},
-            0xA4 => {self.and(self.registers.h); 1},
-            0xA5 => {self.and(self.registers.l); 1},
-            0xA6 => {self.and(self.memory.read_byte(self.registers.hl())); 2},
-            0xE6 => {let byte = self.next_byte(); self.and(byte); 2},
-            //8 bit OR
-            0xB7 => {self.or(self.registers.a); 1},
-            0xB0 => {self.or(self.registers.b); 1},
-            0xB1 => {self.or(self.registers.c); 1},
-            0xB2 => {self.or(self.registers.d); 1},
-            0xB3 => {self.or(self.registers.e); 1},
-            0xB4 => {self.or(self.registers.h); 1},
-            0xB5 => {self.or(self.registers.l); 1},
-            0xB6 => {self.or(self.memory.read_byte(self.registers.hl())); 2},
-            0xF6 => {let byte = self.next_byte(); self.or(byte); 2},
-            //8 bit XOR
-            0xAF => {self.xor(self.registers.a); 1},
-            0xA8 => {self.xor(self.registers.b); 1},
-            0xA9 => {self.xor(self.registers.c); 1},
-            0xAA => {self.xor(self.registers.d); 1},
-            0xAB => {self.xor(self.registers.e); 1},
-            0xAC => {self.xor(self.registers.h); 1},
-            0xAD => {self.xor(self.registers.l); 1},
-            0xAE => {self.xor(self.memory.read_byte(self.registers.hl())); 2},
-            0xEE => {let byte = self.next_byte(); self.xor(byte); 2},
-            //8 bit compare n with a
-            0xBF => {self.cmp(self.registers.b); 1},
-            0xB8 => {self.cmp(self.registers.a); 1},
-            0xB9 => {self.cmp(self.registers.c); 1},
-            0xBA => {self.cmp(self.registers.d); 1},
-            0xBB => {self.cmp(self.registers.e); 1},
-            0xBC => {self.cmp(self.registers.h); 1},
-            0xBD => {self.cmp(self.registers.l); 1},
-            0xBE => {self.cmp(self.memory.read_byte(self.registers.hl())); 2},
-            0xFE => {let byte = self.next_byte(); self.cmp(byte); 2},
-            //INC register n
-            0x3C => {self.registers.a = self.inc(self.registers.a); 1},
-            0x04 => {self.registers.b = self.inc(self.registers.b); 1},
-            0x0C => {self.registers.c = self.inc(self.registers.c); 1},
-            0x14 => {self.registers.d = self.inc(self.registers.d); 1},
-            0x1C => {self.registers.e = self.inc(self.registers.e); 1},
-            0x24 => {self.registers.h = self.inc(self.registers.h); 1},
-            0x2C => {self.registers.l = self.inc(self.registers.l); 1},
-            0x34 => {let inc = self.inc(self.memory.read_byte(self.registers.hl())); self.memory.write_byte(self.registers.hl(), inc); 3},
-            //DEC register n
-            0x3D => {self.registers.a = self.dec(self.registers.a); 1},
-            0x05 => {self.registers.b = self.dec(self.registers.b); 1},
-            0x0D => {self.registers.c = self.dec(self.registers.c); 1},
-            0x15 => {self.registers.d = self.dec(self.registers.d); 1},
-            0x1D => {self.registers.e = self.dec(self.registers.e); 1},
-            0x25 => {self.registers.h = self.dec(self.registers.h); 1},
-            0x2D => {self.registers.l = self.dec(self.registers.l); 1},
-            0x35 => {let dec = self.dec(self.memory.read_byte(self.registers.hl())); self.memory.write_byte(self.registers.hl(), dec); 3},
-            //Add to HL
-            0x09 => {self.add_hl(self.registers.bc()); 2},
-            0x19 => {self.add_hl(self.registers.de()); 2},
-            0x29 => {self.add_hl(self.registers.hl()); 2},
-            0x39 => {self.add_hl(self.registers.sp); 2},
-            //Add to SP
-            0xE8 => {let byte = self.next_byte(); self.add_sp(byte as u16 ); 4},
-            //INC register nn
-            0x03 => {self.registers.set_bc(self.registers.bc().wrapping_add(1)); 2},
-            0x13 => {self.registers.set_de(self.registers.de().wrapping_add(1)); 2},
-            0x23 => {self.registers.set_hl(self.registers.hl().wrapping_add(1)); 2},
-            0x33 => {self.registers.sp += 1; 2},
-            //DEC register nn
-            0x0B => {self.registers.set_bc(self.registers.bc().wrapping_sub(1)); 2},
-            0x1B => {self.registers.set_de(self.registers.de().wrapping_sub(1)); 2},
-            0x2B => {self.registers.set_hl(self.registers.hl().wrapping_sub(1)); 2},
-            0x3B => {self.registers.sp = self.registers.sp.wrapping_sub(1); 2},
-            //Decimal adjust register A
-            0x27 => {self.registers.check_halfcarry(); self.registers.check_addsub(); 1}, //Implement
-            //CPL Register A
-            0x2F => {self.cpl(); 1},
-            //CCF
-            0x3F => {self.ccf(); 1},
-            //SCF
-            0x37 => {self.scf(); 1},
-            //NOP
-            0x0 => {1},
-            //HALT - power down cpu until interrupt occurs
-            0x76 => {self.halted = true; 1},
-            //STOP -halt cpu and lcd display until button pressed
-            0x10 => {self.stopped = true; 1},
-            //Make sure these two wait until after instruction is 
-            //executed to change interrupt status
-            //DI 
-            0xF3 => {self.interrupts_enabled = false; 1},
-            //EI
-            0xFB => {self.interrupts_enabled = true; 1},
-            //RLCA - rotate A left. old bit 7 to carry flag
-            0x07 => {self.rlca(); 1},
-            //RLA - rotate A left through Carry flag
-            0x17 => {self.rla(); 1},
-            //RRCA - rotate A right. old bit 0 to Carry flag
-            0x0F => {self.rrca(); 1},
-            //RRA - rotate A right through Carry flag
-            0x1F => {self.rra(); 1},
-            //JP nn
-            0xC3 => {self.registers.pc = self.next_word(); 3},
-            //JP to nn if coniditon is true
-            0xC2 => {if !self.registers.check_zero() {self.registers.pc = self.next_word(); 4} else {self.registers.pc += 2; 3}},
-            0xCA => {if self.registers.check_zero() {self.registers.pc = self.next_word(); 4} else {self.registers.pc += 2; 3}},
-            0xD2 => {if !self.registers.check_carry() {self.registers.pc = self.next_word(); 4} else {self.registers.pc += 2; 3}},
-            0xDA => {if self.registers.check_carry() {self.registers.pc = self.next_word(); 4} else {self.registers.pc += 2; 3}},
-            //JP to address contained in HL
-            0xE9 => {self.registers.pc = self.registers.hl(); 1},
-            //JR n - add n to current address and jump to it
-            0x18 => {let jump = self.next_byte() as i8; self.registers.pc = self.registers.pc.wrapping_add(jump as u16); 2},
-            //JR cc,n - add n to current address and jump if flag is set
-            0x20 => {let jump = self.next_byte() as i8; if !self.registers.check_zero() {self.registers.pc = self.registers.pc.wrapping_add(jump as u16); 3} else {2}},
-            0x28 => {let jump = self.next_byte() as i8; if self.registers.check_zero() {self.registers.pc = self.registers.pc.wrapping_add(jump as u16); 3} else {2}},
-            0x30 => {let jump = self.next_byte() as i8; if !self.registers.check_carry() {self.registers.pc = self.registers.pc.wrapping_add(jump as u16); 3} else {2}},
-            0x38 => {let jump = self.next_byte() as i8; if self.registers.check_carry() {self.registers.pc = self.registers.pc.wrapping_add(jump as u16); 3} else {2}},
-            //Calls
-            //Call nn, push address of next instruction onto stack, then jump to nn
-            //Not sure about this one
-            0xCD => {self.push_word(self.registers.pc + 2); self.registers.pc = self.next_word(); 6},
-            //Call nn if condition is true
-            0xCC => {if self.registers.check_zero() {self.push_word(self.registers.pc + 2); self.registers.pc = self.next_word(); 6} else {self.registers.pc += 2; 3}},
-            0xD4 => {if !self.registers.check_carry() {self.push_word(self.registers.pc + 2); self.registers.pc = self.next_word(); 6} else {self.registers.pc += 2; 3}},
-            0xC4 => {if !self.registers.check_zero() {self.push_word(self.registers.pc + 2); self.registers.pc = self.next_word(); 6} else {self.registers.pc += 2; 3}},
-            0xDC => {if self.registers.check_carry() {self.push_word(self.registers.pc + 2); self.registers.pc = self.next_word(); 6} else {self.registers.pc += 2; 3}},
-            //Restarts - push present address to stack, jump to $0000 + x
-            0xC7 => {self.push_word(self.registers.pc); self.registers.pc = 0x00; 4},
-            0xCF => {self.push_word(self.registers.pc); self.registers.pc = 0x08; 4},
-            0xD7 => {self.push_word(self.registers.pc); self.registers.pc = 0x10; 4},
-            0xDF => {self.push_word(self.registers.pc); self.registers.pc = 0x18; 4},
-            0xE7 => {self.push_word(self.registers.pc); self.registers.pc = 0x20; 4},
-            0xEF => {self.push_word(self.registers.pc); self.registers.pc = 0x28; 4},
-            0xF7 => {self.push_word(self.registers.pc); self.registers.pc = 0x30; 4},
-            0xFF => {self.push_word(self.registers.pc); self.registers.pc = 0x38; 4},
-            //RET 
-            0xC9 => {self.registers.pc = self.pop_word(); 4},
-            //RET cc
-            0xC0 => {if !self.registers.check_zero() {self.registers.pc = self.pop_word(); 5} else {2}},
-            0xC8 => {if self.registers.check_zero() {self.registers.pc = self.pop_word(); 5} else {2}},
-            0xD0 => {if !self.registers.check_carry() {self.registers.pc = self.pop_word(); 5} else {2}},
-            0xD8 => {if self.registers.check_carry() {self.registers.pc = self.pop_word(); 5} else {2}},
-            //RETI - pop two bytes and jump to address, enable interrupts
-            0xD9 => {self.registers.pc = self.pop_word(); self.interrupts_enabled = true; 4},
-            //CB
-            //CHECK CYCLES FOR THIS ONE
-            0xCB => {let byte = self.next_byte(); self.cb_decode(byte) + 1},
-            _ => {println!("This opcode has not been implemented! {:#04X}", opcode); 1}
+        let mut instruction = &INSTRUCTIONS[opcode as usize];
+        if let Execute::Prefix = instruction.execute {
+            let opcode = self.next_byte();
+            if self.debug {
+                self.print_current_status(opcode, true);
+            }
+            instruction = &CB_INSTRUCTIONS[opcode as usize];
+        }
+
+        let cycles = match instruction.execute {
+            Execute::Always(execute) => {execute(self); instruction.cycles},
+            Execute::Branch(execute) => if execute(self) {instruction.taken_cycles} else {instruction.cycles},
+            Execute::Prefix | Execute::Illegal => {println!("Illegal opcode {:#04X}", opcode); instruction.cycles},
         };
+
+        //Internal M-cycles that weren't already run alongside a bus access
+        debug_assert!(self.ticked <= cycles, "{} ran {} M-cycles, reports {}", instruction.mnemonic, self.ticked, cycles);
+        while self.ticked < cycles {
+            self.tick();
+        }
+
+        //EI before this instruction takes effect now, unless it was followed by DI
+        if enable_interrupts && self.ime == Ime::Pending {
+            self.ime = Ime::Enabled;
+        }
         cycles
     }
 
+    //HALT bug, with IME off and an interrupt already pending the cpu doesn't halt
+    //and the byte after HALT is read twice
+    fn halt(&mut self) {
+        if self.ime != Ime::Enabled && self.memory.interrupts.pending() != 0 {
+            self.halt_bug = true;
+        }
+        else {
+            self.halted = true;
+        }
+    }
+
+    //Conditional jumps, calls and returns, the operands are read whether or not they're taken
+    fn jump(&mut self, taken: bool) -> bool {
+        let address = self.next_word();
+        if taken {
+            self.registers.pc = address;
+        }
+        taken
+    }
+
+    fn jump_relative(&mut self, taken: bool) -> bool {
+        let offset = self.next_byte() as i8;
+        if taken {
+            self.registers.pc = self.registers.pc.wrapping_add(offset as u16);
+        }
+        taken
+    }
+
+    //Push address of the next instruction and jump
+    fn call(&mut self, taken: bool) -> bool {
+        let address = self.next_word();
+        if taken {
+            self.push_word(self.registers.pc);
+            self.registers.pc = address;
+        }
+        taken
+    }
+
+    fn ret(&mut self, taken: bool) -> bool {
+        if taken {
+            self.registers.pc = self.pop_word();
+        }
+        taken
+    }
+
+    //Conditional returns check the flags in an internal M-cycle before popping
+    fn ret_if(&mut self, taken: bool) -> bool {
+        self.tick();
+        self.ret(taken)
+    }
+
     fn rrca(&mut self) {
         let new_carry = self.registers.a & 1u8;
         self.registers.a = self.registers.a >> 1;
@@ -929,7 +679,7 @@ impl Cpu {
         else {
             0u8
         };
-        let new_carry = self.registers.a & (1u8 << 7) >> 7;
+        let new_carry = (self.registers.a & (1u8 << 7)) >> 7;
         self.registers.a = self.registers.a << 1;
         self.registers.a |= old_carry;
         self.registers.set_zero(0);
@@ -939,7 +689,7 @@ impl Cpu {
     }
 
     fn rlc(&mut self, mut data: u8) -> u8 {
-        let new_carry = data & (1u8 << 7) >> 7;
+        let new_carry = (data & (1u8 << 7)) >> 7;
         data = data << 1;
         data |= new_carry;
         if data == 0 {
@@ -961,7 +711,7 @@ impl Cpu {
         else {
             0u8
         };
-        let new_carry = data & (1u8 << 7) >> 7;
+        let new_carry = (data & (1u8 << 7)) >> 7;
         data = data << 1;
         data |= old_carry;
         if data == 0 {
@@ -1071,305 +821,6 @@ impl Cpu {
     }
 
 
-    fn cb_decode(&mut self, opcode: u8) -> u8 {
-
-        if self.debug {
-            self.print_current_status(opcode, true);
-        }
-
-        let cycles = match opcode{
-            //SWAP upper and lower nibbles of n
-            0x37 => {self.registers.a = self.swap(self.registers.a); 2},
-            0x30 => {self.registers.b = self.swap(self.registers.b); 2},
-            0x31 => {self.registers.c = self.swap(self.registers.c); 2},
-            0x32 => {self.registers.d = self.swap(self.registers.d); 2},
-            0x33 => {self.registers.e = self.swap(self.registers.e); 2},
-            0x34 => {self.registers.h = self.swap(self.registers.h); 2},
-            0x35 => {self.registers.l = self.swap(self.registers.l); 2},
-            0x36 => {let address = self.registers.hl(); let swapped = self.swap(self.memory.read_byte(address)); self.memory.write_byte(address, swapped); 4},
-            //RLC n - rotate n left. old bit 7 to carry flag
-            0x07 => {self.registers.a = self.rlc(self.registers.a); 2},
-            0x00 => {self.registers.b = self.rlc(self.registers.b); 2},
-            0x01 => {self.registers.c = self.rlc(self.registers.c); 2},
-            0x02 => {self.registers.d = self.rlc(self.registers.d); 2},
-            0x03 => {self.registers.e = self.rlc(self.registers.e); 2},
-            0x04 => {self.registers.h = self.rlc(self.registers.h); 2},
-            0x05 => {self.registers.l = self.rlc(self.registers.l); 2},
-            0x06 => {let rlc = self.rlc(self.memory.read_byte(self.registers.hl())); self.memory.write_byte(self.registers.hl(), rlc); 4},
-            //RL n - rotate n left through carry flag
-            0x17 => {self.registers.a = self.rl(self.registers.a); 2},
-            0x10 => {self.registers.b = self.rl(self.registers.b); 2},
-            0x11 => {self.registers.c = self.rl(self.registers.c); 2},
-            0x12 => {self.registers.d = self.rl(self.registers.d); 2},
-            0x13 => {self.registers.e = self.rl(self.registers.e); 2},
-            0x14 => {self.registers.h = self.rl(self.registers.h); 2},
-            0x15 => {self.registers.l = self.rl(self.registers.l); 2},
-            0x16 => {let rl = self.rl(self.memory.read_byte(self.registers.hl())); self.memory.write_byte(self.registers.hl(), rl); 4},
-            //RRC n - rotate n right, old bit 0 to carry flag
-            0x0F => {self.registers.a = self.rrc(self.registers.a); 2},
-            0x08 => {self.registers.b = self.rrc(self.registers.b); 2},
-            0x09 => {self.registers.c = self.rrc(self.registers.c); 2},
-            0x0A => {self.registers.d = self.rrc(self.registers.d); 2},
-            0x0B => {self.registers.e = self.rrc(self.registers.e); 2},
-            0x0C => {self.registers.h = self.rrc(self.registers.h); 2},
-            0x0D => {self.registers.l = self.rrc(self.registers.l); 2},
-            0x0E => {let rrc = self.rrc(self.memory.read_byte(self.registers.hl())); self.memory.write_byte(self.registers.hl(), rrc); 4},
-            //RR n - rotate n right through carry flag
-            0x1F => {self.registers.a = self.rr(self.registers.a); 2},
-            0x18 => {self.registers.b = self.rr(self.registers.b); 2},
-            0x19 => {self.registers.c = self.rr(self.registers.c); 2},
-            0x1A => {self.registers.d = self.rr(self.registers.d); 2},
-            0x1B => {self.registers.e = self.rr(self.registers.e); 2},
-            0x1C => {self.registers.h = self.rr(self.registers.h); 2},
-            0x1D => {self.registers.l = self.rr(self.registers.l); 2},
-            0x1E => {let rr = self.rr(self.memory.read_byte(self.registers.hl())); self.memory.write_byte(self.registers.hl(), rr); 4},
-            //SLA n - shift n left into carry flag, LSB=0
-            0x27 => {self.registers.a = self.sla(self.registers.a); 2},
-            0x20 => {self.registers.b = self.sla(self.registers.b); 2},
-            0x21 => {self.registers.c = self.sla(self.registers.c); 2},
-            0x22 => {self.registers.d = self.sla(self.registers.d); 2},
-            0x23 => {self.registers.e = self.sla(self.registers.e); 2},
-            0x24 => {self.registers.h = self.sla(self.registers.h); 2},
-            0x25 => {self.registers.l = self.sla(self.registers.l); 2},
-            0x26 => {let sla = self.sla(self.memory.read_byte(self.registers.hl())); self.memory.write_byte(self.registers.hl(), sla); 4},
-            //SRA n - shift n right into carry flag. MSB doesn't change
-            0x2F => {self.registers.a = self.sra(self.registers.a); 2},
-            0x28 => {self.registers.b = self.sra(self.registers.b); 2},
-            0x29 => {self.registers.c = self.sra(self.registers.c); 2},
-            0x2A => {self.registers.d = self.sra(self.registers.d); 2},
-            0x2B => {self.registers.e = self.sra(self.registers.e); 2},
-            0x2C => {self.registers.h = self.sra(self.registers.h); 2},
-            0x2D => {self.registers.l = self.sra(self.registers.l); 2},
-            0x2E => {let sra = self.sra(self.memory.read_byte(self.registers.hl())); self.memory.write_byte(self.registers.hl(), sra); 4},
-            //SRA n - shift n right into carry flag. MSB=0
-            0x3F => {self.registers.a = self.srl(self.registers.a); 2},
-            0x38 => {self.registers.b = self.srl(self.registers.b); 2},
-            0x39 => {self.registers.c = self.srl(self.registers.c); 2},
-            0x3A => {self.registers.d = self.srl(self.registers.d); 2},
-            0x3B => {self.registers.e = self.srl(self.registers.e); 2},
-            0x3C => {self.registers.h = self.srl(self.registers.h); 2},
-            0x3D => {self.registers.l = self.srl(self.registers.l); 2},
-            0x3E => {let srl = self.srl(self.memory.read_byte(self.registers.hl())); self.memory.write_byte(self.registers.hl(), srl); 4},
-            //Test bit 0
-            0x40 => {self.check_bit(self.registers.b, 0); 2},
-            0x41 => {self.check_bit(self.registers.c, 0); 2},
-            0x42 => {self.check_bit(self.registers.d, 0); 2},
-            0x43 => {self.check_bit(self.registers.e, 0); 2},
-            0x44 => {self.check_bit(self.registers.h, 0); 2},
-            0x45 => {self.check_bit(self.registers.l, 0); 2},
-            0x46 => {self.check_bit(self.memory.read_byte(self.registers.hl()), 0); 4},
-            0x47 => {self.check_bit(self.registers.a, 0); 2},
-            //Test bit 1
-            0x48 => {self.check_bit(self.registers.b, 1); 2},
-            0x49 => {self.check_bit(self.registers.c, 1); 2},
-            0x4A => {self.check_bit(self.registers.d, 1); 2},
-            0x4B => {self.check_bit(self.registers.e, 1); 2},
-            0x4C => {self.check_bit(self.registers.h, 1); 2},
-            0x4D => {self.check_bit(self.registers.l, 1); 2},
-            0x4E => {self.check_bit(self.memory.read_byte(self.registers.hl()), 1); 4},
-            0x4F => {self.check_bit(self.registers.a, 1); 2},
-            //Test bit 2
-            0x50 => {self.check_bit(self.registers.b, 2); 2},
-            0x51 => {self.check_bit(self.registers.c, 2); 2},
-            0x52 => {self.check_bit(self.registers.d, 2); 2},
-            0x53 => {self.check_bit(self.registers.e, 2); 2},
-            0x54 => {self.check_bit(self.registers.h, 2); 2},
-            0x55 => {self.check_bit(self.registers.l, 2); 2},
-            0x56 => {self.check_bit(self.memory.read_byte(self.registers.hl()), 2); 4},
-            0x57 => {self.check_bit(self.registers.a, 2); 2},            
-            //Test bit 3
-            0x58 => {self.check_bit(self.registers.b, 3); 2},
-            0x59 => {self.check_bit(self.registers.c, 3); 2},
-            0x5A => {self.check_bit(self.registers.d, 3); 2},
-            0x5B => {self.check_bit(self.registers.e, 3); 2},
-            0x5C => {self.check_bit(self.registers.h, 3); 2},
-            0x5D => {self.check_bit(self.registers.l, 3); 2},
-            0x5E => {self.check_bit(self.memory.read_byte(self.registers.hl()), 3); 4},
-            0x5F => {self.check_bit(self.registers.a, 3); 2},
-            //Test bit 4
-            0x60 => {self.check_bit(self.registers.b, 4); 2},
-            0x61 => {self.check_bit(self.registers.c, 4); 2},
-            0x62 => {self.check_bit(self.registers.d, 4); 2},
-            0x63 => {self.check_bit(self.registers.e, 4); 2},
-            0x64 => {self.check_bit(self.registers.h, 4); 2},
-            0x65 => {self.check_bit(self.registers.l, 4); 2},
-            0x66 => {self.check_bit(self.memory.read_byte(self.registers.hl()), 4); 4},
-            0x67 => {self.check_bit(self.registers.a, 4); 2},            
-            //Test bit 5
-            0x68 => {self.check_bit(self.registers.b, 5); 2},
-            0x69 => {self.check_bit(self.registers.c, 5); 2},
-            0x6A => {self.check_bit(self.registers.d, 5); 2},
-            0x6B => {self.check_bit(self.registers.e, 5); 2},
-            0x6C => {self.check_bit(self.registers.h, 5); 2},
-            0x6D => {self.check_bit(self.registers.l, 5); 2},
-            0x6E => {self.check_bit(self.memory.read_byte(self.registers.hl()), 5); 4},
-            0x6F => {self.check_bit(self.registers.a, 5); 2},
-            //Test bit 6
-            0x70 => {self.check_bit(self.registers.b, 6); 2},
-            0x71 => {self.check_bit(self.registers.c, 6); 2},
-            0x72 => {self.check_bit(self.registers.d, 6); 2},
-            0x73 => {self.check_bit(self.registers.e, 6); 2},
-            0x74 => {self.check_bit(self.registers.h, 6); 2},
-            0x75 => {self.check_bit(self.registers.l, 6); 2},
-            0x76 => {self.check_bit(self.memory.read_byte(self.registers.hl()), 6); 4},
-            0x77 => {self.check_bit(self.registers.a, 6); 2},            
-            //Test bit 7
-            0x78 => {self.check_bit(self.registers.b, 7); 2},
-            0x79 => {self.check_bit(self.registers.c, 7); 2},
-            0x7A => {self.check_bit(self.registers.d, 7); 2},
-            0x7B => {self.check_bit(self.registers.e, 7); 2},
-            0x7C => {self.check_bit(self.registers.h, 7); 2},
-            0x7D => {self.check_bit(self.registers.l, 7); 2},
-            0x7E => {self.check_bit(self.memory.read_byte(self.registers.hl()), 7); 4},
-            0x7F => {self.check_bit(self.registers.a, 7); 2},
-            //Reset bit 0
-            0x80 => {self.registers.b &= !(1u8 << 0); 2},
-            0x81 => {self.registers.c &= !(1u8 << 0); 2},
-            0x82 => {self.registers.d &= !(1u8 << 0); 2},
-            0x83 => {self.registers.e &= !(1u8 << 0); 2},
-            0x84 => {self.registers.h &= !(1u8 << 0); 2},
-            0x85 => {self.registers.l &= !(1u8 << 0); 2},
-            0x86 => {self.memory.write_byte(self.registers.hl(), self.memory.read_byte(self.registers.hl())  & !(1u8 << 0)); 4},
-            0x87 => {self.registers.a &= !(1u8 << 0); 2},
-            //Reset bit 1
-            0x88 => {self.registers.b &= !(1u8 << 1); 2},
-            0x89 => {self.registers.c &= !(1u8 << 1); 2},
-            0x8A => {self.registers.d &= !(1u8 << 1); 2},
-            0x8B => {self.registers.e &= !(1u8 << 1); 2},
-            0x8C => {self.registers.h &= !(1u8 << 1); 2},
-            0x8D => {self.registers.l &= !(1u8 << 1); 2},
-            0x8E => {self.memory.write_byte(self.registers.hl(), self.memory.read_byte(self.registers.hl())  & !(1u8 << 1)); 4},
-            0x8F => {self.registers.a &= !(1u8 << 1); 2},
-            //Reset bit 2
-            0x90 => {self.registers.b &= !(1u8 << 2); 2},
-            0x91 => {self.registers.c &= !(1u8 << 2); 2},
-            0x92 => {self.registers.d &= !(1u8 << 2); 2},
-            0x93 => {self.registers.e &= !(1u8 << 2); 2},
-            0x94 => {self.registers.h &= !(1u8 << 2); 2},
-            0x95 => {self.registers.l &= !(1u8 << 2); 2},
-            0x96 => {self.memory.write_byte(self.registers.hl(), self.memory.read_byte(self.registers.hl())  & !(1u8 << 2)); 4},
-            0x97 => {self.registers.a &= !(1u8 << 2); 2},
-            //Reset bit 3
-            0x98 => {self.registers.b &= !(1u8 << 3); 2},
-            0x99 => {self.registers.c &= !(1u8 << 3); 2},
-            0x9A => {self.registers.d &= !(1u8 << 3); 2},
-            0x9B => {self.registers.e &= !(1u8 << 3); 2},
-            0x9C => {self.registers.h &= !(1u8 << 3); 2},
-            0x9D => {self.registers.l &= !(1u8 << 3); 2},
-            0x9E => {self.memory.write_byte(self.registers.hl(), self.memory.read_byte(self.registers.hl())  & !(1u8 << 3)); 4},
-            0x9F => {self.registers.a &= !(1u8 << 3); 2},
-            //Reset bit 4
-            0xA0 => {self.registers.b &= !(1u8 << 4); 2},
-            0xA1 => {self.registers.c &= !(1u8 << 4); 2},
-            0xA2 => {self.registers.d &= !(1u8 << 4); 2},
-            0xA3 => {self.registers.e &= !(1u8 << 4); 2},
-            0xA4 => {self.registers.h &= !(1u8 << 4); 2},
-            0xA5 => {self.registers.l &= !(1u8 << 4); 2},
-            0xA6 => {self.memory.write_byte(self.registers.hl(), self.memory.read_byte(self.registers.hl())  & !(1u8 << 4)); 4},
-            0xA7 => {self.registers.a &= !(1u8 << 4); 2},
-            //Reset bit 5
-            0xA8 => {self.registers.b &= !(1u8 << 5); 2},
-            0xA9 => {self.registers.c &= !(1u8 << 5); 2},
-            0xAA => {self.registers.d &= !(1u8 << 5); 2},
-            0xAB => {self.registers.e &= !(1u8 << 5); 2},
-            0xAC => {self.registers.h &= !(1u8 << 5); 2},
-            0xAD => {self.registers.l &= !(1u8 << 5); 2},
-            0xAE => {self.memory.write_byte(self.registers.hl(), self.memory.read_byte(self.registers.hl())  & !(1u8 << 5)); 4},
-            0xAF => {self.registers.a &= !(1u8 << 5); 2},
-            //Reset bit 6
-            0xB0 => {self.registers.b &= !(1u8 << 6); 2},
-            0xB1 => {self.registers.c &= !(1u8 << 6); 2},
-            0xB2 => {self.registers.d &= !(1u8 << 6); 2},
-            0xB3 => {self.registers.e &= !(1u8 << 6); 2},
-            0xB4 => {self.registers.h &= !(1u8 << 6); 2},
-            0xB5 => {self.registers.l &= !(1u8 << 6); 2},
-            0xB6 => {self.memory.write_byte(self.registers.hl(), self.memory.read_byte(self.registers.hl())  & !(1u8 << 6)); 4},
-            0xB7 => {self.registers.a &= !(1u8 << 6); 2},
-            //Reset bit 7
-            0xB8 => {self.registers.b &= !(1u8 << 7); 2},
-            0xB9 => {self.registers.c &= !(1u8 << 7); 2},
-            0xBA => {self.registers.d &= !(1u8 << 7); 2},
-            0xBB => {self.registers.e &= !(1u8 << 7); 2},
-            0xBC => {self.registers.h &= !(1u8 << 7); 2},
-            0xBD => {self.registers.l &= !(1u8 << 7); 2},
-            0xBE => {self.memory.write_byte(self.registers.hl(), self.memory.read_byte(self.registers.hl()) & !(1u8 << 7)); 4},
-            0xBF => {self.registers.a &= !(1u8 << 7); 2},
-            //Set bit 0
-            0xC0 => {self.registers.b |= 1u8 << 0; 2},
-            0xC1 => {self.registers.c |= 1u8 << 0; 2},
-            0xC2 => {self.registers.d |= 1u8 << 0; 2},
-            0xC3 => {self.registers.e |= 1u8 << 0; 2},
-            0xC4 => {self.registers.h |= 1u8 << 0; 2},
-            0xC5 => {self.registers.l |= 1u8 << 0; 2},
-            0xC6 => {self.memory.write_byte(self.registers.hl(), self.memory.read_byte(self.registers.hl()) | (1u8 << 0)); 4},
-            0xC7 => {self.registers.a |= 1u8 << 0; 2},
-            //Set bit 1
-            0xC8 => {self.registers.b |= 1u8 << 1; 2},
-            0xC9 => {self.registers.c |= 1u8 << 1; 2},
-            0xCA => {self.registers.d |= 1u8 << 1; 2},
-            0xCB => {self.registers.e |= 1u8 << 1; 2},
-            0xCC => {self.registers.h |= 1u8 << 1; 2},
-            0xCD => {self.registers.l |= 1u8 << 1; 2},
-            0xCE => {self.memory.write_byte(self.registers.hl(), self.memory.read_byte(self.registers.hl()) | (1u8 << 1)); 4},
-            0xCF => {self.registers.a |= 1u8 << 1; 2},
-            //Set bit 2
-            0xD0 => {self.registers.b |= 1u8 << 2; 2},
-            0xD1 => {self.registers.c |= 1u8 << 2; 2},
-            0xD2 => {self.registers.d |= 1u8 << 2; 2},
-            0xD3 => {self.registers.e |= 1u8 << 2; 2},
-            0xD4 => {self.registers.h |= 1u8 << 2; 2},
-            0xD5 => {self.registers.l |= 1u8 << 2; 2},
-            0xD6 => {self.memory.write_byte(self.registers.hl(), self.memory.read_byte(self.registers.hl()) | (1u8 << 2)); 4},
-            0xD7 => {self.registers.a |= 1u8 << 2; 2},
-            //Set bit 3
-            0xD8 => {self.registers.b |= 1u8 << 3; 2},
-            0xD9 => {self.registers.c |= 1u8 << 3; 2},
-            0xDA => {self.registers.d |= 1u8 << 3; 2},
-            0xDB => {self.registers.e |= 1u8 << 3; 2},
-            0xDC => {self.registers.h |= 1u8 << 3; 2},
-            0xDD => {self.registers.l |= 1u8 << 3; 2},
-            0xDE => {self.memory.write_byte(self.registers.hl(), self.memory.read_byte(self.registers.hl()) | (1u8 << 3)); 4},
-            0xDF => {self.registers.a |= 1u8 << 3; 2},
-            //Set bit 4
-            0xE0 => {self.registers.b |= 1u8 << 4; 2},
-            0xE1 => {self.registers.c |= 1u8 << 4; 2},
-            0xE2 => {self.registers.d |= 1u8 << 4; 2},
-            0xE3 => {self.registers.e |= 1u8 << 4; 2},
-            0xE4 => {self.registers.h |= 1u8 << 4; 2},
-            0xE5 => {self.registers.l |= 1u8 << 4; 2},
-            0xE6 => {self.memory.write_byte(self.registers.hl(), self.memory.read_byte(self.registers.hl()) | (1u8 << 4)); 4},
-            0xE7 => {self.registers.a |= 1u8 << 4; 2},
-            //Set bit 5
-            0xE8 => {self.registers.b |= 1u8 << 5; 2},
-            0xE9 => {self.registers.c |= 1u8 << 5; 2},
-            0xEA => {self.registers.d |= 1u8 << 5; 2},
-            0xEB => {self.registers.e |= 1u8 << 5; 2},
-            0xEC => {self.registers.h |= 1u8 << 5; 2},
-            0xED => {self.registers.l |= 1u8 << 5; 2},
-            0xEE => {self.memory.write_byte(self.registers.hl(), self.memory.read_byte(self.registers.hl()) | (1u8 << 5)); 4},
-            0xEF => {self.registers.a |= 1u8 << 5; 2},
-            //Set bit 6
-            0xF0 => {self.registers.b |= 1u8 << 6; 2},
-            0xF1 => {self.registers.c |= 1u8 << 6; 2},
-            0xF2 => {self.registers.d |= 1u8 << 6; 2},
-            0xF3 => {self.registers.e |= 1u8 << 6; 2},
-            0xF4 => {self.registers.h |= 1u8 << 6; 2},
-            0xF5 => {self.registers.l |= 1u8 << 6; 2},
-            0xF6 => {self.memory.write_byte(self.registers.hl(), self.memory.read_byte(self.registers.hl()) | (1u8 << 6)); 4},
-            0xF7 => {self.registers.a |= 1u8 << 6; 2},
-            //Set bit 7
-            0xF8 => {self.registers.b |= 1u8 << 7; 2},
-            0xF9 => {self.registers.c |= 1u8 << 7; 2},
-            0xFA => {self.registers.d |= 1u8 << 7; 2},
-            0xFB => {self.registers.e |= 1u8 << 7; 2},
-            0xFC => {self.registers.h |= 1u8 << 7; 2},
-            0xFD => {self.registers.l |= 1u8 << 7; 2},
-            0xFE => {self.memory.write_byte(self.registers.hl(), self.memory.read_byte(self.registers.hl()) | (1u8 << 7)); 4},
-            0xFF => {self.registers.a |= 1u8 << 7; 2},
-        };
-        cycles
-    }
-
     //check if bit is zero
     fn check_bit(&mut self, data: u8, bit: u8) {
         let result = data & (1u8 << bit);
@@ -1388,18 +839,971 @@ impl Cpu {
 
 }
 
+//Opcode, mnemonic, length in bytes, M-cycles (not taken and taken for conditional instructions), handler
+pub static INSTRUCTIONS: [Instruction; 256] = [
+    op!(0x00, "NOP", 1, 1, |_| {}),
+    op!(0x01, "LD BC,d16", 3, 3, |cpu| {let word = cpu.next_word(); cpu.registers.set_bc(word)}),
+    op!(0x02, "LD (BC),A", 1, 2, |cpu| cpu.write_byte(cpu.registers.bc(), cpu.registers.a)),
+    op!(0x03, "INC BC", 1, 2, |cpu| cpu.registers.set_bc(cpu.registers.bc().wrapping_add(1))),
+    op!(0x04, "INC B", 1, 1, |cpu| cpu.registers.b = cpu.inc(cpu.registers.b)),
+    op!(0x05, "DEC B", 1, 1, |cpu| cpu.registers.b = cpu.dec(cpu.registers.b)),
+    op!(0x06, "LD B,d8", 2, 2, |cpu| cpu.registers.b = cpu.next_byte()),
+    op!(0x07, "RLCA", 1, 1, |cpu| cpu.rlca()),
+    op!(0x08, "LD (a16),SP", 3, 5, |cpu| {let word = cpu.next_word(); cpu.write_word(word, cpu.registers.sp)}),
+    op!(0x09, "ADD HL,BC", 1, 2, |cpu| cpu.add_hl(cpu.registers.bc())),
+    op!(0x0A, "LD A,(BC)", 1, 2, |cpu| cpu.registers.a = cpu.read_byte(cpu.registers.bc())),
+    op!(0x0B, "DEC BC", 1, 2, |cpu| cpu.registers.set_bc(cpu.registers.bc().wrapping_sub(1))),
+    op!(0x0C, "INC C", 1, 1, |cpu| cpu.registers.c = cpu.inc(cpu.registers.c)),
+    op!(0x0D, "DEC C", 1, 1, |cpu| cpu.registers.c = cpu.dec(cpu.registers.c)),
+    op!(0x0E, "LD C,d8", 2, 2, |cpu| cpu.registers.c = cpu.next_byte()),
+    op!(0x0F, "RRCA", 1, 1, |cpu| cpu.rrca()),
+    op!(0x10, "STOP 0", 2, 2, |cpu| {cpu.next_byte(); cpu.stopped = cpu.memory.stop()}),
+    op!(0x11, "LD DE,d16", 3, 3, |cpu| {let word = cpu.next_word(); cpu.registers.set_de(word)}),
+    op!(0x12, "LD (DE),A", 1, 2, |cpu| cpu.write_byte(cpu.registers.de(), cpu.registers.a)),
+    op!(0x13, "INC DE", 1, 2, |cpu| cpu.registers.set_de(cpu.registers.de().wrapping_add(1))),
+    op!(0x14, "INC D", 1, 1, |cpu| cpu.registers.d = cpu.inc(cpu.registers.d)),
+    op!(0x15, "DEC D", 1, 1, |cpu| cpu.registers.d = cpu.dec(cpu.registers.d)),
+    op!(0x16, "LD D,d8", 2, 2, |cpu| cpu.registers.d = cpu.next_byte()),
+    op!(0x17, "RLA", 1, 1, |cpu| cpu.rla()),
+    op!(0x18, "JR r8", 2, 3, |cpu| {cpu.jump_relative(true);}),
+    op!(0x19, "ADD HL,DE", 1, 2, |cpu| cpu.add_hl(cpu.registers.de())),
+    op!(0x1A, "LD A,(DE)", 1, 2, |cpu| cpu.registers.a = cpu.read_byte(cpu.registers.de())),
+    op!(0x1B, "DEC DE", 1, 2, |cpu| cpu.registers.set_de(cpu.registers.de().wrapping_sub(1))),
+    op!(0x1C, "INC E", 1, 1, |cpu| cpu.registers.e = cpu.inc(cpu.registers.e)),
+    op!(0x1D, "DEC E", 1, 1, |cpu| cpu.registers.e = cpu.dec(cpu.registers.e)),
+    op!(0x1E, "LD E,d8", 2, 2, |cpu| cpu.registers.e = cpu.next_byte()),
+    op!(0x1F, "RRA", 1, 1, |cpu| cpu.rra()),
+    branch!(0x20, "JR NZ,r8", 2, 2, 3, |cpu| cpu.jump_relative(!cpu.registers.check_zero())),
+    op!(0x21, "LD HL,d16", 3, 3, |cpu| {let word = cpu.next_word(); cpu.registers.set_hl(word)}),
+    op!(0x22, "LD (HL+),A", 1, 2, |cpu| {cpu.write_byte(cpu.registers.hl(), cpu.registers.a); cpu.registers.set_hl(cpu.registers.hl().wrapping_add(1))}),
+    op!(0x23, "INC HL", 1, 2, |cpu| cpu.registers.set_hl(cpu.registers.hl().wrapping_add(1))),
+    op!(0x24, "INC H", 1, 1, |cpu| cpu.registers.h = cpu.inc(cpu.registers.h)),
+    op!(0x25, "DEC H", 1, 1, |cpu| cpu.registers.h = cpu.dec(cpu.registers.h)),
+    op!(0x26, "LD H,d8", 2, 2, |cpu| cpu.registers.h = cpu.next_byte()),
+    op!(0x27, "DAA", 1, 1, |cpu| cpu.daa()),
+    branch!(0x28, "JR Z,r8", 2, 2, 3, |cpu| cpu.jump_relative(cpu.registers.check_zero())),
+    op!(0x29, "ADD HL,HL", 1, 2, |cpu| cpu.add_hl(cpu.registers.hl())),
+    op!(0x2A, "LD A,(HL+)", 1, 2, |cpu| {cpu.registers.a = cpu.read_byte(cpu.registers.hl()); cpu.registers.set_hl(cpu.registers.hl().wrapping_add(1))}),
+    op!(0x2B, "DEC HL", 1, 2, |cpu| cpu.registers.set_hl(cpu.registers.hl().wrapping_sub(1))),
+    op!(0x2C, "INC L", 1, 1, |cpu| cpu.registers.l = cpu.inc(cpu.registers.l)),
+    op!(0x2D, "DEC L", 1, 1, |cpu| cpu.registers.l = cpu.dec(cpu.registers.l)),
+    op!(0x2E, "LD L,d8", 2, 2, |cpu| cpu.registers.l = cpu.next_byte()),
+    op!(0x2F, "CPL", 1, 1, |cpu| cpu.cpl()),
+    branch!(0x30, "JR NC,r8", 2, 2, 3, |cpu| cpu.jump_relative(!cpu.registers.check_carry())),
+    op!(0x31, "LD SP,d16", 3, 3, |cpu| cpu.registers.sp = cpu.next_word()),
+    op!(0x32, "LD (HL-),A", 1, 2, |cpu| {cpu.write_byte(cpu.registers.hl(), cpu.registers.a); cpu.registers.set_hl(cpu.registers.hl().wrapping_sub(1))}),
+    op!(0x33, "INC SP", 1, 2, |cpu| cpu.registers.sp = cpu.registers.sp.wrapping_add(1)),
+    op!(0x34, "INC (HL)", 1, 3, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); let inc = cpu.inc(data); cpu.write_byte(cpu.registers.hl(), inc)}),
+    op!(0x35, "DEC (HL)", 1, 3, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); let dec = cpu.dec(data); cpu.write_byte(cpu.registers.hl(), dec)}),
+    op!(0x36, "LD (HL),d8", 2, 3, |cpu| {let byte = cpu.next_byte(); cpu.write_byte(cpu.registers.hl(), byte)}),
+    op!(0x37, "SCF", 1, 1, |cpu| cpu.scf()),
+    branch!(0x38, "JR C,r8", 2, 2, 3, |cpu| cpu.jump_relative(cpu.registers.check_carry())),
+    op!(0x39, "ADD HL,SP", 1, 2, |cpu| cpu.add_hl(cpu.registers.sp)),
+    op!(0x3A, "LD A,(HL-)", 1, 2, |cpu| {cpu.registers.a = cpu.read_byte(cpu.registers.hl()); cpu.registers.set_hl(cpu.registers.hl().wrapping_sub(1))}),
+    op!(0x3B, "DEC SP", 1, 2, |cpu| cpu.registers.sp = cpu.registers.sp.wrapping_sub(1)),
+    op!(0x3C, "INC A", 1, 1, |cpu| cpu.registers.a = cpu.inc(cpu.registers.a)),
+    op!(0x3D, "DEC A", 1, 1, |cpu| cpu.registers.a = cpu.dec(cpu.registers.a)),
+    op!(0x3E, "LD A,d8", 2, 2, |cpu| cpu.registers.a = cpu.next_byte()),
+    op!(0x3F, "CCF", 1, 1, |cpu| cpu.ccf()),
+    op!(0x40, "LD B,B", 1, 1, |_| {}),
+    op!(0x41, "LD B,C", 1, 1, |cpu| cpu.registers.b = cpu.registers.c),
+    op!(0x42, "LD B,D", 1, 1, |cpu| cpu.registers.b = cpu.registers.d),
+    op!(0x43, "LD B,E", 1, 1, |cpu| cpu.registers.b = cpu.registers.e),
+    op!(0x44, "LD B,H", 1, 1, |cpu| cpu.registers.b = cpu.registers.h),
+    op!(0x45, "LD B,L", 1, 1, |cpu| cpu.registers.b = cpu.registers.l),
+    op!(0x46, "LD B,(HL)", 1, 2, |cpu| cpu.registers.b = cpu.read_byte(cpu.registers.hl())),
+    op!(0x47, "LD B,A", 1, 1, |cpu| cpu.registers.b = cpu.registers.a),
+    op!(0x48, "LD C,B", 1, 1, |cpu| cpu.registers.c = cpu.registers.b),
+    op!(0x49, "LD C,C", 1, 1, |_| {}),
+    op!(0x4A, "LD C,D", 1, 1, |cpu| cpu.registers.c = cpu.registers.d),
+    op!(0x4B, "LD C,E", 1, 1, |cpu| cpu.registers.c = cpu.registers.e),
+    op!(0x4C, "LD C,H", 1, 1, |cpu| cpu.registers.c = cpu.registers.h),
+    op!(0x4D, "LD C,L", 1, 1, |cpu| cpu.registers.c = cpu.registers.l),
+    op!(0x4E, "LD C,(HL)", 1, 2, |cpu| cpu.registers.c = cpu.read_byte(cpu.registers.hl())),
+    op!(0x4F, "LD C,A", 1, 1, |cpu| cpu.registers.c = cpu.registers.a),
+    op!(0x50, "LD D,B", 1, 1, |cpu| cpu.registers.d = cpu.registers.b),
+    op!(0x51, "LD D,C", 1, 1, |cpu| cpu.registers.d = cpu.registers.c),
+    op!(0x52, "LD D,D", 1, 1, |_| {}),
+    op!(0x53, "LD D,E", 1, 1, |cpu| cpu.registers.d = cpu.registers.e),
+    op!(0x54, "LD D,H", 1, 1, |cpu| cpu.registers.d = cpu.registers.h),
+    op!(0x55, "LD D,L", 1, 1, |cpu| cpu.registers.d = cpu.registers.l),
+    op!(0x56, "LD D,(HL)", 1, 2, |cpu| cpu.registers.d = cpu.read_byte(cpu.registers.hl())),
+    op!(0x57, "LD D,A", 1, 1, |cpu| cpu.registers.d = cpu.registers.a),
+    op!(0x58, "LD E,B", 1, 1, |cpu| cpu.registers.e = cpu.registers.b),
+    op!(0x59, "LD E,C", 1, 1, |cpu| cpu.registers.e = cpu.registers.c),
+    op!(0x5A, "LD E,D", 1, 1, |cpu| cpu.registers.e = cpu.registers.d),
+    op!(0x5B, "LD E,E", 1, 1, |_| {}),
+    op!(0x5C, "LD E,H", 1, 1, |cpu| cpu.registers.e = cpu.registers.h),
+    op!(0x5D, "LD E,L", 1, 1, |cpu| cpu.registers.e = cpu.registers.l),
+    op!(0x5E, "LD E,(HL)", 1, 2, |cpu| cpu.registers.e = cpu.read_byte(cpu.registers.hl())),
+    op!(0x5F, "LD E,A", 1, 1, |cpu| cpu.registers.e = cpu.registers.a),
+    op!(0x60, "LD H,B", 1, 1, |cpu| cpu.registers.h = cpu.registers.b),
+    op!(0x61, "LD H,C", 1, 1, |cpu| cpu.registers.h = cpu.registers.c),
+    op!(0x62, "LD H,D", 1, 1, |cpu| cpu.registers.h = cpu.registers.d),
+    op!(0x63, "LD H,E", 1, 1, |cpu| cpu.registers.h = cpu.registers.e),
+    op!(0x64, "LD H,H", 1, 1, |_| {}),
+    op!(0x65, "LD H,L", 1, 1, |cpu| cpu.registers.h = cpu.registers.l),
+    op!(0x66, "LD H,(HL)", 1, 2, |cpu| cpu.registers.h = cpu.read_byte(cpu.registers.hl())),
+    op!(0x67, "LD H,A", 1, 1, |cpu| cpu.registers.h = cpu.registers.a),
+    op!(0x68, "LD L,B", 1, 1, |cpu| cpu.registers.l = cpu.registers.b),
+    op!(0x69, "LD L,C", 1, 1, |cpu| cpu.registers.l = cpu.registers.c),
+    op!(0x6A, "LD L,D", 1, 1, |cpu| cpu.registers.l = cpu.registers.d),
+    op!(0x6B, "LD L,E", 1, 1, |cpu| cpu.registers.l = cpu.registers.e),
+    op!(0x6C, "LD L,H", 1, 1, |cpu| cpu.registers.l = cpu.registers.h),
+    op!(0x6D, "LD L,L", 1, 1, |_| {}),
+    op!(0x6E, "LD L,(HL)", 1, 2, |cpu| cpu.registers.l = cpu.read_byte(cpu.registers.hl())),
+    op!(0x6F, "LD L,A", 1, 1, |cpu| cpu.registers.l = cpu.registers.a),
+    op!(0x70, "LD (HL),B", 1, 2, |cpu| cpu.write_byte(cpu.registers.hl(), cpu.registers.b)),
+    op!(0x71, "LD (HL),C", 1, 2, |cpu| cpu.write_byte(cpu.registers.hl(), cpu.registers.c)),
+    op!(0x72, "LD (HL),D", 1, 2, |cpu| cpu.write_byte(cpu.registers.hl(), cpu.registers.d)),
+    op!(0x73, "LD (HL),E", 1, 2, |cpu| cpu.write_byte(cpu.registers.hl(), cpu.registers.e)),
+    op!(0x74, "LD (HL),H", 1, 2, |cpu| cpu.write_byte(cpu.registers.hl(), cpu.registers.h)),
+    op!(0x75, "LD (HL),L", 1, 2, |cpu| cpu.write_byte(cpu.registers.hl(), cpu.registers.l)),
+    op!(0x76, "HALT", 1, 1, |cpu| cpu.halt()),
+    op!(0x77, "LD (HL),A", 1, 2, |cpu| cpu.write_byte(cpu.registers.hl(), cpu.registers.a)),
+    op!(0x78, "LD A,B", 1, 1, |cpu| cpu.registers.a = cpu.registers.b),
+    op!(0x79, "LD A,C", 1, 1, |cpu| cpu.registers.a = cpu.registers.c),
+    op!(0x7A, "LD A,D", 1, 1, |cpu| cpu.registers.a = cpu.registers.d),
+    op!(0x7B, "LD A,E", 1, 1, |cpu| cpu.registers.a = cpu.registers.e),
+    op!(0x7C, "LD A,H", 1, 1, |cpu| cpu.registers.a = cpu.registers.h),
+    op!(0x7D, "LD A,L", 1, 1, |cpu| cpu.registers.a = cpu.registers.l),
+    op!(0x7E, "LD A,(HL)", 1, 2, |cpu| cpu.registers.a = cpu.read_byte(cpu.registers.hl())),
+    op!(0x7F, "LD A,A", 1, 1, |_| {}),
+    op!(0x80, "ADD A,B", 1, 1, |cpu| cpu.add8(cpu.registers.b)),
+    op!(0x81, "ADD A,C", 1, 1, |cpu| cpu.add8(cpu.registers.c)),
+    op!(0x82, "ADD A,D", 1, 1, |cpu| cpu.add8(cpu.registers.d)),
+    op!(0x83, "ADD A,E", 1, 1, |cpu| cpu.add8(cpu.registers.e)),
+    op!(0x84, "ADD A,H", 1, 1, |cpu| cpu.add8(cpu.registers.h)),
+    op!(0x85, "ADD A,L", 1, 1, |cpu| cpu.add8(cpu.registers.l)),
+    op!(0x86, "ADD A,(HL)", 1, 2, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.add8(data)}),
+    op!(0x87, "ADD A,A", 1, 1, |cpu| cpu.add8(cpu.registers.a)),
+    op!(0x88, "ADC A,B", 1, 1, |cpu| cpu.add8_carry(cpu.registers.b)),
+    op!(0x89, "ADC A,C", 1, 1, |cpu| cpu.add8_carry(cpu.registers.c)),
+    op!(0x8A, "ADC A,D", 1, 1, |cpu| cpu.add8_carry(cpu.registers.d)),
+    op!(0x8B, "ADC A,E", 1, 1, |cpu| cpu.add8_carry(cpu.registers.e)),
+    op!(0x8C, "ADC A,H", 1, 1, |cpu| cpu.add8_carry(cpu.registers.h)),
+    op!(0x8D, "ADC A,L", 1, 1, |cpu| cpu.add8_carry(cpu.registers.l)),
+    op!(0x8E, "ADC A,(HL)", 1, 2, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.add8_carry(data)}),
+    op!(0x8F, "ADC A,A", 1, 1, |cpu| cpu.add8_carry(cpu.registers.a)),
+    op!(0x90, "SUB B", 1, 1, |cpu| cpu.sub8(cpu.registers.b)),
+    op!(0x91, "SUB C", 1, 1, |cpu| cpu.sub8(cpu.registers.c)),
+    op!(0x92, "SUB D", 1, 1, |cpu| cpu.sub8(cpu.registers.d)),
+    op!(0x93, "SUB E", 1, 1, |cpu| cpu.sub8(cpu.registers.e)),
+    op!(0x94, "SUB H", 1, 1, |cpu| cpu.sub8(cpu.registers.h)),
+    op!(0x95, "SUB L", 1, 1, |cpu| cpu.sub8(cpu.registers.l)),
+    op!(0x96, "SUB (HL)", 1, 2, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.sub8(data)}),
+    op!(0x97, "SUB A", 1, 1, |cpu| cpu.sub8(cpu.registers.a)),
+    op!(0x98, "SBC A,B", 1, 1, |cpu| cpu.sub8_carry(cpu.registers.b)),
+    op!(0x99, "SBC A,C", 1, 1, |cpu| cpu.sub8_carry(cpu.registers.c)),
+    op!(0x9A, "SBC A,D", 1, 1, |cpu| cpu.sub8_carry(cpu.registers.d)),
+    op!(0x9B, "SBC A,E", 1, 1, |cpu| cpu.sub8_carry(cpu.registers.e)),
+    op!(0x9C, "SBC A,H", 1, 1, |cpu| cpu.sub8_carry(cpu.registers.h)),
+    op!(0x9D, "SBC A,L", 1, 1, |cpu| cpu.sub8_carry(cpu.registers.l)),
+    op!(0x9E, "SBC A,(HL)", 1, 2, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.sub8_carry(data)}),
+    op!(0x9F, "SBC A,A", 1, 1, |cpu| cpu.sub8_carry(cpu.registers.a)),
+    op!(0xA0, "AND B", 1, 1, |cpu| cpu.and(cpu.registers.b)),
+    op!(0xA1, "AND C", 1, 1, |cpu| cpu.and(cpu.registers.c)),
+    op!(0xA2, "AND D", 1, 1, |cpu| cpu.and(cpu.registers.d)),
+    op!(0xA3, "AND E", 1, 1, |cpu| cpu.and(cpu.registers.e)),
+    op!(0xA4, "AND H", 1, 1, |cpu| cpu.and(cpu.registers.h)),
+    op!(0xA5, "AND L", 1, 1, |cpu| cpu.and(cpu.registers.l)),
+    op!(0xA6, "AND (HL)", 1, 2, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.and(data)}),
+    op!(0xA7, "AND A", 1, 1, |cpu| cpu.and(cpu.registers.a)),
+    op!(0xA8, "XOR B", 1, 1, |cpu| cpu.xor(cpu.registers.b)),
+    op!(0xA9, "XOR C", 1, 1, |cpu| cpu.xor(cpu.registers.c)),
+    op!(0xAA, "XOR D", 1, 1, |cpu| cpu.xor(cpu.registers.d)),
+    op!(0xAB, "XOR E", 1, 1, |cpu| cpu.xor(cpu.registers.e)),
+    op!(0xAC, "XOR H", 1, 1, |cpu| cpu.xor(cpu.registers.h)),
+    op!(0xAD, "XOR L", 1, 1, |cpu| cpu.xor(cpu.registers.l)),
+    op!(0xAE, "XOR (H,L)", 1, 2, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.xor(data)}),
+    op!(0xAF, "XOR A", 1, 1, |cpu| cpu.xor(cpu.registers.a)),
+    op!(0xB0, "OR B", 1, 1, |cpu| cpu.or(cpu.registers.b)),
+    op!(0xB1, "OR C", 1, 1, |cpu| cpu.or(cpu.registers.c)),
+    op!(0xB2, "OR D", 1, 1, |cpu| cpu.or(cpu.registers.d)),
+    op!(0xB3, "OR E", 1, 1, |cpu| cpu.or(cpu.registers.e)),
+    op!(0xB4, "OR H", 1, 1, |cpu| cpu.or(cpu.registers.h)),
+    op!(0xB5, "OR L", 1, 1, |cpu| cpu.or(cpu.registers.l)),
+    op!(0xB6, "OR (HL)", 1, 2, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.or(data)}),
+    op!(0xB7, "OR A", 1, 1, |cpu| cpu.or(cpu.registers.a)),
+    op!(0xB8, "CP B", 1, 1, |cpu| cpu.cmp(cpu.registers.b)),
+    op!(0xB9, "CP C", 1, 1, |cpu| cpu.cmp(cpu.registers.c)),
+    op!(0xBA, "CP D", 1, 1, |cpu| cpu.cmp(cpu.registers.d)),
+    op!(0xBB, "CP E", 1, 1, |cpu| cpu.cmp(cpu.registers.e)),
+    op!(0xBC, "CP H", 1, 1, |cpu| cpu.cmp(cpu.registers.h)),
+    op!(0xBD, "CP L", 1, 1, |cpu| cpu.cmp(cpu.registers.l)),
+    op!(0xBE, "CP (H,L)", 1, 2, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.cmp(data)}),
+    op!(0xBF, "CP A", 1, 1, |cpu| cpu.cmp(cpu.registers.a)),
+    branch!(0xC0, "RET NZ", 1, 2, 5, |cpu| cpu.ret_if(!cpu.registers.check_zero())),
+    op!(0xC1, "POP BC", 1, 3, |cpu| {let word = cpu.pop_word(); cpu.registers.set_bc(word)}),
+    branch!(0xC2, "JP NZ,a16", 3, 3, 4, |cpu| cpu.jump(!cpu.registers.check_zero())),
+    op!(0xC3, "JP a16", 3, 4, |cpu| {cpu.jump(true);}),
+    branch!(0xC4, "CALL NZ,a16", 3, 3, 6, |cpu| cpu.call(!cpu.registers.check_zero())),
+    op!(0xC5, "PUSH BC", 1, 4, |cpu| cpu.push_word(cpu.registers.bc())),
+    op!(0xC6, "ADD A,d8", 2, 2, |cpu| {let byte = cpu.next_byte(); cpu.add8(byte)}),
+    op!(0xC7, "RST 00H", 1, 4, |cpu| {cpu.push_word(cpu.registers.pc); cpu.registers.pc = 0x00}),
+    branch!(0xC8, "RET Z", 1, 2, 5, |cpu| cpu.ret_if(cpu.registers.check_zero())),
+    op!(0xC9, "RET", 1, 4, |cpu| {cpu.ret(true);}),
+    branch!(0xCA, "JP Z,a16", 3, 3, 4, |cpu| cpu.jump(cpu.registers.check_zero())),
+    prefix!(0xCB),
+    branch!(0xCC, "CALL Z,a16", 3, 3, 6, |cpu| cpu.call(cpu.registers.check_zero())),
+    op!(0xCD, "CALL a16", 3, 6, |cpu| {cpu.call(true);}),
+    op!(0xCE, "ADC A,d8", 2, 2, |cpu| {let byte = cpu.next_byte(); cpu.add8_carry(byte)}),
+    op!(0xCF, "RST 08H", 1, 4, |cpu| {cpu.push_word(cpu.registers.pc); cpu.registers.pc = 0x08}),
+    branch!(0xD0, "RET NC", 1, 2, 5, |cpu| cpu.ret_if(!cpu.registers.check_carry())),
+    op!(0xD1, "POP DE", 1, 3, |cpu| {let word = cpu.pop_word(); cpu.registers.set_de(word)}),
+    branch!(0xD2, "JP NC,a16", 3, 3, 4, |cpu| cpu.jump(!cpu.registers.check_carry())),
+    illegal!(0xD3),
+    branch!(0xD4, "CALL NC,a16", 3, 3, 6, |cpu| cpu.call(!cpu.registers.check_carry())),
+    op!(0xD5, "PUSH DE", 1, 4, |cpu| cpu.push_word(cpu.registers.de())),
+    op!(0xD6, "SUB d8", 2, 2, |cpu| {let byte = cpu.next_byte(); cpu.sub8(byte)}),
+    op!(0xD7, "RST 10H", 1, 4, |cpu| {cpu.push_word(cpu.registers.pc); cpu.registers.pc = 0x10}),
+    branch!(0xD8, "RET C", 1, 2, 5, |cpu| cpu.ret_if(cpu.registers.check_carry())),
+    op!(0xD9, "RETI", 1, 4, |cpu| {cpu.registers.pc = cpu.pop_word(); cpu.ime = Ime::Enabled}),
+    branch!(0xDA, "JP C,a16", 3, 3, 4, |cpu| cpu.jump(cpu.registers.check_carry())),
+    illegal!(0xDB),
+    branch!(0xDC, "CALL C,a16", 3, 3, 6, |cpu| cpu.call(cpu.registers.check_carry())),
+    illegal!(0xDD),
+    op!(0xDE, "SBC A,d8", 2, 2, |cpu| {let byte = cpu.next_byte(); cpu.sub8_carry(byte)}),
+    op!(0xDF, "RST 18H", 1, 4, |cpu| {cpu.push_word(cpu.registers.pc); cpu.registers.pc = 0x18}),
+    op!(0xE0, "LDH (a8),A", 2, 3, |cpu| {let byte = cpu.next_byte(); cpu.write_byte(0xFF00 + byte as u16, cpu.registers.a)}),
+    op!(0xE1, "POP HL", 1, 3, |cpu| {let word = cpu.pop_word(); cpu.registers.set_hl(word)}),
+    op!(0xE2, "LD (C),A", 1, 2, |cpu| cpu.write_byte(0xFF00 + cpu.registers.c as u16, cpu.registers.a)),
+    illegal!(0xE3),
+    illegal!(0xE4),
+    op!(0xE5, "PUSH HL", 1, 4, |cpu| cpu.push_word(cpu.registers.hl())),
+    op!(0xE6, "AND d8", 2, 2, |cpu| {let byte = cpu.next_byte(); cpu.and(byte)}),
+    op!(0xE7, "RST 20H", 1, 4, |cpu| {cpu.push_word(cpu.registers.pc); cpu.registers.pc = 0x20}),
+    op!(0xE8, "ADD SP,r8", 2, 4, |cpu| {let byte = cpu.next_byte(); cpu.registers.sp = cpu.add_sp(byte)}),
+    op!(0xE9, "JP (HL)", 1, 1, |cpu| cpu.registers.pc = cpu.registers.hl()),
+    op!(0xEA, "LD (a16),A", 3, 4, |cpu| {let word = cpu.next_word(); cpu.write_byte(word, cpu.registers.a)}),
+    illegal!(0xEB),
+    illegal!(0xEC),
+    illegal!(0xED),
+    op!(0xEE, "XOR d8", 2, 2, |cpu| {let byte = cpu.next_byte(); cpu.xor(byte)}),
+    op!(0xEF, "RST 28H", 1, 4, |cpu| {cpu.push_word(cpu.registers.pc); cpu.registers.pc = 0x28}),
+    op!(0xF0, "LDH A,(a8)", 2, 3, |cpu| {let byte = cpu.next_byte(); cpu.registers.a = cpu.read_byte(0xFF00 + byte as u16)}),
+    op!(0xF1, "POP AF", 1, 3, |cpu| {let word = cpu.pop_word(); cpu.registers.set_af(word)}),
+    op!(0xF2, "LD A,(C)", 1, 2, |cpu| cpu.registers.a = cpu.read_byte(0xFF00 + cpu.registers.c as u16)),
+    op!(0xF3, "DI", 1, 1, |cpu| cpu.ime = Ime::Disabled),
+    illegal!(0xF4),
+    op!(0xF5, "PUSH AF", 1, 4, |cpu| cpu.push_word(cpu.registers.af())),
+    op!(0xF6, "OR d8", 2, 2, |cpu| {let byte = cpu.next_byte(); cpu.or(byte)}),
+    op!(0xF7, "RST 30H", 1, 4, |cpu| {cpu.push_word(cpu.registers.pc); cpu.registers.pc = 0x30}),
+    op!(0xF8, "LD HL,SP+r8", 2, 3, |cpu| {let byte = cpu.next_byte(); let hl = cpu.add_sp(byte); cpu.registers.set_hl(hl)}),
+    op!(0xF9, "LD SP,HL", 1, 2, |cpu| cpu.registers.sp = cpu.registers.hl()),
+    op!(0xFA, "LD A,(a16)", 3, 4, |cpu| {let word = cpu.next_word(); cpu.registers.a = cpu.read_byte(word)}),
+    op!(0xFB, "EI", 1, 1, |cpu| if cpu.ime == Ime::Disabled {cpu.ime = Ime::Pending}),
+    illegal!(0xFC),
+    illegal!(0xFD),
+    op!(0xFE, "CP d8", 2, 2, |cpu| {let byte = cpu.next_byte(); cpu.cmp(byte)}),
+    op!(0xFF, "RST 38H", 1, 4, |cpu| {cpu.push_word(cpu.registers.pc); cpu.registers.pc = 0x38}),
+];
+
+//CB prefixed instructions, lengths and cycles include the prefix
+pub static CB_INSTRUCTIONS: [Instruction; 256] = [
+    op!(0x00, "RLC B", 2, 2, |cpu| cpu.registers.b = cpu.rlc(cpu.registers.b)),
+    op!(0x01, "RLC C", 2, 2, |cpu| cpu.registers.c = cpu.rlc(cpu.registers.c)),
+    op!(0x02, "RLC D", 2, 2, |cpu| cpu.registers.d = cpu.rlc(cpu.registers.d)),
+    op!(0x03, "RLC E", 2, 2, |cpu| cpu.registers.e = cpu.rlc(cpu.registers.e)),
+    op!(0x04, "RLC H", 2, 2, |cpu| cpu.registers.h = cpu.rlc(cpu.registers.h)),
+    op!(0x05, "RLC L", 2, 2, |cpu| cpu.registers.l = cpu.rlc(cpu.registers.l)),
+    op!(0x06, "RLC (HL)", 2, 4, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); let rlc = cpu.rlc(data); cpu.write_byte(cpu.registers.hl(), rlc)}),
+    op!(0x07, "RLC A", 2, 2, |cpu| cpu.registers.a = cpu.rlc(cpu.registers.a)),
+    op!(0x08, "RRC B", 2, 2, |cpu| cpu.registers.b = cpu.rrc(cpu.registers.b)),
+    op!(0x09, "RRC C", 2, 2, |cpu| cpu.registers.c = cpu.rrc(cpu.registers.c)),
+    op!(0x0A, "RRC D", 2, 2, |cpu| cpu.registers.d = cpu.rrc(cpu.registers.d)),
+    op!(0x0B, "RRC E", 2, 2, |cpu| cpu.registers.e = cpu.rrc(cpu.registers.e)),
+    op!(0x0C, "RRC H", 2, 2, |cpu| cpu.registers.h = cpu.rrc(cpu.registers.h)),
+    op!(0x0D, "RRC L", 2, 2, |cpu| cpu.registers.l = cpu.rrc(cpu.registers.l)),
+    op!(0x0E, "RRC (HL)", 2, 4, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); let rrc = cpu.rrc(data); cpu.write_byte(cpu.registers.hl(), rrc)}),
+    op!(0x0F, "RRC A", 2, 2, |cpu| cpu.registers.a = cpu.rrc(cpu.registers.a)),
+    op!(0x10, "RL B", 2, 2, |cpu| cpu.registers.b = cpu.rl(cpu.registers.b)),
+    op!(0x11, "RL C", 2, 2, |cpu| cpu.registers.c = cpu.rl(cpu.registers.c)),
+    op!(0x12, "RL D", 2, 2, |cpu| cpu.registers.d = cpu.rl(cpu.registers.d)),
+    op!(0x13, "RL E", 2, 2, |cpu| cpu.registers.e = cpu.rl(cpu.registers.e)),
+    op!(0x14, "RL H", 2, 2, |cpu| cpu.registers.h = cpu.rl(cpu.registers.h)),
+    op!(0x15, "RL L", 2, 2, |cpu| cpu.registers.l = cpu.rl(cpu.registers.l)),
+    op!(0x16, "RL (HL)", 2, 4, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); let rl = cpu.rl(data); cpu.write_byte(cpu.registers.hl(), rl)}),
+    op!(0x17, "RL A", 2, 2, |cpu| cpu.registers.a = cpu.rl(cpu.registers.a)),
+    op!(0x18, "RR B", 2, 2, |cpu| cpu.registers.b = cpu.rr(cpu.registers.b)),
+    op!(0x19, "RR C", 2, 2, |cpu| cpu.registers.c = cpu.rr(cpu.registers.c)),
+    op!(0x1A, "RR D", 2, 2, |cpu| cpu.registers.d = cpu.rr(cpu.registers.d)),
+    op!(0x1B, "RR E", 2, 2, |cpu| cpu.registers.e = cpu.rr(cpu.registers.e)),
+    op!(0x1C, "RR H", 2, 2, |cpu| cpu.registers.h = cpu.rr(cpu.registers.h)),
+    op!(0x1D, "RR L", 2, 2, |cpu| cpu.registers.l = cpu.rr(cpu.registers.l)),
+    op!(0x1E, "RR (HL)", 2, 4, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); let rr = cpu.rr(data); cpu.write_byte(cpu.registers.hl(), rr)}),
+    op!(0x1F, "RR A", 2, 2, |cpu| cpu.registers.a = cpu.rr(cpu.registers.a)),
+    op!(0x20, "SLA B", 2, 2, |cpu| cpu.registers.b = cpu.sla(cpu.registers.b)),
+    op!(0x21, "SLA C", 2, 2, |cpu| cpu.registers.c = cpu.sla(cpu.registers.c)),
+    op!(0x22, "SLA D", 2, 2, |cpu| cpu.registers.d = cpu.sla(cpu.registers.d)),
+    op!(0x23, "SLA E", 2, 2, |cpu| cpu.registers.e = cpu.sla(cpu.registers.e)),
+    op!(0x24, "SLA H", 2, 2, |cpu| cpu.registers.h = cpu.sla(cpu.registers.h)),
+    op!(0x25, "SLA L", 2, 2, |cpu| cpu.registers.l = cpu.sla(cpu.registers.l)),
+    op!(0x26, "SLA (HL)", 2, 4, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); let sla = cpu.sla(data); cpu.write_byte(cpu.registers.hl(), sla)}),
+    op!(0x27, "SLA A", 2, 2, |cpu| cpu.registers.a = cpu.sla(cpu.registers.a)),
+    op!(0x28, "SRA B", 2, 2, |cpu| cpu.registers.b = cpu.sra(cpu.registers.b)),
+    op!(0x29, "SRA C", 2, 2, |cpu| cpu.registers.c = cpu.sra(cpu.registers.c)),
+    op!(0x2A, "SRA D", 2, 2, |cpu| cpu.registers.d = cpu.sra(cpu.registers.d)),
+    op!(0x2B, "SRA E", 2, 2, |cpu| cpu.registers.e = cpu.sra(cpu.registers.e)),
+    op!(0x2C, "SRA H", 2, 2, |cpu| cpu.registers.h = cpu.sra(cpu.registers.h)),
+    op!(0x2D, "SRA L", 2, 2, |cpu| cpu.registers.l = cpu.sra(cpu.registers.l)),
+    op!(0x2E, "SRA (HL)", 2, 4, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); let sra = cpu.sra(data); cpu.write_byte(cpu.registers.hl(), sra)}),
+    op!(0x2F, "SRA A", 2, 2, |cpu| cpu.registers.a = cpu.sra(cpu.registers.a)),
+    op!(0x30, "SWAP B", 2, 2, |cpu| cpu.registers.b = cpu.swap(cpu.registers.b)),
+    op!(0x31, "SWAP C", 2, 2, |cpu| cpu.registers.c = cpu.swap(cpu.registers.c)),
+    op!(0x32, "SWAP D", 2, 2, |cpu| cpu.registers.d = cpu.swap(cpu.registers.d)),
+    op!(0x33, "SWAP E", 2, 2, |cpu| cpu.registers.e = cpu.swap(cpu.registers.e)),
+    op!(0x34, "SWAP H", 2, 2, |cpu| cpu.registers.h = cpu.swap(cpu.registers.h)),
+    op!(0x35, "SWAP L", 2, 2, |cpu| cpu.registers.l = cpu.swap(cpu.registers.l)),
+    op!(0x36, "SWAP (HL)", 2, 4, |cpu| {let address = cpu.registers.hl(); let data = cpu.read_byte(address); let swapped = cpu.swap(data); cpu.write_byte(address, swapped)}),
+    op!(0x37, "SWAP A", 2, 2, |cpu| cpu.registers.a = cpu.swap(cpu.registers.a)),
+    op!(0x38, "SRL B", 2, 2, |cpu| cpu.registers.b = cpu.srl(cpu.registers.b)),
+    op!(0x39, "SRL C", 2, 2, |cpu| cpu.registers.c = cpu.srl(cpu.registers.c)),
+    op!(0x3A, "SRL D", 2, 2, |cpu| cpu.registers.d = cpu.srl(cpu.registers.d)),
+    op!(0x3B, "SRL E", 2, 2, |cpu| cpu.registers.e = cpu.srl(cpu.registers.e)),
+    op!(0x3C, "SRL H", 2, 2, |cpu| cpu.registers.h = cpu.srl(cpu.registers.h)),
+    op!(0x3D, "SRL L", 2, 2, |cpu| cpu.registers.l = cpu.srl(cpu.registers.l)),
+    op!(0x3E, "SRL (HL)", 2, 4, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); let srl = cpu.srl(data); cpu.write_byte(cpu.registers.hl(), srl)}),
+    op!(0x3F, "SRL A", 2, 2, |cpu| cpu.registers.a = cpu.srl(cpu.registers.a)),
+    op!(0x40, "BIT 0,B", 2, 2, |cpu| cpu.check_bit(cpu.registers.b, 0)),
+    op!(0x41, "BIT 0,C", 2, 2, |cpu| cpu.check_bit(cpu.registers.c, 0)),
+    op!(0x42, "BIT 0,D", 2, 2, |cpu| cpu.check_bit(cpu.registers.d, 0)),
+    op!(0x43, "BIT 0,E", 2, 2, |cpu| cpu.check_bit(cpu.registers.e, 0)),
+    op!(0x44, "BIT 0,H", 2, 2, |cpu| cpu.check_bit(cpu.registers.h, 0)),
+    op!(0x45, "BIT 0,L", 2, 2, |cpu| cpu.check_bit(cpu.registers.l, 0)),
+    op!(0x46, "BIT 0,(HL)", 2, 3, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.check_bit(data, 0)}),
+    op!(0x47, "BIT 0,A", 2, 2, |cpu| cpu.check_bit(cpu.registers.a, 0)),
+    op!(0x48, "BIT 1,B", 2, 2, |cpu| cpu.check_bit(cpu.registers.b, 1)),
+    op!(0x49, "BIT 1,C", 2, 2, |cpu| cpu.check_bit(cpu.registers.c, 1)),
+    op!(0x4A, "BIT 1,D", 2, 2, |cpu| cpu.check_bit(cpu.registers.d, 1)),
+    op!(0x4B, "BIT 1,E", 2, 2, |cpu| cpu.check_bit(cpu.registers.e, 1)),
+    op!(0x4C, "BIT 1,H", 2, 2, |cpu| cpu.check_bit(cpu.registers.h, 1)),
+    op!(0x4D, "BIT 1,L", 2, 2, |cpu| cpu.check_bit(cpu.registers.l, 1)),
+    op!(0x4E, "BIT 1,(HL)", 2, 3, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.check_bit(data, 1)}),
+    op!(0x4F, "BIT 1,A", 2, 2, |cpu| cpu.check_bit(cpu.registers.a, 1)),
+    op!(0x50, "BIT 2,B", 2, 2, |cpu| cpu.check_bit(cpu.registers.b, 2)),
+    op!(0x51, "BIT 2,C", 2, 2, |cpu| cpu.check_bit(cpu.registers.c, 2)),
+    op!(0x52, "BIT 2,D", 2, 2, |cpu| cpu.check_bit(cpu.registers.d, 2)),
+    op!(0x53, "BIT 2,E", 2, 2, |cpu| cpu.check_bit(cpu.registers.e, 2)),
+    op!(0x54, "BIT 2,H", 2, 2, |cpu| cpu.check_bit(cpu.registers.h, 2)),
+    op!(0x55, "BIT 2,L", 2, 2, |cpu| cpu.check_bit(cpu.registers.l, 2)),
+    op!(0x56, "BIT 2,(HL)", 2, 3, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.check_bit(data, 2)}),
+    op!(0x57, "BIT 2,A", 2, 2, |cpu| cpu.check_bit(cpu.registers.a, 2)),
+    op!(0x58, "BIT 3,B", 2, 2, |cpu| cpu.check_bit(cpu.registers.b, 3)),
+    op!(0x59, "BIT 3,C", 2, 2, |cpu| cpu.check_bit(cpu.registers.c, 3)),
+    op!(0x5A, "BIT 3,D", 2, 2, |cpu| cpu.check_bit(cpu.registers.d, 3)),
+    op!(0x5B, "BIT 3,E", 2, 2, |cpu| cpu.check_bit(cpu.registers.e, 3)),
+    op!(0x5C, "BIT 3,H", 2, 2, |cpu| cpu.check_bit(cpu.registers.h, 3)),
+    op!(0x5D, "BIT 3,L", 2, 2, |cpu| cpu.check_bit(cpu.registers.l, 3)),
+    op!(0x5E, "BIT 3,(HL)", 2, 3, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.check_bit(data, 3)}),
+    op!(0x5F, "BIT 3,A", 2, 2, |cpu| cpu.check_bit(cpu.registers.a, 3)),
+    op!(0x60, "BIT 4,B", 2, 2, |cpu| cpu.check_bit(cpu.registers.b, 4)),
+    op!(0x61, "BIT 4,C", 2, 2, |cpu| cpu.check_bit(cpu.registers.c, 4)),
+    op!(0x62, "BIT 4,D", 2, 2, |cpu| cpu.check_bit(cpu.registers.d, 4)),
+    op!(0x63, "BIT 4,E", 2, 2, |cpu| cpu.check_bit(cpu.registers.e, 4)),
+    op!(0x64, "BIT 4,H", 2, 2, |cpu| cpu.check_bit(cpu.registers.h, 4)),
+    op!(0x65, "BIT 4,L", 2, 2, |cpu| cpu.check_bit(cpu.registers.l, 4)),
+    op!(0x66, "BIT 4,(HL)", 2, 3, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.check_bit(data, 4)}),
+    op!(0x67, "BIT 4,A", 2, 2, |cpu| cpu.check_bit(cpu.registers.a, 4)),
+    op!(0x68, "BIT 5,B", 2, 2, |cpu| cpu.check_bit(cpu.registers.b, 5)),
+    op!(0x69, "BIT 5,C", 2, 2, |cpu| cpu.check_bit(cpu.registers.c, 5)),
+    op!(0x6A, "BIT 5,D", 2, 2, |cpu| cpu.check_bit(cpu.registers.d, 5)),
+    op!(0x6B, "BIT 5,E", 2, 2, |cpu| cpu.check_bit(cpu.registers.e, 5)),
+    op!(0x6C, "BIT 5,H", 2, 2, |cpu| cpu.check_bit(cpu.registers.h, 5)),
+    op!(0x6D, "BIT 5,L", 2, 2, |cpu| cpu.check_bit(cpu.registers.l, 5)),
+    op!(0x6E, "BIT 5,(HL)", 2, 3, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.check_bit(data, 5)}),
+    op!(0x6F, "BIT 5,A", 2, 2, |cpu| cpu.check_bit(cpu.registers.a, 5)),
+    op!(0x70, "BIT 6,B", 2, 2, |cpu| cpu.check_bit(cpu.registers.b, 6)),
+    op!(0x71, "BIT 6,C", 2, 2, |cpu| cpu.check_bit(cpu.registers.c, 6)),
+    op!(0x72, "BIT 6,D", 2, 2, |cpu| cpu.check_bit(cpu.registers.d, 6)),
+    op!(0x73, "BIT 6,E", 2, 2, |cpu| cpu.check_bit(cpu.registers.e, 6)),
+    op!(0x74, "BIT 6,H", 2, 2, |cpu| cpu.check_bit(cpu.registers.h, 6)),
+    op!(0x75, "BIT 6,L", 2, 2, |cpu| cpu.check_bit(cpu.registers.l, 6)),
+    op!(0x76, "BIT 6,(HL)", 2, 3, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.check_bit(data, 6)}),
+    op!(0x77, "BIT 6,A", 2, 2, |cpu| cpu.check_bit(cpu.registers.a, 6)),
+    op!(0x78, "BIT 7,B", 2, 2, |cpu| cpu.check_bit(cpu.registers.b, 7)),
+    op!(0x79, "BIT 7,C", 2, 2, |cpu| cpu.check_bit(cpu.registers.c, 7)),
+    op!(0x7A, "BIT 7,D", 2, 2, |cpu| cpu.check_bit(cpu.registers.d, 7)),
+    op!(0x7B, "BIT 7,E", 2, 2, |cpu| cpu.check_bit(cpu.registers.e, 7)),
+    op!(0x7C, "BIT 7,H", 2, 2, |cpu| cpu.check_bit(cpu.registers.h, 7)),
+    op!(0x7D, "BIT 7,L", 2, 2, |cpu| cpu.check_bit(cpu.registers.l, 7)),
+    op!(0x7E, "BIT 7,(HL)", 2, 3, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.check_bit(data, 7)}),
+    op!(0x7F, "BIT 7,A", 2, 2, |cpu| cpu.check_bit(cpu.registers.a, 7)),
+    op!(0x80, "RES 0,B", 2, 2, |cpu| cpu.registers.b &= !(1u8 << 0)),
+    op!(0x81, "RES 0,C", 2, 2, |cpu| cpu.registers.c &= !(1u8 << 0)),
+    op!(0x82, "RES 0,D", 2, 2, |cpu| cpu.registers.d &= !(1u8 << 0)),
+    op!(0x83, "RES 0,E", 2, 2, |cpu| cpu.registers.e &= !(1u8 << 0)),
+    op!(0x84, "RES 0,H", 2, 2, |cpu| cpu.registers.h &= !(1u8 << 0)),
+    op!(0x85, "RES 0,L", 2, 2, |cpu| cpu.registers.l &= !(1u8 << 0)),
+    op!(0x86, "RES 0,(HL)", 2, 4, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.write_byte(cpu.registers.hl(), data & !(1u8 << 0))}),
+    op!(0x87, "RES 0,A", 2, 2, |cpu| cpu.registers.a &= !(1u8 << 0)),
+    op!(0x88, "RES 1,B", 2, 2, |cpu| cpu.registers.b &= !(1u8 << 1)),
+    op!(0x89, "RES 1,C", 2, 2, |cpu| cpu.registers.c &= !(1u8 << 1)),
+    op!(0x8A, "RES 1,D", 2, 2, |cpu| cpu.registers.d &= !(1u8 << 1)),
+    op!(0x8B, "RES 1,E", 2, 2, |cpu| cpu.registers.e &= !(1u8 << 1)),
+    op!(0x8C, "RES 1,H", 2, 2, |cpu| cpu.registers.h &= !(1u8 << 1)),
+    op!(0x8D, "RES 1,L", 2, 2, |cpu| cpu.registers.l &= !(1u8 << 1)),
+    op!(0x8E, "RES 1,(HL)", 2, 4, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.write_byte(cpu.registers.hl(), data & !(1u8 << 1))}),
+    op!(0x8F, "RES 1,A", 2, 2, |cpu| cpu.registers.a &= !(1u8 << 1)),
+    op!(0x90, "RES 2,B", 2, 2, |cpu| cpu.registers.b &= !(1u8 << 2)),
+    op!(0x91, "RES 2,C", 2, 2, |cpu| cpu.registers.c &= !(1u8 << 2)),
+    op!(0x92, "RES 2,D", 2, 2, |cpu| cpu.registers.d &= !(1u8 << 2)),
+    op!(0x93, "RES 2,E", 2, 2, |cpu| cpu.registers.e &= !(1u8 << 2)),
+    op!(0x94, "RES 2,H", 2, 2, |cpu| cpu.registers.h &= !(1u8 << 2)),
+    op!(0x95, "RES 2,L", 2, 2, |cpu| cpu.registers.l &= !(1u8 << 2)),
+    op!(0x96, "RES 2,(HL)", 2, 4, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.write_byte(cpu.registers.hl(), data & !(1u8 << 2))}),
+    op!(0x97, "RES 2,A", 2, 2, |cpu| cpu.registers.a &= !(1u8 << 2)),
+    op!(0x98, "RES 3,B", 2, 2, |cpu| cpu.registers.b &= !(1u8 << 3)),
+    op!(0x99, "RES 3,C", 2, 2, |cpu| cpu.registers.c &= !(1u8 << 3)),
+    op!(0x9A, "RES 3,D", 2, 2, |cpu| cpu.registers.d &= !(1u8 << 3)),
+    op!(0x9B, "RES 3,E", 2, 2, |cpu| cpu.registers.e &= !(1u8 << 3)),
+    op!(0x9C, "RES 3,H", 2, 2, |cpu| cpu.registers.h &= !(1u8 << 3)),
+    op!(0x9D, "RES 3,L", 2, 2, |cpu| cpu.registers.l &= !(1u8 << 3)),
+    op!(0x9E, "RES 3,(HL)", 2, 4, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.write_byte(cpu.registers.hl(), data & !(1u8 << 3))}),
+    op!(0x9F, "RES 3,A", 2, 2, |cpu| cpu.registers.a &= !(1u8 << 3)),
+    op!(0xA0, "RES 4,B", 2, 2, |cpu| cpu.registers.b &= !(1u8 << 4)),
+    op!(0xA1, "RES 4,C", 2, 2, |cpu| cpu.registers.c &= !(1u8 << 4)),
+    op!(0xA2, "RES 4,D", 2, 2, |cpu| cpu.registers.d &= !(1u8 << 4)),
+    op!(0xA3, "RES 4,E", 2, 2, |cpu| cpu.registers.e &= !(1u8 << 4)),
+    op!(0xA4, "RES 4,H", 2, 2, |cpu| cpu.registers.h &= !(1u8 << 4)),
+    op!(0xA5, "RES 4,L", 2, 2, |cpu| cpu.registers.l &= !(1u8 << 4)),
+    op!(0xA6, "RES 4,(HL)", 2, 4, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.write_byte(cpu.registers.hl(), data & !(1u8 << 4))}),
+    op!(0xA7, "RES 4,A", 2, 2, |cpu| cpu.registers.a &= !(1u8 << 4)),
+    op!(0xA8, "RES 5,B", 2, 2, |cpu| cpu.registers.b &= !(1u8 << 5)),
+    op!(0xA9, "RES 5,C", 2, 2, |cpu| cpu.registers.c &= !(1u8 << 5)),
+    op!(0xAA, "RES 5,D", 2, 2, |cpu| cpu.registers.d &= !(1u8 << 5)),
+    op!(0xAB, "RES 5,E", 2, 2, |cpu| cpu.registers.e &= !(1u8 << 5)),
+    op!(0xAC, "RES 5,H", 2, 2, |cpu| cpu.registers.h &= !(1u8 << 5)),
+    op!(0xAD, "RES 5,L", 2, 2, |cpu| cpu.registers.l &= !(1u8 << 5)),
+    op!(0xAE, "RES 5,(HL)", 2, 4, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.write_byte(cpu.registers.hl(), data & !(1u8 << 5))}),
+    op!(0xAF, "RES 5,A", 2, 2, |cpu| cpu.registers.a &= !(1u8 << 5)),
+    op!(0xB0, "RES 6,B", 2, 2, |cpu| cpu.registers.b &= !(1u8 << 6)),
+    op!(0xB1, "RES 6,C", 2, 2, |cpu| cpu.registers.c &= !(1u8 << 6)),
+    op!(0xB2, "RES 6,D", 2, 2, |cpu| cpu.registers.d &= !(1u8 << 6)),
+    op!(0xB3, "RES 6,E", 2, 2, |cpu| cpu.registers.e &= !(1u8 << 6)),
+    op!(0xB4, "RES 6,H", 2, 2, |cpu| cpu.registers.h &= !(1u8 << 6)),
+    op!(0xB5, "RES 6,L", 2, 2, |cpu| cpu.registers.l &= !(1u8 << 6)),
+    op!(0xB6, "RES 6,(HL)", 2, 4, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.write_byte(cpu.registers.hl(), data & !(1u8 << 6))}),
+    op!(0xB7, "RES 6,A", 2, 2, |cpu| cpu.registers.a &= !(1u8 << 6)),
+    op!(0xB8, "RES 7,B", 2, 2, |cpu| cpu.registers.b &= !(1u8 << 7)),
+    op!(0xB9, "RES 7,C", 2, 2, |cpu| cpu.registers.c &= !(1u8 << 7)),
+    op!(0xBA, "RES 7,D", 2, 2, |cpu| cpu.registers.d &= !(1u8 << 7)),
+    op!(0xBB, "RES 7,E", 2, 2, |cpu| cpu.registers.e &= !(1u8 << 7)),
+    op!(0xBC, "RES 7,H", 2, 2, |cpu| cpu.registers.h &= !(1u8 << 7)),
+    op!(0xBD, "RES 7,L", 2, 2, |cpu| cpu.registers.l &= !(1u8 << 7)),
+    op!(0xBE, "RES 7,(HL)", 2, 4, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.write_byte(cpu.registers.hl(), data & !(1u8 << 7))}),
+    op!(0xBF, "RES 7,A", 2, 2, |cpu| cpu.registers.a &= !(1u8 << 7)),
+    op!(0xC0, "SET 0,B", 2, 2, |cpu| cpu.registers.b |= 1u8 << 0),
+    op!(0xC1, "SET 0,C", 2, 2, |cpu| cpu.registers.c |= 1u8 << 0),
+    op!(0xC2, "SET 0,D", 2, 2, |cpu| cpu.registers.d |= 1u8 << 0),
+    op!(0xC3, "SET 0,E", 2, 2, |cpu| cpu.registers.e |= 1u8 << 0),
+    op!(0xC4, "SET 0,H", 2, 2, |cpu| cpu.registers.h |= 1u8 << 0),
+    op!(0xC5, "SET 0,L", 2, 2, |cpu| cpu.registers.l |= 1u8 << 0),
+    op!(0xC6, "SET 0,(HL)", 2, 4, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.write_byte(cpu.registers.hl(), data | (1u8 << 0))}),
+    op!(0xC7, "SET 0,A", 2, 2, |cpu| cpu.registers.a |= 1u8 << 0),
+    op!(0xC8, "SET 1,B", 2, 2, |cpu| cpu.registers.b |= 1u8 << 1),
+    op!(0xC9, "SET 1,C", 2, 2, |cpu| cpu.registers.c |= 1u8 << 1),
+    op!(0xCA, "SET 1,D", 2, 2, |cpu| cpu.registers.d |= 1u8 << 1),
+    op!(0xCB, "SET 1,E", 2, 2, |cpu| cpu.registers.e |= 1u8 << 1),
+    op!(0xCC, "SET 1,H", 2, 2, |cpu| cpu.registers.h |= 1u8 << 1),
+    op!(0xCD, "SET 1,L", 2, 2, |cpu| cpu.registers.l |= 1u8 << 1),
+    op!(0xCE, "SET 1,(HL)", 2, 4, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.write_byte(cpu.registers.hl(), data | (1u8 << 1))}),
+    op!(0xCF, "SET 1,A", 2, 2, |cpu| cpu.registers.a |= 1u8 << 1),
+    op!(0xD0, "SET 2,B", 2, 2, |cpu| cpu.registers.b |= 1u8 << 2),
+    op!(0xD1, "SET 2,C", 2, 2, |cpu| cpu.registers.c |= 1u8 << 2),
+    op!(0xD2, "SET 2,D", 2, 2, |cpu| cpu.registers.d |= 1u8 << 2),
+    op!(0xD3, "SET 2,E", 2, 2, |cpu| cpu.registers.e |= 1u8 << 2),
+    op!(0xD4, "SET 2,H", 2, 2, |cpu| cpu.registers.h |= 1u8 << 2),
+    op!(0xD5, "SET 2,L", 2, 2, |cpu| cpu.registers.l |= 1u8 << 2),
+    op!(0xD6, "SET 2,(HL)", 2, 4, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.write_byte(cpu.registers.hl(), data | (1u8 << 2))}),
+    op!(0xD7, "SET 2,A", 2, 2, |cpu| cpu.registers.a |= 1u8 << 2),
+    op!(0xD8, "SET 3,B", 2, 2, |cpu| cpu.registers.b |= 1u8 << 3),
+    op!(0xD9, "SET 3,C", 2, 2, |cpu| cpu.registers.c |= 1u8 << 3),
+    op!(0xDA, "SET 3,D", 2, 2, |cpu| cpu.registers.d |= 1u8 << 3),
+    op!(0xDB, "SET 3,E", 2, 2, |cpu| cpu.registers.e |= 1u8 << 3),
+    op!(0xDC, "SET 3,H", 2, 2, |cpu| cpu.registers.h |= 1u8 << 3),
+    op!(0xDD, "SET 3,L", 2, 2, |cpu| cpu.registers.l |= 1u8 << 3),
+    op!(0xDE, "SET 3,(HL)", 2, 4, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.write_byte(cpu.registers.hl(), data | (1u8 << 3))}),
+    op!(0xDF, "SET 3,A", 2, 2, |cpu| cpu.registers.a |= 1u8 << 3),
+    op!(0xE0, "SET 4,B", 2, 2, |cpu| cpu.registers.b |= 1u8 << 4),
+    op!(0xE1, "SET 4,C", 2, 2, |cpu| cpu.registers.c |= 1u8 << 4),
+    op!(0xE2, "SET 4,D", 2, 2, |cpu| cpu.registers.d |= 1u8 << 4),
+    op!(0xE3, "SET 4,E", 2, 2, |cpu| cpu.registers.e |= 1u8 << 4),
+    op!(0xE4, "SET 4,H", 2, 2, |cpu| cpu.registers.h |= 1u8 << 4),
+    op!(0xE5, "SET 4,L", 2, 2, |cpu| cpu.registers.l |= 1u8 << 4),
+    op!(0xE6, "SET 4,(HL)", 2, 4, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.write_byte(cpu.registers.hl(), data | (1u8 << 4))}),
+    op!(0xE7, "SET 4,A", 2, 2, |cpu| cpu.registers.a |= 1u8 << 4),
+    op!(0xE8, "SET 5,B", 2, 2, |cpu| cpu.registers.b |= 1u8 << 5),
+    op!(0xE9, "SET 5,C", 2, 2, |cpu| cpu.registers.c |= 1u8 << 5),
+    op!(0xEA, "SET 5,D", 2, 2, |cpu| cpu.registers.d |= 1u8 << 5),
+    op!(0xEB, "SET 5,E", 2, 2, |cpu| cpu.registers.e |= 1u8 << 5),
+    op!(0xEC, "SET 5,H", 2, 2, |cpu| cpu.registers.h |= 1u8 << 5),
+    op!(0xED, "SET 5,L", 2, 2, |cpu| cpu.registers.l |= 1u8 << 5),
+    op!(0xEE, "SET 5,(HL)", 2, 4, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.write_byte(cpu.registers.hl(), data | (1u8 << 5))}),
+    op!(0xEF, "SET 5,A", 2, 2, |cpu| cpu.registers.a |= 1u8 << 5),
+    op!(0xF0, "SET 6,B", 2, 2, |cpu| cpu.registers.b |= 1u8 << 6),
+    op!(0xF1, "SET 6,C", 2, 2, |cpu| cpu.registers.c |= 1u8 << 6),
+    op!(0xF2, "SET 6,D", 2, 2, |cpu| cpu.registers.d |= 1u8 << 6),
+    op!(0xF3, "SET 6,E", 2, 2, |cpu| cpu.registers.e |= 1u8 << 6),
+    op!(0xF4, "SET 6,H", 2, 2, |cpu| cpu.registers.h |= 1u8 << 6),
+    op!(0xF5, "SET 6,L", 2, 2, |cpu| cpu.registers.l |= 1u8 << 6),
+    op!(0xF6, "SET 6,(HL)", 2, 4, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.write_byte(cpu.registers.hl(), data | (1u8 << 6))}),
+    op!(0xF7, "SET 6,A", 2, 2, |cpu| cpu.registers.a |= 1u8 << 6),
+    op!(0xF8, "SET 7,B", 2, 2, |cpu| cpu.registers.b |= 1u8 << 7),
+    op!(0xF9, "SET 7,C", 2, 2, |cpu| cpu.registers.c |= 1u8 << 7),
+    op!(0xFA, "SET 7,D", 2, 2, |cpu| cpu.registers.d |= 1u8 << 7),
+    op!(0xFB, "SET 7,E", 2, 2, |cpu| cpu.registers.e |= 1u8 << 7),
+    op!(0xFC, "SET 7,H", 2, 2, |cpu| cpu.registers.h |= 1u8 << 7),
+    op!(0xFD, "SET 7,L", 2, 2, |cpu| cpu.registers.l |= 1u8 << 7),
+    op!(0xFE, "SET 7,(HL)", 2, 4, |cpu| {let data = cpu.read_byte(cpu.registers.hl()); cpu.write_byte(cpu.registers.hl(), data | (1u8 << 7))}),
+    op!(0xFF, "SET 7,A", 2, 2, |cpu| cpu.registers.a |= 1u8 << 7),
+];
+
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn xor_test() {
-        let mut cpu = Cpu::new();
+        let mut cpu = Cpu::new(Memory::new(&[0; 0x8000], None));
         cpu.registers.a = 0x0D;
         cpu.xor(0x0D);
         assert_eq!(cpu.registers.a, 0x00);
     }
 
+    #[test]
+    fn interrupt_dispatch_test() {
+        let mut cpu = Cpu::new(Memory::new(&[0; 0x8000], None));
+        cpu.registers.pc = 0x1234;
+        cpu.registers.sp = 0xD000;
+        cpu.ime = Ime::Enabled;
+        cpu.memory.write_byte(0xFFFF, 0x1F);
+        cpu.memory.write_byte(0xFF0F, 0x14);
+        assert_eq!(cpu.cycle(), 5);
+        assert_eq!(cpu.registers.pc, 0x0050);
+        assert_eq!(cpu.memory.read_word(0xCFFE), 0x1234);
+        assert_eq!(cpu.memory.read_byte(0xFF0F), 0xF0);
+        assert_eq!(cpu.ime, Ime::Disabled);
+    }
+
+    #[test]
+    fn interrupt_ie_push_test() {
+        //Upper byte of pc 0x0200 overwrites IE with 0x02, cancelling vblank
+        let mut cpu = Cpu::new(Memory::new(&[0; 0x8000], None));
+        cpu.registers.pc = 0x0200;
+        cpu.registers.sp = 0x0000;
+        cpu.ime = Ime::Enabled;
+        cpu.memory.write_byte(0xFFFF, 0x01);
+        cpu.memory.write_byte(0xFF0F, 0x01);
+        cpu.cycle();
+        assert_eq!(cpu.registers.pc, 0x0000);
+        assert_eq!(cpu.memory.read_byte(0xFF0F), 0xE1);
+
+        //With stat also requested it's taken instead
+        let mut cpu = Cpu::new(Memory::new(&[0; 0x8000], None));
+        cpu.registers.pc = 0x0200;
+        cpu.registers.sp = 0x0000;
+        cpu.ime = Ime::Enabled;
+        cpu.memory.write_byte(0xFFFF, 0x01);
+        cpu.memory.write_byte(0xFF0F, 0x03);
+        cpu.cycle();
+        assert_eq!(cpu.registers.pc, 0x0048);
+    }
+
+    #[test]
+    fn halt_test() {
+        let mut cpu = Cpu::new(Memory::new(&[0; 0x8000], None));
+        cpu.registers.pc = 0xC000;
+        cpu.registers.a = 0;
+        cpu.memory.write_byte(0xC000, 0x76); //HALT
+        cpu.memory.write_byte(0xC001, 0x3C); //INC A
+        cpu.memory.write_byte(0xFFFF, 0x04);
+        cpu.cycle();
+        assert!(cpu.halted);
+        cpu.cycle();
+        assert_eq!(cpu.registers.pc, 0xC001);
+
+        //Wakes without IME and carries on without servicing the interrupt
+        cpu.memory.write_byte(0xFF0F, 0x04);
+        cpu.cycle();
+        assert!(!cpu.halted);
+        assert_eq!(cpu.registers.a, 1);
+        assert_eq!(cpu.memory.read_byte(0xFF0F), 0xE4);
+
+        //With IME on the handler is entered, returning to after HALT
+        cpu.registers.pc = 0xC000;
+        cpu.registers.sp = 0xD000;
+        cpu.memory.write_byte(0xFF0F, 0x00);
+        cpu.ime = Ime::Enabled;
+        cpu.cycle();
+        cpu.memory.write_byte(0xFF0F, 0x04);
+        cpu.cycle();
+        assert_eq!(cpu.registers.pc, 0x0050);
+        assert_eq!(cpu.memory.read_word(0xCFFE), 0xC001);
+    }
+
+    #[test]
+    fn halt_bug_test() {
+        let mut cpu = Cpu::new(Memory::new(&[0; 0x8000], None));
+        cpu.registers.pc = 0xC000;
+        cpu.registers.a = 0;
+        cpu.memory.write_byte(0xC000, 0x76); //HALT
+        cpu.memory.write_byte(0xC001, 0x3C); //INC A
+        cpu.memory.write_byte(0xFFFF, 0x04);
+        cpu.memory.write_byte(0xFF0F, 0x04);
+        cpu.cycle();
+        assert!(!cpu.halted);
+        cpu.cycle();
+        cpu.cycle();
+        assert_eq!(cpu.registers.a, 2);
+        assert_eq!(cpu.registers.pc, 0xC002);
+    }
+
+    #[test]
+    fn stop_test() {
+        let mut cpu = Cpu::new(Memory::new(&[0; 0x8000], None));
+        cpu.registers.pc = 0xC000;
+        cpu.registers.a = 0;
+        cpu.memory.write_byte(0xC000, 0x10); //STOP
+        cpu.memory.write_byte(0xC001, 0x00);
+        cpu.memory.write_byte(0xC002, 0x3C); //INC A
+        cpu.memory.timer.divider = 0x1234;
+        cpu.memory.memory[0xFF00] = 0xDF;
+        cpu.cycle();
+        assert!(cpu.stopped);
+        assert_eq!(cpu.registers.pc, 0xC002);
+        assert_eq!(cpu.memory.read_byte(0xFF04), 0);
+        assert_eq!(cpu.cycle(), 0);
+        assert_eq!(cpu.registers.a, 0);
+
+        //Pressing a button wakes it
+        cpu.memory.memory[0xFF00] = 0xDE;
+        cpu.cycle();
+        assert!(!cpu.stopped);
+        assert_eq!(cpu.registers.a, 1);
+    }
+
+    //Load a program into work ram and point pc at it
+    fn load_program(program: &[u8]) -> Cpu {
+        let mut cpu = Cpu::new(Memory::new(&[0; 0x8000], None));
+        for (offset, byte) in program.iter().enumerate() {
+            cpu.memory.write_byte(0xC000 + offset as u16, *byte);
+        }
+        cpu.registers.pc = 0xC000;
+        cpu.registers.sp = 0xD000;
+        cpu.memory.write_byte(0xFFFF, 0x04);
+        cpu
+    }
+
+    #[test]
+    fn ei_delay_test() {
+        //EI, NOP, NOP with the timer interrupt already pending
+        let mut cpu = load_program(&[0xFB, 0x00, 0x00]);
+        cpu.memory.write_byte(0xFF0F, 0x04);
+        cpu.cycle();
+        assert_eq!(cpu.ime, Ime::Pending);
+        cpu.cycle();
+        assert_eq!(cpu.registers.pc, 0xC002);
+        assert_eq!(cpu.ime, Ime::Enabled);
+        cpu.cycle();
+        assert_eq!(cpu.registers.pc, 0x0050);
+        assert_eq!(cpu.memory.read_word(0xCFFE), 0xC002);
+    }
+
+    #[test]
+    fn ei_di_test() {
+        //EI, DI never lets the interrupt in
+        let mut cpu = load_program(&[0xFB, 0xF3, 0x00]);
+        cpu.memory.write_byte(0xFF0F, 0x04);
+        for _ in 0..3 {
+            cpu.cycle();
+        }
+        assert_eq!(cpu.registers.pc, 0xC003);
+        assert_eq!(cpu.ime, Ime::Disabled);
+    }
+
+    #[test]
+    fn ei_halt_test() {
+        //EI, HALT waits for the interrupt then returns after the HALT
+        let mut cpu = load_program(&[0xFB, 0x76, 0x00]);
+        cpu.cycle();
+        cpu.cycle();
+        assert!(cpu.halted);
+        assert_eq!(cpu.ime, Ime::Enabled);
+        cpu.memory.write_byte(0xFF0F, 0x04);
+        cpu.cycle();
+        assert_eq!(cpu.registers.pc, 0x0050);
+        assert_eq!(cpu.memory.read_word(0xCFFE), 0xC002);
+
+        //With the interrupt already pending the handler returns to the HALT
+        let mut cpu = load_program(&[0xFB, 0x76, 0x00]);
+        cpu.memory.write_byte(0xFF0F, 0x04);
+        cpu.cycle();
+        cpu.cycle();
+        assert!(!cpu.halted);
+        cpu.cycle();
+        assert_eq!(cpu.registers.pc, 0x0050);
+        assert_eq!(cpu.memory.read_word(0xCFFE), 0xC001);
+    }
+
+    #[test]
+    fn reti_test() {
+        //RETI enables interrupts before the next instruction
+        let mut cpu = load_program(&[0xD9]);
+        cpu.registers.sp = 0xCFFE;
+        cpu.memory.write_word(0xCFFE, 0xC100);
+        cpu.memory.write_byte(0xFF0F, 0x04);
+        cpu.cycle();
+        assert_eq!(cpu.registers.pc, 0xC100);
+        assert_eq!(cpu.ime, Ime::Enabled);
+        cpu.cycle();
+        assert_eq!(cpu.registers.pc, 0x0050);
+        assert_eq!(cpu.memory.read_word(0xCFFE), 0xC100);
+    }
+
+    //Flag register from individual flags, for checking against results worked out with wider integers
+    fn flags(zero: bool, addsub: bool, half: bool, carry: bool) -> u8 {
+        (zero as u8) << 7 | (addsub as u8) << 6 | (half as u8) << 5 | (carry as u8) << 4
+    }
+
+    fn bcd(value: u8) -> u8 {
+        ((value / 10) << 4) | (value % 10)
+    }
+
+    #[test]
+    fn alu_8bit_test() {
+        let mut cpu = Cpu::new(Memory::new(&[0; 0x8000], None));
+        for a in 0..=255u8 {
+            for data in 0..=255u8 {
+                for carry in 0..2u8 {
+                    let sum = a as u16 + data as u16 + carry as u16;
+                    cpu.registers.a = a;
+                    cpu.registers.f = carry << 4;
+                    cpu.add8_carry(data);
+                    let half = (a & 0xF) + (data & 0xF) + carry > 0xF;
+                    assert_eq!((cpu.registers.a, cpu.registers.f), (sum as u8, flags(sum as u8 == 0, false, half, sum > 0xFF)));
+
+                    let difference = a as i16 - data as i16 - carry as i16;
+                    cpu.registers.a = a;
+                    cpu.registers.f = carry << 4;
+                    cpu.sub8_carry(data);
+                    let half = ((a & 0xF) as i16) - ((data & 0xF) as i16) - (carry as i16) < 0;
+                    assert_eq!((cpu.registers.a, cpu.registers.f), (difference as u8, flags(difference as u8 == 0, true, half, difference < 0)));
+                }
+
+                let sum = a as u16 + data as u16;
+                cpu.registers.a = a;
+                cpu.add8(data);
+                let half = (a & 0xF) + (data & 0xF) > 0xF;
+                assert_eq!((cpu.registers.a, cpu.registers.f), (sum as u8, flags(sum as u8 == 0, false, half, sum > 0xFF)));
+
+                let expected = flags(a == data, true, a & 0xF < data & 0xF, a < data);
+                cpu.registers.a = a;
+                cpu.cmp(data);
+                assert_eq!((cpu.registers.a, cpu.registers.f), (a, expected));
+                cpu.sub8(data);
+                assert_eq!((cpu.registers.a, cpu.registers.f), (a.wrapping_sub(data), expected));
+            }
+        }
+    }
+
+    #[test]
+    fn daa_test() {
+        //Every pair of BCD values added and subtracted
+        let mut cpu = Cpu::new(Memory::new(&[0; 0x8000], None));
+        for x in 0..100u8 {
+            for y in 0..100u8 {
+                cpu.registers.a = bcd(x);
+                cpu.add8(bcd(y));
+                cpu.daa();
+                let sum = (x + y) % 100;
+                assert_eq!((cpu.registers.a, cpu.registers.f), (bcd(sum), flags(sum == 0, false, false, x + y >= 100)));
+
+                cpu.registers.a = bcd(x);
+                cpu.sub8(bcd(y));
+                cpu.daa();
+                let difference = (x + 100 - y) % 100;
+                assert_eq!((cpu.registers.a, cpu.registers.f), (bcd(difference), flags(difference == 0, true, false, x < y)));
+            }
+        }
+    }
+
+    #[test]
+    fn alu_16bit_test() {
+        let mut cpu = Cpu::new(Memory::new(&[0; 0x8000], None));
+        //Zero flag is kept
+        cpu.registers.f = flags(true, true, false, false);
+        cpu.registers.set_hl(0x0FFF);
+        cpu.add_hl(0x0001);
+        assert_eq!((cpu.registers.hl(), cpu.registers.f), (0x1000, flags(true, false, true, false)));
+        cpu.registers.set_hl(0xF000);
+        cpu.add_hl(0x1000);
+        assert_eq!((cpu.registers.hl(), cpu.registers.f), (0x0000, flags(true, false, false, true)));
+        cpu.registers.set_hl(0x00FF);
+        cpu.add_hl(0x0001);
+        assert_eq!((cpu.registers.hl(), cpu.registers.f), (0x0100, flags(true, false, false, false)));
+
+        //Flags come from the low byte whatever the sign
+        cpu.registers.sp = 0xFFFF;
+        assert_eq!(cpu.add_sp(0x01), 0x0000);
+        assert_eq!(cpu.registers.f, flags(false, false, true, true));
+        cpu.registers.sp = 0x0000;
+        assert_eq!(cpu.add_sp(0xFF), 0xFFFF);
+        assert_eq!(cpu.registers.f, flags(false, false, false, false));
+        cpu.registers.sp = 0xD00F;
+        assert_eq!(cpu.add_sp(0xF1), 0xD000);
+        assert_eq!(cpu.registers.f, flags(false, false, true, true));
+    }
+
+    #[test]
+    fn rotate_test() {
+        let mut cpu = Cpu::new(Memory::new(&[0; 0x8000], None));
+        cpu.registers.f = 0;
+        assert_eq!(cpu.rlc(0x80), 0x01);
+        assert_eq!(cpu.registers.f, flags(false, false, false, true));
+        assert_eq!(cpu.rl(0x80), 0x01);
+        assert_eq!(cpu.registers.f, flags(false, false, false, true));
+        assert_eq!(cpu.rl(0x40), 0x81);
+        assert_eq!(cpu.registers.f, flags(false, false, false, false));
+        assert_eq!(cpu.rr(0x01), 0x00);
+        assert_eq!(cpu.registers.f, flags(true, false, false, true));
+        assert_eq!(cpu.rrc(0x01), 0x80);
+        assert_eq!(cpu.registers.f, flags(false, false, false, true));
+
+        //Accumulator rotates always clear zero
+        cpu.registers.a = 0x80;
+        cpu.registers.f = 0;
+        cpu.rla();
+        assert_eq!((cpu.registers.a, cpu.registers.f), (0x00, flags(false, false, false, true)));
+        cpu.rla();
+        assert_eq!((cpu.registers.a, cpu.registers.f), (0x01, flags(false, false, false, false)));
+        cpu.rrca();
+        assert_eq!((cpu.registers.a, cpu.registers.f), (0x80, flags(false, false, false, true)));
+        cpu.rra();
+        assert_eq!((cpu.registers.a, cpu.registers.f), (0xC0, flags(false, false, false, false)));
+    }
+
+    #[test]
+    fn pop_af_test() {
+        let mut cpu = load_program(&[0xF1]);
+        cpu.registers.sp = 0xCFFE;
+        cpu.memory.write_word(0xCFFE, 0x12FF);
+        cpu.cycle();
+        assert_eq!(cpu.registers.af(), 0x12F0);
+    }
+
+    #[test]
+    fn table_order_test() {
+        for index in 0..256 {
+            assert_eq!(INSTRUCTIONS[index].opcode as usize, index);
+            assert_eq!(CB_INSTRUCTIONS[index].opcode as usize, index);
+        }
+        assert_eq!(INSTRUCTIONS[0x26].length, 2);
+        assert_eq!(INSTRUCTIONS[0xDA].length, 3);
+    }
+
+    #[test]
+    fn instruction_length_test() {
+        //Anything that doesn't jump moves pc on by its length, conditional ones when not taken
+        let jumps = ["JP ", "JR ", "CALL ", "RET", "RST "];
+        for opcode in 0..=255u8 {
+            let instruction = &INSTRUCTIONS[opcode as usize];
+            match instruction.execute {
+                Execute::Always(_) if jumps.iter().any(|jump| instruction.mnemonic.starts_with(jump)) => continue,
+                Execute::Prefix => continue,
+                _ => (),
+            }
+            for flags in [0x00, 0xF0].iter() {
+                let mut cpu = load_program(&[opcode, 0x00, 0x00]);
+                cpu.registers.f = *flags;
+                let cycles = cpu.cycle();
+                if cycles == instruction.cycles {
+                    assert_eq!(cpu.registers.pc, 0xC000 + instruction.length as u16, "{}", instruction.mnemonic);
+                }
+                else {
+                    assert_eq!(cycles, instruction.taken_cycles, "{}", instruction.mnemonic);
+                }
+            }
+        }
+
+        for opcode in 0..=255u8 {
+            let instruction = &CB_INSTRUCTIONS[opcode as usize];
+            let mut cpu = load_program(&[0xCB, opcode]);
+            assert_eq!(cpu.cycle(), instruction.cycles, "{}", instruction.mnemonic);
+            assert_eq!(cpu.registers.pc, 0xC000 + instruction.length as u16, "{}", instruction.mnemonic);
+        }
+    }
+
+    #[test]
+    fn branch_cycles_test() {
+        //JR NZ,+4
+        let mut cpu = load_program(&[0x20, 0x04]);
+        cpu.registers.f = flags(true, false, false, false);
+        assert_eq!(cpu.cycle(), 2);
+        assert_eq!(cpu.registers.pc, 0xC002);
+        let mut cpu = load_program(&[0x20, 0x04]);
+        cpu.registers.f = 0;
+        assert_eq!(cpu.cycle(), 3);
+        assert_eq!(cpu.registers.pc, 0xC006);
+
+        //CALL C,a16 then RET
+        let mut cpu = load_program(&[0xDC, 0x00, 0xC1]);
+        cpu.memory.write_byte(0xC100, 0xC9);
+        cpu.registers.f = flags(false, false, false, true);
+        assert_eq!(cpu.cycle(), 6);
+        assert_eq!(cpu.registers.pc, 0xC100);
+        assert_eq!(cpu.cycle(), 4);
+        assert_eq!(cpu.registers.pc, 0xC003);
+    }
+
+    #[test]
+    fn bus_timing_test() {
+        //Each instruction clocks the rest of the system for as many M-cycles as it reports
+        //Counted as ticks rather than from DIV, STOP resets it
+        for opcode in 0..=255u8 {
+            for flags in [0x00, 0xF0].iter() {
+                let mut cpu = load_program(&[opcode, 0x00, 0xC1]);
+                cpu.registers.f = *flags;
+                let cycles = cpu.cycle();
+                assert_eq!(cpu.ticked, cycles, "{}", INSTRUCTIONS[opcode as usize].mnemonic);
+            }
+        }
+
+        //Pop the return address from TIMA/TMA, TIMA increments once DIV passes 16
+        //Starting DIV at 4 it's seen from the 3rd M-cycle on, at 8 from the 2nd
+        let ret_from_timer = |opcode: u8, divider: u16| {
+            let mut cpu = load_program(&[opcode]);
+            cpu.memory.write_byte(0xFF07, 0x05);
+            cpu.registers.sp = 0xFF05;
+            cpu.registers.f = 0x80;
+            cpu.memory.timer.divider = divider;
+            cpu.cycle();
+            cpu.registers.pc
+        };
+        //RET Z pops in M-cycles 3 and 4, after checking the flag
+        assert_eq!(ret_from_timer(0xC8, 0), 0x0000);
+        assert_eq!(ret_from_timer(0xC8, 4), 0x0001);
+        //RET pops straight away in M-cycles 2 and 3
+        assert_eq!(ret_from_timer(0xC9, 4), 0x0000);
+        assert_eq!(ret_from_timer(0xC9, 8), 0x0001);
+
+        //TIMA increments every 4 M-cycles, LD A,(a16) reads it in its last one
+        let mut cpu = load_program(&[0xFA, 0x05, 0xFF]);
+        cpu.memory.write_byte(0xFF07, 0x05);
+        cpu.memory.timer.divider = 0;
+        cpu.cycle();
+        assert_eq!(cpu.registers.a, 1);
+
+        //LD (a16),A writes after that increment so it isn't lost
+        let mut cpu = load_program(&[0xEA, 0x05, 0xFF]);
+        cpu.memory.write_byte(0xFF07, 0x05);
+        cpu.memory.timer.divider = 0;
+        cpu.registers.a = 0x20;
+        cpu.cycle();
+        assert_eq!(cpu.memory.timer.tima, 0x20);
+    }
+
     #[test]
     fn ret_test() {
         //0xC9 is return
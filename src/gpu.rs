@@ -13,8 +13,14 @@
 
 //So How Will We Do This?
 
+use crate::interrupt::{Interrupt, Interrupts};
+
 const VRAM_START: u16 = 0x8000;
 //const VRAM_END: u16   = 0x9FFF;
+const OAM_START: u16 = 0xFE00;
+
+//Only the first 10 sprites found in OAM on a line are drawn
+const SPRITES_PER_LINE: usize = 10;
 
 
 //Since each pixel is governed by two bits...
@@ -27,10 +33,36 @@ pub enum PixelColor {
     Darkest,  
 }
 
+impl PixelColor {
+    //Palette registers hold a 2 bit shade for each of the 4 color indices, index 0 in the low bits
+    pub fn from_palette(palette: u8, index: u8) -> PixelColor {
+        match (palette >> (index * 2)) & 0x03 {
+            0 => PixelColor::Lightest,
+            1 => PixelColor::Light,
+            2 => PixelColor::Dark,
+            _ => PixelColor::Darkest,
+        }
+    }
+}
 
-//Each Row can be represented as an array of 8 PixelColor types
+//Sprite attributes as stored in OAM, 4 bytes each
+//Byte 0 - Y position + 16
+//Byte 1 - X position + 8
+//Byte 2 - Tile number, always from $8000
+//Byte 3 - Flags: 7 behind background colors 1-3, 6 Y flip, 5 X flip, 4 OBP1 when set
+#[derive(Debug, Copy, Clone)]
+struct Sprite {
+    y: i16,
+    x: i16,
+    tile: u8,
+    flags: u8,
+}
+
+
+//Each Row can be represented as an array of 8 color indices
+//Indices are only turned into shades through the palettes when a line is drawn
 //A full tile would then just be an array of 8 rows 
-type Tile = [[PixelColor; 8];8];
+type Tile = [[u8; 8];8];
 
 //The full tile set would then just be an array of 384 tiles
 //We will worry about dividing the tile sets into window and
@@ -40,6 +72,7 @@ type TileSet = [Tile; 384];
 pub struct Vram {
     tile_set: TileSet, 
     vram: [u8; 0x2000],
+    oam: [u8; 0xA0], //0xFE00-0xFE9F
     render_mode: u8,
     pub render_mode_cycles: u32,
     pub lcd_control: LcdControl, //0xFF40
@@ -47,15 +80,17 @@ pub struct Vram {
     pub scroll_x: u8, //0xFF43
     pub window_y: u8, //0xFF4A
     pub window_x: u8, //0xFF4B
+    window_line: u8, //Window row drawn next
+    window_triggered: bool, //WY matched the current line at some point this frame
     pub scan_row: u8, //0xFF44
-    pub lcd_stat: u8, //0xFF45 Holds value that will interrupt when matched with scan_row
+    pub lyc: u8, //0xFF45 Compared against scan_row for the coincidence flag
+    stat_select: u8, //0xFF41 bits 3-6, which conditions drive the STAT interrupt
+    stat_line: bool, //All selected STAT conditions ORed together
     pub background_palette: u8, //0xFF47
+    pub sprite_palette_0: u8, //0xFF48
+    pub sprite_palette_1: u8, //0xFF49
     pub pixel_buffer: [u8; (160*144*3) as usize],
     pub vblank_flag: bool, //Tells emulator loop to update texture
-    pub vblank_int_enable: bool, //Interrupt enable for vblank
-    pub vblank_int_request: bool, //Interrupt Request for vblank
-    pub lcd_stat_int_enable: bool, //Interrupt enable for LCD stat
-    pub lcd_stat_int_request: bool //Interrupt request for LCD stat 
 }
 
 pub struct LcdControl {
@@ -88,11 +123,12 @@ impl Vram {
 
     pub fn new() -> Vram {
 
-        let blank_tile = [[PixelColor::Lightest; 8];8];
+        let blank_tile = [[0u8; 8];8];
         let blank_set = [blank_tile; 384];
         Vram {
             tile_set: blank_set,
             vram: [0;0x2000],
+            oam: [0; 0xA0],
             render_mode : 0,
             render_mode_cycles: 0,
             lcd_control: LcdControl::new(),
@@ -100,44 +136,36 @@ impl Vram {
             scroll_x: 0,
             window_y: 0,
             window_x: 0,
+            window_line: 0,
+            window_triggered: false,
             scan_row: 0,
-            lcd_stat: 0,
+            lyc: 0,
+            stat_select: 0,
+            stat_line: false,
             background_palette: 0,
+            sprite_palette_0: 0,
+            sprite_palette_1: 0,
             pixel_buffer: [0; (160*144*3) as usize],
             vblank_flag: false,
-            vblank_int_enable: false,
-            vblank_int_request: false,
-            lcd_stat_int_enable: false,
-            lcd_stat_int_request: false,
         }
 
     }
 
 
 
-    pub fn step(&mut self,) {
-
-        //Check for STAT interrupt
-        if self.scan_row == self.lcd_stat {
-            self.lcd_stat_int_request = true;
-        }
-        else {
-            self.lcd_stat_int_request = false;
-        }
-
-        self.vblank_int_request = false;
+    pub fn step(&mut self, interrupts: &mut Interrupts) {
 
         //All clock cycles divided by 4
         match self.render_mode {
             //H-Blank - CPU can access VRAM and OAM
             0 => {
-                self.vblank_int_request = false;
                 if self.render_mode_cycles >= 51 {
                     self.render_mode_cycles = 0;
                     self.scan_row += 1;
 
                     if self.scan_row == 144 {
                         self.render_mode = 1;
+                        interrupts.request(Interrupt::VBlank);
                         //Write pixel buffer to screen
                     }
                     else {
@@ -148,7 +176,6 @@ impl Vram {
 
             //V-Blank - CPU can access VRAM and OAM
             1 => {
-                self.vblank_int_request = true;
                 if self.render_mode_cycles >= 114 {
                     self.render_mode_cycles = 0;
                     self.scan_row += 1;
@@ -157,6 +184,8 @@ impl Vram {
                         self.vblank_flag = true;
                         self.scan_row = 0;
                         self.render_mode = 2;
+                        self.window_line = 0;
+                        self.window_triggered = false;
                     }
                 }
 
@@ -164,7 +193,6 @@ impl Vram {
 
             //LCD is reading OAM, CPU cannot access VRAM or OAM
             2 => {
-                self.vblank_int_request = false;
                 if self.render_mode_cycles >= 20 {
                     self.render_mode_cycles = 0;
                     self.render_mode = 3;
@@ -173,7 +201,6 @@ impl Vram {
 
             //LCD is reading OAM and VRAM, CPU cannot access VRAM, OAM, or Color Palette
             3 => {
-                self.vblank_int_request = false;
                 if self.render_mode_cycles >= 43 {
                     self.render_mode_cycles = 0;
                     self.render_mode = 0;
@@ -186,13 +213,59 @@ impl Vram {
             _ => {panic!("Invalid Render Mode!")}
         }
 
-        if self.scan_row == self.lcd_stat {self.lcd_stat_int_request = true;}
+        self.update_stat(interrupts);
 
     }
 
+    //STAT register
+    //Bit 7 - Unused, always 1
+    //Bit 6 - LYC=LY interrupt select
+    //Bit 5 - Mode 2 OAM interrupt select
+    //Bit 4 - Mode 1 V-Blank interrupt select
+    //Bit 3 - Mode 0 H-Blank interrupt select
+    //Bit 2 - LYC=LY coincidence flag (read only)
+    //Bit 1-0 - Current mode (read only), 0 while the display is off
+    pub fn read_stat(&self) -> u8 {
+        let mode = if self.lcd_control.display {self.render_mode} else {0};
+        0x80 | self.stat_select | ((self.scan_row == self.lyc) as u8) << 2 | mode
+    }
+
+    pub fn write_stat(&mut self, data: u8, interrupts: &mut Interrupts) {
+        self.stat_select = data & 0x78;
+        self.update_stat(interrupts);
+    }
+
+    pub fn write_lyc(&mut self, data: u8, interrupts: &mut Interrupts) {
+        self.lyc = data;
+        self.update_stat(interrupts);
+    }
+
+    //The STAT interrupt is only requested when the combined line goes from low to high,
+    //so a condition that starts while another selected one still holds the line high is lost
+    fn update_stat(&mut self, interrupts: &mut Interrupts) {
+        let sources = [
+            (0x08, self.render_mode == 0),
+            (0x10, self.render_mode == 1),
+            (0x20, self.render_mode == 2),
+            (0x40, self.scan_row == self.lyc),
+        ];
+        let line = self.lcd_control.display && sources.iter().any(|(bit, active)| self.stat_select & bit > 0 && *active);
+
+        if line && !self.stat_line {
+            interrupts.request(Interrupt::LcdStat);
+        }
+        self.stat_line = line;
+    }
+
     //Fix this so that wrapping works correctly
     pub fn render_scan(&mut self) {
-        if self.lcd_control.display {
+        if !self.lcd_control.display {
+            return
+        }
+
+        //Color index of each background pixel on the line, sprites need these for priority
+        let mut background = [0u8; 160];
+        if self.lcd_control.background {
 
             let mut map_offset: u16;
 
@@ -224,32 +297,14 @@ impl Vram {
             let mut tile_pixel_x = self.scroll_x & 0x07;
 
             //Obtain index of next tile
-            let mut tile_number: u16 = self.vram[(map_offset+line_offset as u16) as usize] as u16;
-
-            //If second tile map is being used, indices are signed
-            //Tile set is 384 Tiles
-            if (self.lcd_control.bg_set) && (tile_number < 128) {
-                tile_number += 256;
-            }
+            let mut tile_number = self.tile_index(self.vram[(map_offset+line_offset as u16) as usize]);
 
             //Read tile from correct tile map
-            let mut tile = self.tile_set[tile_number as usize];
-
-            let mut pixel_buffer_offset: u32 = self.scan_row as u32 * 160 * 3;
+            let mut tile = self.tile_set[tile_number];
 
-            for _i in 0..160 {
+            for pixel in background.iter_mut() {
 
-                let color: u8 = match tile[tile_pixel_y as usize][tile_pixel_x as usize] {
-                    PixelColor::Darkest => 0x00,
-                    PixelColor::Dark => 0x4D,
-                    PixelColor::Light => 0xB3,
-                    PixelColor::Lightest => 0xFF,
-                };
-
-                self.pixel_buffer[pixel_buffer_offset as usize] = color;
-                self.pixel_buffer[(pixel_buffer_offset+1) as usize] = color;
-                self.pixel_buffer[(pixel_buffer_offset+2) as usize] = color;
-                pixel_buffer_offset += 3;
+                *pixel = tile[tile_pixel_y as usize][tile_pixel_x as usize];
 
                 tile_pixel_x += 1;
 
@@ -266,13 +321,137 @@ impl Vram {
                     }
 
                     //Get new tile
-                    tile_number = self.vram[(map_offset+line_offset as u16) as usize] as u16;
-                    if (self.lcd_control.bg_set) && tile_number < 128 {
-                        tile_number += 256;
-                    }
+                    tile_number = self.tile_index(self.vram[(map_offset+line_offset as u16) as usize]);
+                    tile = self.tile_set[tile_number];
+                }
+            }
+        }
+
+        //The window is checked against WY at the start of every line, whatever LCDC says,
+        //once they've matched it stays on for the rest of the frame, even if WY changes
+        if self.scan_row == self.window_y {
+            self.window_triggered = true;
+        }
 
-                    tile = self.tile_set[tile_number as usize];
+        //WX is the window's left edge + 7, anything past 166 is off screen
+        //With the background off the window is off too
+        if self.lcd_control.background && self.lcd_control.window && self.window_triggered && self.window_x < 167 {
+            self.render_window(&mut background);
+        }
+
+        let mut line = [PixelColor::Lightest; 160];
+        for (pixel, index) in line.iter_mut().zip(background.iter()) {
+            *pixel = PixelColor::from_palette(self.background_palette, *index);
+        }
+
+        if self.lcd_control.sprites {
+            self.render_sprites(&background, &mut line);
+        }
+
+        let mut pixel_buffer_offset = self.scan_row as usize * 160 * 3;
+        for pixel in line.iter() {
+            let color: u8 = match pixel {
+                PixelColor::Darkest => 0x00,
+                PixelColor::Dark => 0x4D,
+                PixelColor::Light => 0xB3,
+                PixelColor::Lightest => 0xFF,
+            };
+
+            self.pixel_buffer[pixel_buffer_offset] = color;
+            self.pixel_buffer[pixel_buffer_offset + 1] = color;
+            self.pixel_buffer[pixel_buffer_offset + 2] = color;
+            pixel_buffer_offset += 3;
+        }
+    }
+
+    //Window covers the background from WX-7 to the end of the line
+    //It has its own line counter that only moves on lines where it was drawn
+    fn render_window(&mut self, background: &mut [u8; 160]) {
+        let mut map_offset: u16 = if self.lcd_control.window_map {0x1C00} else {0x1800};
+        map_offset += 32 * (self.window_line as u16 >> 3);
+        let tile_pixel_y = (self.window_line & 0x07) as usize;
+        let start = self.window_x as i16 - 7;
+
+        for x in start.max(0)..160 {
+            let window_pixel_x = (x - start) as u16;
+            let tile_number = self.tile_index(self.vram[(map_offset + (window_pixel_x >> 3)) as usize]);
+            background[x as usize] = self.tile_set[tile_number][tile_pixel_y][(window_pixel_x & 0x07) as usize];
+        }
+
+        self.window_line = self.window_line.wrapping_add(1);
+    }
+
+    //Background and window tile numbers are unsigned from $8000 when LCDC bit 4 is set,
+    //otherwise signed from $9000, so 0-127 are the last 128 tiles of the set
+    fn tile_index(&self, tile_number: u8) -> usize {
+        if !self.lcd_control.bg_set && tile_number < 128 {
+            tile_number as usize + 256
+        }
+        else {
+            tile_number as usize
+        }
+    }
+
+    //Sprites on the current line in drawing priority order
+    fn line_sprites(&self) -> Vec<Sprite> {
+        let height = if self.lcd_control.sprite_size {16} else {8};
+        let row = self.scan_row as i16;
+
+        //Selection only looks at Y and stops after 10, even if some are off screen horizontally
+        let mut sprites: Vec<Sprite> = self.oam.chunks(4)
+            .map(|entry| Sprite {
+                y: entry[0] as i16 - 16,
+                x: entry[1] as i16 - 8,
+                tile: entry[2],
+                flags: entry[3],
+            })
+            .filter(|sprite| row >= sprite.y && row < sprite.y + height)
+            .take(SPRITES_PER_LINE)
+            .collect();
+
+        //Lower X wins, OAM order breaks ties since the sort is stable
+        sprites.sort_by_key(|sprite| sprite.x);
+        sprites
+    }
+
+    fn render_sprites(&self, background: &[u8; 160], line: &mut [PixelColor; 160]) {
+        let height = if self.lcd_control.sprite_size {16} else {8};
+        //The first sprite with a visible pixel owns it, even when it ends up hidden behind the background
+        let mut drawn = [false; 160];
+
+        for sprite in self.line_sprites() {
+            let mut row = self.scan_row as i16 - sprite.y;
+            if sprite.flags & 0x40 > 0 {
+                row = height - 1 - row;
+            }
+
+            //8x16 sprites ignore bit 0 of the tile number
+            let mut tile_number = sprite.tile as usize;
+            if height == 16 {
+                tile_number = (tile_number & 0xFE) + (row as usize >> 3);
+            }
+            let tile_row = self.tile_set[tile_number][(row & 0x07) as usize];
+
+            let palette = if sprite.flags & 0x10 > 0 {self.sprite_palette_1} else {self.sprite_palette_0};
+
+            for column in 0..8 {
+                let x = sprite.x + column;
+                if !(0..160).contains(&x) || drawn[x as usize] {
+                    continue
                 }
+
+                let tile_column = if sprite.flags & 0x20 > 0 {7 - column} else {column};
+                let index = tile_row[tile_column as usize];
+                //Color 0 is transparent
+                if index == 0 {
+                    continue
+                }
+                drawn[x as usize] = true;
+
+                if sprite.flags & 0x80 > 0 && background[x as usize] != 0 {
+                    continue
+                }
+                line[x as usize] = PixelColor::from_palette(palette, index);
             }
         }
     }
@@ -297,6 +476,16 @@ impl Vram {
         }
     }
 
+    //Read Byte in OAM
+    pub fn read_oam(&self, address: u16) -> u8 {
+        self.oam[(address - OAM_START) as usize]
+    }
+
+    //Write Byte in OAM
+    pub fn write_oam(&mut self, address: u16, data: u8) {
+        self.oam[(address - OAM_START) as usize] = data;
+    }
+
     //Obtain corresponsing number of tile 
     pub fn tile_number(mut address: u16) -> u16 {
         if address >= 0x8000 {
@@ -335,17 +524,11 @@ impl Vram {
             first = self.read_byte(address - 1);
             second = data;
         }
-        let mut pixel_mask = 1u8 << 7;
+        //First byte holds bit 0 of each pixel's color index, second byte holds bit 1
         for pixel in 0..8 {
-            let first_mask = first & pixel_mask;
-            let second_mask = second & pixel_mask;
-            match (first_mask==0, second_mask==0) {
-                (true, true) => self.tile_set[tile_number as usize][tile_row as usize][pixel as usize] = PixelColor::Lightest,
-                (true, false) => self.tile_set[tile_number as usize][tile_row as usize][pixel as usize] = PixelColor::Light,
-                (false, true) => self.tile_set[tile_number as usize][tile_row as usize][pixel as usize] = PixelColor::Dark,
-                (false, false) => self.tile_set[tile_number as usize][tile_row as usize][pixel as usize] = PixelColor::Darkest,
-            }
-            pixel_mask >>= 1;
+            let shift = 7 - pixel;
+            let index = ((first >> shift) & 1) | (((second >> shift) & 1) << 1);
+            self.tile_set[tile_number][tile_row][pixel] = index;
         }
     }
 
@@ -378,9 +561,340 @@ mod tests {
         vram.write_byte(0x8000, 0xFF);
         vram.write_byte(0x801E, 0xFF);
         vram.write_byte(0x801F, 0xFF);
-        assert_eq!(vram.tile_set[0][0][0], PixelColor::Dark);
-        assert_eq!(vram.tile_set[1][7], [PixelColor::Darkest, PixelColor::Darkest, PixelColor::Darkest, PixelColor::Darkest,
-            PixelColor::Darkest,PixelColor::Darkest,PixelColor::Darkest,PixelColor::Darkest]);     
+        assert_eq!(vram.tile_set[0][0][0], 1);
+        assert_eq!(vram.tile_set[1][7], [3; 8]);
+        vram.write_byte(0x8003, 0x80);
+        assert_eq!(vram.tile_set[0][1][0], 2);
+    }
+
+    //Clock the lcd like Memory::tick does, returns the interrupts requested
+    fn run(vram: &mut Vram, cycles: u32) -> u8 {
+        let mut interrupts = Interrupts::new();
+        for _ in 0..cycles {
+            vram.render_mode_cycles += 1;
+            vram.step(&mut interrupts);
+        }
+        interrupts.flags
+    }
+
+    #[test]
+    fn test_stat_mode() {
+        let mut vram = Vram::new();
+        assert_eq!(vram.read_stat(), 0x84);
+        vram.lcd_control.display = true;
+        vram.lyc = 1;
+        run(&mut vram, 51);
+        assert_eq!(vram.read_stat(), 0x86);
+        run(&mut vram, 20);
+        assert_eq!(vram.read_stat(), 0x87);
+        run(&mut vram, 43);
+        assert_eq!(vram.read_stat(), 0x84);
+
+        //Mode bits read 0 with the display off
+        run(&mut vram, 51);
+        vram.lcd_control.display = false;
+        assert_eq!(vram.read_stat(), 0x80);
+    }
+
+    #[test]
+    fn test_stat_interrupt_sources() {
+        let mut vram = Vram::new();
+        vram.lcd_control.display = true;
+        let mut interrupts = Interrupts::new();
+        vram.write_stat(0x20, &mut interrupts);
+        assert_eq!(interrupts.flags, 0);
+        assert_eq!(run(&mut vram, 51), Interrupt::LcdStat.bit());
+
+        //V-Blank select requests at line 144 alongside the V-Blank interrupt
+        vram.write_stat(0x10, &mut interrupts);
+        assert_eq!(run(&mut vram, 114 * 142 + 20 + 43 + 51), Interrupt::VBlank.bit() | Interrupt::LcdStat.bit());
+
+        //Writing LYC to the current line fires straight away
+        vram.write_stat(0x40, &mut interrupts);
+        interrupts.flags = 0;
+        vram.write_lyc(vram.scan_row, &mut interrupts);
+        assert_eq!(interrupts.flags, Interrupt::LcdStat.bit());
+    }
+
+    #[test]
+    fn test_stat_blocking() {
+        let mut vram = Vram::new();
+        vram.lcd_control.display = true;
+        let mut interrupts = Interrupts::new();
+        vram.write_lyc(1, &mut interrupts);
+        vram.write_stat(0x48, &mut interrupts);
+        assert_eq!(interrupts.flags, Interrupt::LcdStat.bit());
+
+        //Line 1 matches LYC as H-Blank ends, the line never drops so nothing new is requested
+        assert_eq!(run(&mut vram, 51 + 20 + 43 + 51), 0);
+
+        //On line 2 the line drops during mode 2 and rises again at H-Blank
+        assert_eq!(run(&mut vram, 20 + 42), 0);
+        assert_eq!(run(&mut vram, 1), Interrupt::LcdStat.bit());
+    }
+
+    #[test]
+    fn test_background_palette() {
+        let mut vram = Vram::new();
+        vram.lcd_control.display = true;
+        vram.lcd_control.background = true;
+        vram.lcd_control.bg_set = true;
+        //Tile 0 row 0 is colors 0, 1, 2, 3
+        vram.write_byte(0x8000, 0b0101_0000);
+        vram.write_byte(0x8001, 0b0011_0000);
+        vram.background_palette = 0b1110_0100;
+        vram.render_scan();
+        assert_eq!(vram.pixel_buffer[0..12], [0xFF, 0xFF, 0xFF, 0xB3, 0xB3, 0xB3, 0x4D, 0x4D, 0x4D, 0x00, 0x00, 0x00]);
+
+        //Fading only needs a palette write, the tiles stay the same
+        vram.background_palette = 0b0001_1011;
+        vram.render_scan();
+        assert_eq!(vram.pixel_buffer[0..12], [0x00, 0x00, 0x00, 0x4D, 0x4D, 0x4D, 0xB3, 0xB3, 0xB3, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(vram.tile_set[0][0][0..4], [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_background_signed_tile_set() {
+        let mut vram = Vram::new();
+        vram.lcd_control.display = true;
+        vram.lcd_control.background = true;
+        vram.lcd_control.bg_set = false;
+        vram.background_palette = 0b1110_0100;
+        //Tile 0 comes from 9000 rather than 8000, tile 128 from 8800
+        vram.write_byte(0x8000, 0xFF);
+        vram.write_byte(0x9000, 0xFF);
+        vram.write_byte(0x9001, 0xFF);
+        vram.write_byte(0x8800, 0xFF);
+        vram.write_byte(0x9801, 0x80);
+        vram.render_scan();
+        assert_eq!(vram.pixel_buffer[0..3], [0x00, 0x00, 0x00]);
+        assert_eq!(vram.pixel_buffer[8 * 3..8 * 3 + 3], [0xB3, 0xB3, 0xB3]);
+    }
+
+    //Display and sprites on, tile 1 is color 3 everywhere and tile 2 is color 0 except its top left pixel
+    fn sprite_vram() -> Vram {
+        let mut vram = Vram::new();
+        vram.lcd_control.display = true;
+        vram.lcd_control.sprites = true;
+        vram.background_palette = 0b1110_0100;
+        vram.sprite_palette_0 = 0b1110_0100;
+        vram.sprite_palette_1 = 0b0001_1011;
+        for address in 0x8010..0x8020 {
+            vram.write_byte(address, 0xFF);
+        }
+        vram.write_byte(0x8020, 0x80);
+        vram.write_byte(0x8021, 0x80);
+        vram
+    }
+
+    fn set_sprite(vram: &mut Vram, index: u16, y: u8, x: u8, tile: u8, flags: u8) {
+        for (offset, data) in [y, x, tile, flags].iter().enumerate() {
+            vram.write_oam(OAM_START + index * 4 + offset as u16, *data);
+        }
+    }
+
+    fn pixel(vram: &Vram, x: usize) -> u8 {
+        vram.pixel_buffer[(vram.scan_row as usize * 160 + x) * 3]
+    }
+
+    #[test]
+    fn test_sprite_palettes() {
+        let mut vram = sprite_vram();
+        set_sprite(&mut vram, 0, 16, 8, 1, 0x00);
+        set_sprite(&mut vram, 1, 16, 16, 1, 0x10);
+        vram.render_scan();
+        assert_eq!(pixel(&vram, 0), 0x00);
+        assert_eq!(pixel(&vram, 8), 0xFF);
+        assert_eq!(pixel(&vram, 16), 0xFF);
+
+        //Sprites are off
+        vram.lcd_control.sprites = false;
+        vram.render_scan();
+        assert_eq!(pixel(&vram, 0), 0xFF);
+    }
+
+    #[test]
+    fn test_sprite_flip() {
+        let mut vram = sprite_vram();
+        set_sprite(&mut vram, 0, 16, 8, 2, 0x00);
+        set_sprite(&mut vram, 1, 16, 16, 2, 0x20);
+        //Last row of sprites starting 7 lines above the screen, flipped it's the top row of the tile
+        set_sprite(&mut vram, 2, 9, 24, 2, 0x40);
+        set_sprite(&mut vram, 3, 9, 32, 2, 0x00);
+        vram.render_scan();
+        assert_eq!(pixel(&vram, 0), 0x00);
+        assert_eq!(pixel(&vram, 1), 0xFF);
+        assert_eq!(pixel(&vram, 8), 0xFF);
+        assert_eq!(pixel(&vram, 15), 0x00);
+        assert_eq!(pixel(&vram, 16), 0x00);
+        assert_eq!(pixel(&vram, 24), 0xFF);
+    }
+
+    #[test]
+    fn test_tall_sprites() {
+        let mut vram = sprite_vram();
+        vram.lcd_control.sprite_size = true;
+        //Bit 0 of the tile is ignored, tile 2 is the top half and tile 3 the bottom
+        set_sprite(&mut vram, 0, 16, 8, 3, 0x00);
+        set_sprite(&mut vram, 1, 16, 16, 3, 0x40);
+        vram.render_scan();
+        assert_eq!(pixel(&vram, 0), 0x00);
+        assert_eq!(pixel(&vram, 8), 0xFF);
+
+        //Flipped vertically the top row of tile 2 ends up on the last line
+        vram.scan_row = 15;
+        vram.render_scan();
+        assert_eq!(pixel(&vram, 0), 0xFF);
+        assert_eq!(pixel(&vram, 8), 0x00);
+    }
+
+    #[test]
+    fn test_sprite_priority() {
+        let mut vram = sprite_vram();
+        //Lower X wins over OAM order, the OBP1 sprite at X 4 is drawn over the one at X 8
+        set_sprite(&mut vram, 0, 16, 12, 1, 0x00);
+        set_sprite(&mut vram, 1, 16, 8, 1, 0x10);
+        vram.render_scan();
+        assert_eq!(pixel(&vram, 4), 0xFF);
+        assert_eq!(pixel(&vram, 8), 0x00);
+
+        //Same X, lower OAM index wins
+        set_sprite(&mut vram, 1, 16, 12, 1, 0x10);
+        vram.render_scan();
+        assert_eq!(pixel(&vram, 4), 0x00);
+    }
+
+    #[test]
+    fn test_sprite_line_limit() {
+        let mut vram = sprite_vram();
+        //The first 10 in OAM are picked even if they're off screen
+        set_sprite(&mut vram, 0, 16, 0, 1, 0x00);
+        for index in 1..12 {
+            set_sprite(&mut vram, index, 16, 8 * index as u8, 1, 0x00);
+        }
+        vram.render_scan();
+        assert_eq!(pixel(&vram, 8 * 9 - 8), 0x00);
+        assert_eq!(pixel(&vram, 8 * 10 - 8), 0xFF);
+    }
+
+    //Background is blank tile 0, the window map is tile 1 which is color 3 everywhere
+    //and tile 3 which only has its second row set
+    fn window_vram() -> Vram {
+        let mut vram = sprite_vram();
+        vram.lcd_control.background = true;
+        vram.lcd_control.bg_set = true;
+        vram.lcd_control.window = true;
+        vram.lcd_control.window_map = true;
+        vram.write_byte(0x8032, 0xFF);
+        vram.write_byte(0x8033, 0xFF);
+        for address in 0x9C00..0xA000 {
+            vram.write_byte(address, 1);
+        }
+        vram
+    }
+
+    #[test]
+    fn test_window_position() {
+        let mut vram = window_vram();
+        vram.window_x = 7 + 80;
+        vram.render_scan();
+        assert_eq!(pixel(&vram, 79), 0xFF);
+        assert_eq!(pixel(&vram, 80), 0x00);
+        assert_eq!(pixel(&vram, 159), 0x00);
+
+        //Past 166 it's hidden
+        vram.scan_row = 1;
+        vram.window_x = 167;
+        vram.render_scan();
+        assert_eq!(pixel(&vram, 159), 0xFF);
+
+        //Turned off in LCDC
+        vram.window_x = 7;
+        vram.lcd_control.window = false;
+        vram.render_scan();
+        assert_eq!(pixel(&vram, 0), 0xFF);
+    }
+
+    #[test]
+    fn test_window_trigger_with_background_off() {
+        //WY matches on a line where the background and window are off, the window still shows once they're back on
+        let mut vram = window_vram();
+        vram.window_x = 7;
+        vram.window_y = 3;
+        vram.scan_row = 3;
+        vram.lcd_control.background = false;
+        vram.render_scan();
+        assert_eq!(pixel(&vram, 0), 0xFF);
+
+        vram.lcd_control.background = true;
+        vram.scan_row = 4;
+        vram.render_scan();
+        assert_eq!(pixel(&vram, 0), 0x00);
+    }
+
+    #[test]
+    fn test_window_line_counter() {
+        let mut vram = window_vram();
+        for address in 0x9C00..0xA000 {
+            vram.write_byte(address, 3);
+        }
+        vram.window_x = 7;
+        vram.render_scan();
+        assert_eq!(pixel(&vram, 0), 0xFF);
+
+        //Skipping a line doesn't move the window down, line 2 shows its second row
+        vram.scan_row = 1;
+        vram.lcd_control.window = false;
+        vram.render_scan();
+        vram.scan_row = 2;
+        vram.lcd_control.window = true;
+        vram.render_scan();
+        assert_eq!(pixel(&vram, 0), 0x00);
+    }
+
+    #[test]
+    fn test_window_y_change() {
+        //Moving WY down after it matched doesn't hide the window
+        let mut vram = window_vram();
+        vram.window_x = 7;
+        vram.render_scan();
+        vram.window_y = 100;
+        vram.scan_row = 1;
+        vram.render_scan();
+        assert_eq!(pixel(&vram, 0), 0x00);
+
+        //Moving WY up to a line that's already been drawn doesn't show it
+        let mut vram = window_vram();
+        vram.window_x = 7;
+        vram.window_y = 20;
+        vram.scan_row = 10;
+        vram.render_scan();
+        vram.window_y = 5;
+        vram.scan_row = 11;
+        vram.render_scan();
+        assert_eq!(pixel(&vram, 0), 0xFF);
+    }
+
+    #[test]
+    fn test_sprite_behind_background() {
+        let mut vram = sprite_vram();
+        vram.lcd_control.background = true;
+        vram.lcd_control.bg_set = true;
+        //Background map 0 uses tile 2, only the first pixel of every tile is color 3
+        for address in 0x9800..0x9C00 {
+            vram.write_byte(address, 2);
+        }
+        set_sprite(&mut vram, 0, 16, 8, 1, 0x80);
+        vram.render_scan();
+        assert_eq!(pixel(&vram, 0), 0x00);
+        assert_eq!(pixel(&vram, 1), 0x00);
+
+        //A hidden pixel still blocks sprites behind it in priority
+        set_sprite(&mut vram, 1, 16, 8, 1, 0x10);
+        vram.sprite_palette_0 = 0;
+        vram.render_scan();
+        assert_eq!(pixel(&vram, 0), 0x00);
+        assert_eq!(pixel(&vram, 1), 0xFF);
     }
 
 }
\ No newline at end of file
@@ -0,0 +1,435 @@
+use std::fmt;
+
+//Cartridge header lives at 0100-014F of every rom
+//0100-0103 Entry point
+//0104-0133 Nintendo logo
+//0134-0143 Title (0134-013E on CGB carts, followed by manufacturer code and CGB flag)
+//0144-0145 New licensee code
+//0146      SGB flag
+//0147      Cartridge type
+//0148      ROM size
+//0149      RAM size
+//014A      Destination code
+//014B      Old licensee code
+//014C      Mask ROM version number
+//014D      Header checksum
+//014E-014F Global checksum
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum CgbSupport {
+    None,       //DMG only
+    Compatible, //0x80 - works on DMG and CGB
+    Only,       //0xC0 - CGB only
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Mapper {
+    RomOnly,
+    Mbc1,
+    Mbc2,
+    Mmm01,
+    Mbc3,
+    Mbc5,
+    Mbc6,
+    Mbc7,
+    PocketCamera,
+    BandaiTama5,
+    HuC3,
+    HuC1,
+    Unknown,
+}
+
+//Decoded form of the cartridge type byte at 0x147
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct CartridgeType {
+    pub code: u8,
+    pub mapper: Mapper,
+    pub ram: bool,
+    pub battery: bool,
+    pub timer: bool,
+    pub rumble: bool,
+    pub sensor: bool,
+}
+
+impl CartridgeType {
+    pub fn from_code(code: u8) -> CartridgeType {
+        //(mapper, ram, battery, timer, rumble, sensor)
+        let (mapper, ram, battery, timer, rumble, sensor) = match code {
+            0x00 => (Mapper::RomOnly, false, false, false, false, false),
+            0x01 => (Mapper::Mbc1, false, false, false, false, false),
+            0x02 => (Mapper::Mbc1, true, false, false, false, false),
+            0x03 => (Mapper::Mbc1, true, true, false, false, false),
+            0x05 => (Mapper::Mbc2, false, false, false, false, false),
+            0x06 => (Mapper::Mbc2, false, true, false, false, false),
+            0x08 => (Mapper::RomOnly, true, false, false, false, false),
+            0x09 => (Mapper::RomOnly, true, true, false, false, false),
+            0x0B => (Mapper::Mmm01, false, false, false, false, false),
+            0x0C => (Mapper::Mmm01, true, false, false, false, false),
+            0x0D => (Mapper::Mmm01, true, true, false, false, false),
+            0x0F => (Mapper::Mbc3, false, true, true, false, false),
+            0x10 => (Mapper::Mbc3, true, true, true, false, false),
+            0x11 => (Mapper::Mbc3, false, false, false, false, false),
+            0x12 => (Mapper::Mbc3, true, false, false, false, false),
+            0x13 => (Mapper::Mbc3, true, true, false, false, false),
+            0x19 => (Mapper::Mbc5, false, false, false, false, false),
+            0x1A => (Mapper::Mbc5, true, false, false, false, false),
+            0x1B => (Mapper::Mbc5, true, true, false, false, false),
+            0x1C => (Mapper::Mbc5, false, false, false, true, false),
+            0x1D => (Mapper::Mbc5, true, false, false, true, false),
+            0x1E => (Mapper::Mbc5, true, true, false, true, false),
+            0x20 => (Mapper::Mbc6, true, true, false, false, false),
+            0x22 => (Mapper::Mbc7, true, true, false, true, true),
+            0xFC => (Mapper::PocketCamera, true, true, false, false, false),
+            0xFD => (Mapper::BandaiTama5, false, false, false, false, false),
+            0xFE => (Mapper::HuC3, true, true, true, false, false),
+            0xFF => (Mapper::HuC1, true, true, false, false, false),
+            _ => (Mapper::Unknown, false, false, false, false, false),
+        };
+        CartridgeType { code, mapper, ram, battery, timer, rumble, sensor }
+    }
+}
+
+impl fmt::Display for CartridgeType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self.mapper {
+            Mapper::RomOnly => if self.ram {"ROM"} else {"ROM ONLY"},
+            Mapper::Mbc1 => "MBC1",
+            Mapper::Mbc2 => "MBC2",
+            Mapper::Mmm01 => "MMM01",
+            Mapper::Mbc3 => "MBC3",
+            Mapper::Mbc5 => "MBC5",
+            Mapper::Mbc6 => "MBC6",
+            Mapper::Mbc7 => "MBC7",
+            Mapper::PocketCamera => "POCKET CAMERA",
+            Mapper::BandaiTama5 => "BANDAI TAMA5",
+            Mapper::HuC3 => "HuC3",
+            Mapper::HuC1 => "HuC1",
+            Mapper::Unknown => return write!(f, "UNKNOWN ({:#04X})", self.code),
+        };
+        write!(f, "{}", name)?;
+        //Same ordering as the official names, e.g. MBC7+SENSOR+RUMBLE+RAM+BATTERY
+        if self.timer {write!(f, "+TIMER")?;}
+        if self.sensor {write!(f, "+SENSOR")?;}
+        if self.rumble {write!(f, "+RUMBLE")?;}
+        if self.ram {write!(f, "+RAM")?;}
+        if self.battery {write!(f, "+BATTERY")?;}
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Licensee {
+    Old(u8),     //0x14B
+    New(String), //0x144-0x145, used when 0x14B is 0x33
+}
+
+#[derive(Debug, Clone)]
+pub struct CartridgeHeader {
+    pub title: String,
+    pub manufacturer: Option<String>,
+    pub cgb: CgbSupport,
+    pub sgb: bool,
+    pub licensee: Licensee,
+    pub cartridge_type: CartridgeType,
+    pub rom_size_code: u8,
+    pub ram_size_code: u8,
+    pub japanese: bool,
+    pub version: u8,
+    pub header_checksum: u8,
+    pub global_checksum: u16,
+    pub computed_header_checksum: u8,
+    pub computed_global_checksum: u16,
+}
+
+impl CartridgeHeader {
+
+    pub fn parse(rom: &[u8]) -> CartridgeHeader {
+        //Anything past the end of a short rom reads as zero
+        let byte = |address: usize| -> u8 { rom.get(address).copied().unwrap_or(0) };
+
+        let cgb = match byte(0x143) {
+            0xC0 => CgbSupport::Only,
+            0x80 => CgbSupport::Compatible,
+            _ => CgbSupport::None,
+        };
+
+        let manufacturer_code: Vec<u8> = (0x13F..0x143).map(byte).collect();
+        let manufacturer = if cgb != CgbSupport::None && manufacturer_code.iter().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()) {
+            Some(manufacturer_code.iter().map(|c| *c as char).collect())
+        }
+        else {
+            None
+        };
+
+        //CGB carts shortened the title to make room for the CGB flag, and later the manufacturer code
+        let title_end = match (cgb, &manufacturer) {
+            (CgbSupport::None, _) => 0x144,
+            (_, Some(_)) => 0x13F,
+            (_, None) => 0x143,
+        };
+        let title: String = (0x134..title_end)
+            .map(byte)
+            .take_while(|c| *c != 0)
+            .map(|c| c as char)
+            .collect();
+
+        let licensee = if byte(0x14B) == 0x33 {
+            Licensee::New([byte(0x144), byte(0x145)].iter().map(|c| *c as char).collect())
+        }
+        else {
+            Licensee::Old(byte(0x14B))
+        };
+
+        CartridgeHeader {
+            title,
+            manufacturer,
+            cgb,
+            sgb: byte(0x146) == 0x03,
+            licensee,
+            cartridge_type: CartridgeType::from_code(byte(0x147)),
+            rom_size_code: byte(0x148),
+            ram_size_code: byte(0x149),
+            japanese: byte(0x14A) == 0x00,
+            version: byte(0x14C),
+            header_checksum: byte(0x14D),
+            global_checksum: ((byte(0x14E) as u16) << 8) | byte(0x14F) as u16,
+            computed_header_checksum: CartridgeHeader::compute_header_checksum(rom),
+            computed_global_checksum: CartridgeHeader::compute_global_checksum(rom),
+        }
+    }
+
+    //x = x - byte - 1 over 0134-014C, checked by the boot rom
+    pub fn compute_header_checksum(rom: &[u8]) -> u8 {
+        let mut checksum: u8 = 0;
+        for address in 0x134..=0x14C {
+            checksum = checksum.wrapping_sub(rom.get(address).copied().unwrap_or(0)).wrapping_sub(1);
+        }
+        checksum
+    }
+
+    //Sum of every byte in the rom except the two checksum bytes, not checked by hardware
+    pub fn compute_global_checksum(rom: &[u8]) -> u16 {
+        let mut checksum: u16 = 0;
+        for (address, data) in rom.iter().enumerate() {
+            if address != 0x14E && address != 0x14F {
+                checksum = checksum.wrapping_add(*data as u16);
+            }
+        }
+        checksum
+    }
+
+    pub fn header_checksum_valid(&self) -> bool {
+        self.header_checksum == self.computed_header_checksum
+    }
+
+    pub fn global_checksum_valid(&self) -> bool {
+        self.global_checksum == self.computed_global_checksum
+    }
+
+    //ROM size in bytes, None for unknown codes
+    pub fn rom_size(&self) -> Option<usize> {
+        match self.rom_size_code {
+            0x00..=0x08 => Some(0x8000 << self.rom_size_code),
+            0x52 => Some(72 * 0x4000),
+            0x53 => Some(80 * 0x4000),
+            0x54 => Some(96 * 0x4000),
+            _ => None,
+        }
+    }
+
+    //External RAM size in bytes, None for unknown codes
+    pub fn ram_size(&self) -> Option<usize> {
+        match self.ram_size_code {
+            0x00 => Some(0),
+            0x01 => Some(0x800),
+            0x02 => Some(0x2000),
+            0x03 => Some(0x8000),
+            0x04 => Some(0x20000),
+            0x05 => Some(0x10000),
+            _ => None,
+        }
+    }
+
+    pub fn licensee_name(&self) -> Option<&'static str> {
+        match &self.licensee {
+            Licensee::Old(0x01) => Some("Nintendo"),
+            Licensee::Old(_) => None,
+            Licensee::New(code) => new_licensee_name(code),
+        }
+    }
+}
+
+fn new_licensee_name(code: &str) -> Option<&'static str> {
+    let name = match code {
+        "00" => "None",
+        "01" => "Nintendo R&D1",
+        "08" => "Capcom",
+        "13" => "Electronic Arts",
+        "18" => "Hudson Soft",
+        "19" => "b-ai",
+        "20" => "kss",
+        "22" => "pow",
+        "24" => "PCM Complete",
+        "25" => "san-x",
+        "28" => "Kemco Japan",
+        "29" => "seta",
+        "30" => "Viacom",
+        "31" => "Nintendo",
+        "32" => "Bandai",
+        "33" => "Ocean/Acclaim",
+        "34" => "Konami",
+        "35" => "Hector",
+        "37" => "Taito",
+        "38" => "Hudson",
+        "39" => "Banpresto",
+        "41" => "Ubi Soft",
+        "42" => "Atlus",
+        "44" => "Malibu",
+        "46" => "angel",
+        "47" => "Bullet-Proof",
+        "49" => "irem",
+        "50" => "Absolute",
+        "51" => "Acclaim",
+        "52" => "Activision",
+        "53" => "American sammy",
+        "54" => "Konami",
+        "55" => "Hi tech entertainment",
+        "56" => "LJN",
+        "57" => "Matchbox",
+        "58" => "Mattel",
+        "59" => "Milton Bradley",
+        "60" => "Titus",
+        "61" => "Virgin",
+        "64" => "LucasArts",
+        "67" => "Ocean",
+        "69" => "Electronic Arts",
+        "70" => "Infogrames",
+        "71" => "Interplay",
+        "72" => "Broderbund",
+        "73" => "sculptured",
+        "75" => "sci",
+        "78" => "THQ",
+        "79" => "Accolade",
+        "80" => "misawa",
+        "83" => "lozc",
+        "86" => "Tokuma Shoten Intermedia",
+        "87" => "Tsukuda Original",
+        "91" => "Chunsoft",
+        "92" => "Video system",
+        "93" => "Ocean/Acclaim",
+        "95" => "Varie",
+        "96" => "Yonezawa/s'pal",
+        "97" => "Kaneko",
+        "99" => "Pack in soft",
+        "A4" => "Konami (Yu-Gi-Oh!)",
+        _ => return None,
+    };
+    Some(name)
+}
+
+impl fmt::Display for CartridgeHeader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Title:           {}", self.title)?;
+        if let Some(manufacturer) = &self.manufacturer {
+            writeln!(f, "Manufacturer:    {}", manufacturer)?;
+        }
+        let cgb = match self.cgb {
+            CgbSupport::None => "No",
+            CgbSupport::Compatible => "Supported",
+            CgbSupport::Only => "Required",
+        };
+        writeln!(f, "CGB:             {}", cgb)?;
+        writeln!(f, "SGB:             {}", if self.sgb {"Supported"} else {"No"})?;
+        let code = match &self.licensee {
+            Licensee::Old(code) => format!("{:02X}", code),
+            Licensee::New(code) => code.clone(),
+        };
+        writeln!(f, "Licensee:        {} ({})", code, self.licensee_name().unwrap_or("Unknown"))?;
+        writeln!(f, "Cartridge Type:  {:#04X} ({})", self.cartridge_type.code, self.cartridge_type)?;
+        match self.rom_size() {
+            Some(size) => writeln!(f, "ROM Size:        {} KiB", size / 1024)?,
+            None => writeln!(f, "ROM Size:        Unknown ({:#04X})", self.rom_size_code)?,
+        }
+        match self.ram_size() {
+            Some(size) => writeln!(f, "RAM Size:        {} KiB", size / 1024)?,
+            None => writeln!(f, "RAM Size:        Unknown ({:#04X})", self.ram_size_code)?,
+        }
+        writeln!(f, "Destination:     {}", if self.japanese {"Japan"} else {"Overseas"})?;
+        writeln!(f, "Version:         {}", self.version)?;
+        write!(f, "Header Checksum: {:#04X}", self.header_checksum)?;
+        if self.header_checksum_valid() {
+            writeln!(f, " (OK)")?;
+        }
+        else {
+            writeln!(f, " (MISMATCH, computed {:#04X})", self.computed_header_checksum)?;
+        }
+        write!(f, "Global Checksum: {:#06X}", self.global_checksum)?;
+        if self.global_checksum_valid() {
+            write!(f, " (OK)")
+        }
+        else {
+            write!(f, " (MISMATCH, computed {:#06X})", self.computed_global_checksum)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_rom() -> Vec<u8> {
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x134..0x13B].copy_from_slice(b"POKEMON");
+        rom[0x13F..0x143].copy_from_slice(b"AAUE");
+        rom[0x143] = 0x80;
+        rom[0x144] = b'0';
+        rom[0x145] = b'1';
+        rom[0x146] = 0x03;
+        rom[0x147] = 0x10;
+        rom[0x148] = 0x06;
+        rom[0x149] = 0x03;
+        rom[0x14A] = 0x01;
+        rom[0x14B] = 0x33;
+        rom[0x14D] = CartridgeHeader::compute_header_checksum(&rom);
+        let global = CartridgeHeader::compute_global_checksum(&rom);
+        rom[0x14E] = (global >> 8) as u8;
+        rom[0x14F] = global as u8;
+        rom
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let header = CartridgeHeader::parse(&test_rom());
+        assert_eq!(header.title, "POKEMON");
+        assert_eq!(header.manufacturer, Some(String::from("AAUE")));
+        assert_eq!(header.cgb, CgbSupport::Compatible);
+        assert!(header.sgb);
+        assert_eq!(header.licensee, Licensee::New(String::from("01")));
+        assert_eq!(header.cartridge_type.mapper, Mapper::Mbc3);
+        assert!(header.cartridge_type.timer && header.cartridge_type.ram && header.cartridge_type.battery);
+        assert_eq!(header.rom_size(), Some(0x200000));
+        assert_eq!(header.ram_size(), Some(0x8000));
+        assert!(!header.japanese);
+        assert!(header.header_checksum_valid());
+        assert!(header.global_checksum_valid());
+    }
+
+    #[test]
+    fn test_checksum_mismatch() {
+        let mut rom = test_rom();
+        rom[0x14D] = rom[0x14D].wrapping_add(1);
+        rom[0x4000] = 0xAA;
+        let header = CartridgeHeader::parse(&rom);
+        assert!(!header.header_checksum_valid());
+        assert!(!header.global_checksum_valid());
+    }
+
+    #[test]
+    fn test_cartridge_type_names() {
+        assert_eq!(CartridgeType::from_code(0x00).to_string(), "ROM ONLY");
+        assert_eq!(CartridgeType::from_code(0x03).to_string(), "MBC1+RAM+BATTERY");
+        assert_eq!(CartridgeType::from_code(0x10).to_string(), "MBC3+TIMER+RAM+BATTERY");
+        assert_eq!(CartridgeType::from_code(0x22).to_string(), "MBC7+SENSOR+RUMBLE+RAM+BATTERY");
+    }
+}
@@ -0,0 +1,278 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+//Minimal still image loading for feeding emulated sensors
+//Supports netpbm (P2/P3/P5/P6) and uncompressed 8/24/32 bit BMP, everything is converted to greyscale
+
+pub const IMAGE_EXTENSIONS: [&str; 4] = ["pgm", "ppm", "pnm", "bmp"];
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(PathBuf, io::Error),
+    Unsupported(PathBuf),
+    Malformed(PathBuf),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageError::Io(path, error) => write!(f, "Could not read {}: {}", path.display(), error),
+            ImageError::Unsupported(path) => write!(f, "{} is not a PNM or uncompressed BMP image", path.display()),
+            ImageError::Malformed(path) => write!(f, "{} is not a valid image", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct GrayImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>, //Row major, 0 is black and 255 is white
+}
+
+impl GrayImage {
+    pub fn load(path: &Path) -> Result<GrayImage, ImageError> {
+        let data = fs::read(path).map_err(|error| ImageError::Io(path.to_path_buf(), error))?;
+        match data.get(0..2) {
+            Some(b"P2") | Some(b"P3") | Some(b"P5") | Some(b"P6") => {
+                GrayImage::decode_pnm(&data).ok_or_else(|| ImageError::Malformed(path.to_path_buf()))
+            }
+            Some(b"BM") => GrayImage::decode_bmp(&data).ok_or_else(|| ImageError::Malformed(path.to_path_buf())),
+            _ => Err(ImageError::Unsupported(path.to_path_buf())),
+        }
+    }
+
+    pub fn decode_pnm(data: &[u8]) -> Option<GrayImage> {
+        let magic = data.get(0..2)?;
+        let mut position = 2;
+
+        //Header is whitespace separated width, height and max value, with # comments
+        let mut fields: Vec<usize> = Vec::new();
+        while fields.len() < 3 {
+            while position < data.len() && (data[position].is_ascii_whitespace() || data[position] == b'#') {
+                if data[position] == b'#' {
+                    while position < data.len() && data[position] != b'\n' {
+                        position += 1;
+                    }
+                }
+                else {
+                    position += 1;
+                }
+            }
+            let start = position;
+            while position < data.len() && data[position].is_ascii_digit() {
+                position += 1;
+            }
+            fields.push(std::str::from_utf8(&data[start..position]).ok()?.parse().ok()?);
+        }
+        let (width, height, max_value) = (fields[0], fields[1], fields[2]);
+        if max_value == 0 || max_value > 255 {
+            return None
+        }
+        //Single whitespace byte separates the header from binary data
+        position += 1;
+
+        let channels = if magic == b"P3" || magic == b"P6" {3} else {1};
+        //Every sample takes at least a byte, so anything bigger than the file is a bad header
+        let sample_count = width.checked_mul(height)?.checked_mul(channels)?;
+        if sample_count > data.len() {
+            return None
+        }
+        let samples: Vec<usize> = if magic == b"P5" || magic == b"P6" {
+            data.get(position..position.checked_add(sample_count)?)?.iter().map(|sample| *sample as usize).collect()
+        }
+        else {
+            data.get(position..)?
+                .split(|byte| byte.is_ascii_whitespace())
+                .filter(|token| !token.is_empty())
+                .take(sample_count)
+                .map(|token| std::str::from_utf8(token).ok()?.parse().ok())
+                .collect::<Option<Vec<usize>>>()?
+        };
+        if samples.len() < sample_count || samples.iter().any(|sample| *sample > max_value) {
+            return None
+        }
+
+        let pixels = samples
+            .chunks(channels)
+            .map(|pixel| (pixel.iter().sum::<usize>() * 255 / (max_value * channels)) as u8)
+            .collect();
+        Some(GrayImage { width, height, pixels })
+    }
+
+    pub fn decode_bmp(data: &[u8]) -> Option<GrayImage> {
+        let u16_at = |offset: usize| -> Option<usize> {
+            Some(u16::from_le_bytes([*data.get(offset)?, *data.get(offset + 1)?]) as usize)
+        };
+        let i32_at = |offset: usize| -> Option<i32> {
+            let bytes = data.get(offset..offset + 4)?;
+            Some(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        };
+
+        let pixel_offset = usize::try_from(i32_at(10)?).ok()?;
+        let header_size = usize::try_from(i32_at(14)?).ok()?;
+        let width = i32_at(18)?.unsigned_abs() as usize;
+        let raw_height = i32_at(22)?;
+        let height = raw_height.unsigned_abs() as usize;
+        let bits = u16_at(28)?;
+        let compression = i32_at(30)?;
+        //BI_RGB, or BI_BITFIELDS with the usual 32 bit layout
+        if compression != 0 && compression != 3 {
+            return None
+        }
+
+        let palette: Vec<u8> = if bits == 8 {
+            let palette_start = header_size.checked_add(14)?;
+            data.get(palette_start..pixel_offset)?
+                .chunks_exact(4)
+                .map(|entry| luminance(entry[2], entry[1], entry[0]))
+                .collect()
+        }
+        else {
+            Vec::new()
+        };
+
+        let bytes_per_pixel = match bits {
+            8 => 1,
+            24 => 3,
+            32 => 4,
+            _ => return None,
+        };
+        //Rows are padded to 4 bytes and stored bottom up unless height is negative
+        let stride = width.checked_mul(bytes_per_pixel)?.checked_add(3)? & !3;
+        //Check the whole pixel array is there before allocating anything the header asks for
+        if width == 0 || height == 0 || pixel_offset.checked_add(stride.checked_mul(height)?)? > data.len() {
+            return None
+        }
+        let mut pixels = vec![0u8; width * height];
+        for y in 0..height {
+            let row = if raw_height > 0 {height - 1 - y} else {y};
+            let row_data = data.get(pixel_offset + row * stride..pixel_offset + row * stride + width * bytes_per_pixel)?;
+            for x in 0..width {
+                let pixel = &row_data[x * bytes_per_pixel..(x + 1) * bytes_per_pixel];
+                pixels[y * width + x] = match bits {
+                    8 => *palette.get(pixel[0] as usize)?,
+                    _ => luminance(pixel[2], pixel[1], pixel[0]),
+                };
+            }
+        }
+        Some(GrayImage { width, height, pixels })
+    }
+
+    //Scale to the given size by averaging the source pixels that land in each destination pixel
+    pub fn resize(&self, width: usize, height: usize) -> Vec<u8> {
+        let mut resized = vec![0u8; width * height];
+        if self.width == 0 || self.height == 0 {
+            return resized
+        }
+        for y in 0..height {
+            let y_start = y * self.height / height;
+            let y_end = ((y + 1) * self.height / height).max(y_start + 1);
+            for x in 0..width {
+                let x_start = x * self.width / width;
+                let x_end = ((x + 1) * self.width / width).max(x_start + 1);
+                let mut total: usize = 0;
+                for source_y in y_start..y_end {
+                    for source_x in x_start..x_end {
+                        total += self.pixels[source_y * self.width + source_x] as usize;
+                    }
+                }
+                resized[y * width + x] = (total / ((y_end - y_start) * (x_end - x_start))) as u8;
+            }
+        }
+        resized
+    }
+}
+
+fn luminance(red: u8, green: u8, blue: u8) -> u8 {
+    ((red as u32 * 299 + green as u32 * 587 + blue as u32 * 114) / 1000) as u8
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_binary_pgm() {
+        let mut data = b"P5\n# comment\n2 2\n255\n".to_vec();
+        data.extend_from_slice(&[0, 64, 128, 255]);
+        let image = GrayImage::decode_pnm(&data).unwrap();
+        assert_eq!((image.width, image.height), (2, 2));
+        assert_eq!(image.pixels, vec![0, 64, 128, 255]);
+    }
+
+    #[test]
+    fn test_decode_ascii_ppm() {
+        let image = GrayImage::decode_pnm(b"P3 1 2 15\n15 15 15\n0 0 0\n").unwrap();
+        assert_eq!(image.pixels, vec![255, 0]);
+    }
+
+    #[test]
+    fn test_decode_bmp() {
+        //2x2 24 bit bottom up, rows padded to 8 bytes
+        let mut data = vec![0u8; 54];
+        data[0..2].copy_from_slice(b"BM");
+        data[10] = 54;
+        data[14] = 40;
+        data[18] = 2;
+        data[22] = 2;
+        data[28] = 24;
+        data.extend_from_slice(&[0, 0, 0, 255, 255, 255, 0, 0]);
+        data.extend_from_slice(&[255, 255, 255, 0, 0, 0, 0, 0]);
+        let image = GrayImage::decode_bmp(&data).unwrap();
+        assert_eq!(image.pixels, vec![255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_malformed_pnm() {
+        //Sizes that overflow or need far more data than there is
+        assert_eq!(GrayImage::decode_pnm(b"P6 18446744073709551615 2 255\n\0\0\0"), None);
+        assert_eq!(GrayImage::decode_pnm(b"P5 65536 65536 255\n\0\0\0"), None);
+        assert_eq!(GrayImage::decode_pnm(b"P5 2 2 255\n\0\0\0"), None);
+        assert_eq!(GrayImage::decode_pnm(b"P2 1 1 15\n18446744073709551615\n"), None);
+        assert_eq!(GrayImage::decode_pnm(b"P2 1"), None);
+    }
+
+    #[test]
+    fn test_malformed_bmp() {
+        let mut header = vec![0u8; 54];
+        header[0..2].copy_from_slice(b"BM");
+        header[10] = 54;
+        header[14] = 40;
+        header[18] = 2;
+        header[22] = 2;
+        header[28] = 24;
+        assert_eq!(GrayImage::decode_bmp(&header), None);
+
+        //Huge width and height with no pixel data
+        let mut data = header.clone();
+        data[18..22].copy_from_slice(&0x7FFF_FFFFi32.to_le_bytes());
+        data[22..26].copy_from_slice(&(-0x7FFF_FFFFi32).to_le_bytes());
+        assert_eq!(GrayImage::decode_bmp(&data), None);
+
+        //Negative header size
+        let mut data = header.clone();
+        data[14..18].copy_from_slice(&(-1i32).to_le_bytes());
+        data[28] = 8;
+        assert_eq!(GrayImage::decode_bmp(&data), None);
+
+        //Palette area that isn't a whole number of entries, the leftover bytes are ignored
+        let mut data = header;
+        data[10] = 54 + 4 + 3;
+        data[28] = 8;
+        data.extend_from_slice(&[0; 4 + 3 + 8]);
+        assert_eq!(GrayImage::decode_bmp(&data).unwrap().pixels, vec![0; 4]);
+    }
+
+    #[test]
+    fn test_resize() {
+        let image = GrayImage { width: 4, height: 2, pixels: vec![0, 100, 200, 200, 0, 100, 200, 200] };
+        assert_eq!(image.resize(2, 1), vec![50, 200]);
+    }
+}
@@ -0,0 +1,264 @@
+use std::fmt;
+
+//DEFLATE decompressor (RFC 1951), enough to read .gz and .zip roms without system libraries
+//Blocks are stored, or huffman coded with either the fixed code or one sent in the block header
+
+#[derive(Debug, PartialEq)]
+pub struct InflateError(pub &'static str);
+
+impl fmt::Display for InflateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for InflateError {}
+
+const MAX_BITS: usize = 15;
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+//Order code length code lengths are sent in for dynamic blocks
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    //Bits are packed starting from the least significant bit of each byte
+    fn bits(&mut self, count: u32) -> Result<u32, InflateError> {
+        while self.count < count {
+            let byte = *self.data.get(self.position).ok_or(InflateError("unexpected end of data"))?;
+            self.position += 1;
+            self.buffer |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1u32 << count) - 1);
+        self.buffer >>= count;
+        self.count -= count;
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+//Canonical huffman code, decoded one bit at a time by counting codes of each length
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Huffman, InflateError> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for length in lengths {
+            counts[*length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut left: i32 = 1;
+        for count in counts.iter().skip(1) {
+            left = (left << 1) - *count as i32;
+            if left < 0 {
+                return Err(InflateError("over-subscribed huffman code"))
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for length in 1..=MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, length) in lengths.iter().enumerate() {
+            if *length != 0 {
+                symbols[offsets[*length as usize] as usize] = symbol as u16;
+                offsets[*length as usize] += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, InflateError> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for length in 1..=MAX_BITS {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize])
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(InflateError("invalid huffman code"))
+    }
+}
+
+//Decompress a raw DEFLATE stream, giving up once the output passes limit bytes
+pub fn inflate(data: &[u8], limit: usize) -> Result<Vec<u8>, InflateError> {
+    let mut reader = BitReader { data, position: 0, buffer: 0, count: 0 };
+    let mut output = Vec::new();
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => stored_block(&mut reader, &mut output)?,
+            1 => {
+                let (literals, distances) = fixed_codes()?;
+                huffman_block(&mut reader, &mut output, &literals, &distances, limit)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut reader)?;
+                huffman_block(&mut reader, &mut output, &literals, &distances, limit)?;
+            }
+            _ => return Err(InflateError("invalid block type")),
+        }
+        if output.len() > limit {
+            return Err(InflateError("output too large"))
+        }
+        if last {
+            return Ok(output)
+        }
+    }
+}
+
+fn stored_block(reader: &mut BitReader, output: &mut Vec<u8>) -> Result<(), InflateError> {
+    reader.align();
+    let header = reader.data.get(reader.position..reader.position + 4).ok_or(InflateError("unexpected end of data"))?;
+    let length = u16::from_le_bytes([header[0], header[1]]);
+    let inverse = u16::from_le_bytes([header[2], header[3]]);
+    if length != !inverse {
+        return Err(InflateError("stored block length check failed"))
+    }
+    reader.position += 4;
+    let bytes = reader.data.get(reader.position..reader.position + length as usize).ok_or(InflateError("unexpected end of data"))?;
+    output.extend_from_slice(bytes);
+    reader.position += length as usize;
+    Ok(())
+}
+
+fn fixed_codes() -> Result<(Huffman, Huffman), InflateError> {
+    let mut lengths = [0u8; 288];
+    lengths[0..144].iter_mut().for_each(|length| *length = 8);
+    lengths[144..256].iter_mut().for_each(|length| *length = 9);
+    lengths[256..280].iter_mut().for_each(|length| *length = 7);
+    lengths[280..288].iter_mut().for_each(|length| *length = 8);
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), InflateError> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for index in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+        code_lengths[*index] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths)?;
+
+    //Literal and distance lengths are sent as one run, repeats can cross between them
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut index = 0;
+    while index < lengths.len() {
+        let symbol = code_length_code.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths.get(index.wrapping_sub(1)).ok_or(InflateError("repeat with no previous length"))?;
+                (previous, 3 + reader.bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize),
+        };
+        if index + repeat > lengths.len() {
+            return Err(InflateError("too many code lengths"))
+        }
+        lengths[index..index + repeat].iter_mut().for_each(|length| *length = value);
+        index += repeat;
+    }
+    if lengths[256] == 0 {
+        return Err(InflateError("no end of block code"))
+    }
+    Ok((Huffman::new(&lengths[..literal_count])?, Huffman::new(&lengths[literal_count..])?))
+}
+
+fn huffman_block(reader: &mut BitReader, output: &mut Vec<u8>, literals: &Huffman, distances: &Huffman, limit: usize) -> Result<(), InflateError> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let index = symbol - 257;
+                if index >= LENGTH_BASE.len() {
+                    return Err(InflateError("invalid length code"))
+                }
+                let length = LENGTH_BASE[index] as usize + reader.bits(LENGTH_EXTRA[index] as u32)? as usize;
+                let index = distances.decode(reader)? as usize;
+                if index >= DISTANCE_BASE.len() {
+                    return Err(InflateError("invalid distance code"))
+                }
+                let distance = DISTANCE_BASE[index] as usize + reader.bits(DISTANCE_EXTRA[index] as u32)? as usize;
+                if distance > output.len() {
+                    return Err(InflateError("distance before start of output"))
+                }
+                //Copies can overlap their own output, so go byte by byte
+                let start = output.len() - distance;
+                for offset in 0..length {
+                    output.push(output[start + offset]);
+                }
+            }
+        }
+        if output.len() > limit {
+            return Err(InflateError("output too large"))
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stored_block() {
+        assert_eq!(inflate(&[0x01, 0x03, 0x00, 0xFC, 0xFF, 0x61, 0x62, 0x63], 100).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn test_fixed_block() {
+        let data = [0xF3, 0x48, 0xCD, 0xC9, 0xC9, 0xD7, 0x51, 0xF0, 0x40, 0xA1, 0xDC, 0x13, 0x73, 0x53, 0x15, 0x9C, 0xF2, 0x2B, 0x15, 0x01];
+        assert_eq!(inflate(&data, 100).unwrap(), b"Hello, Hello, Hello, Game Boy!".to_vec());
+    }
+
+    #[test]
+    fn test_dynamic_block() {
+        let data = [
+            0x7D, 0xD0, 0xCB, 0x09, 0x80, 0x30, 0x14, 0x44, 0xD1, 0x56, 0xA6, 0x00, 0x17, 0x19, 0xFF, 0x96,
+            0xA3, 0x90, 0xE0, 0x22, 0xF8, 0x02, 0x06, 0x6C, 0xDF, 0x0E, 0xEE, 0xFA, 0xEC, 0x4E, 0xD2, 0x15,
+            0xBD, 0xD7, 0xFC, 0x2A, 0x8A, 0x5A, 0x34, 0xC5, 0xA3, 0x7E, 0x67, 0x7D, 0x67, 0xAD, 0x83, 0x8C,
+            0x3A, 0xA2, 0x4E, 0xA8, 0x33, 0xEA, 0x82, 0xBA, 0xA2, 0x6E, 0xA8, 0x3B, 0xEA, 0xC1, 0x1B, 0x89,
+            0x99, 0xB7, 0xCC, 0x5D, 0xE6, 0x2F, 0x73, 0x98, 0x79, 0xCC, 0x5C, 0x66, 0x3E, 0x33, 0xA7, 0x99,
+            0xD7, 0x7E,
+        ];
+        let expected: String = (0..20).map(|bottles| format!("{} bottles of pop on the wall, ", bottles)).collect();
+        assert_eq!(inflate(&data, 1000).unwrap(), expected.into_bytes());
+        assert_eq!(inflate(&data, 100), Err(InflateError("output too large")));
+    }
+
+    #[test]
+    fn test_truncated_data() {
+        assert!(inflate(&[0xF3, 0x48, 0xCD], 100).is_err());
+    }
+}
@@ -0,0 +1,96 @@
+//Interrupt controller
+//IF -> FF0F one request bit per source, set by hardware and cleared when the handler is entered
+//IE -> FFFF one enable bit per source
+//A source fires when its bits are set in both and IME is on, lower bits win
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Interrupt {
+    VBlank,
+    LcdStat,
+    Timer,
+    Serial,
+    Joypad,
+}
+
+//Highest priority first
+const PRIORITY: [Interrupt; 5] = [Interrupt::VBlank, Interrupt::LcdStat, Interrupt::Timer, Interrupt::Serial, Interrupt::Joypad];
+
+impl Interrupt {
+    pub fn bit(self) -> u8 {
+        1 << self as u8
+    }
+
+    //Handlers are at 0x40, 0x48, 0x50, 0x58 and 0x60
+    pub fn vector(self) -> u16 {
+        0x40 + 8 * self as u16
+    }
+}
+
+pub struct Interrupts {
+    pub flags: u8, //0xFF0F
+    pub enable: u8, //0xFFFF
+}
+
+impl Interrupts {
+    pub fn new() -> Interrupts {
+        Interrupts {
+            flags: 0,
+            enable: 0,
+        }
+    }
+
+    pub fn request(&mut self, interrupt: Interrupt) {
+        self.flags |= interrupt.bit();
+    }
+
+    pub fn acknowledge(&mut self, interrupt: Interrupt) {
+        self.flags &= !interrupt.bit();
+    }
+
+    //Sources that are both requested and enabled, whether or not IME is set
+    pub fn pending(&self) -> u8 {
+        self.flags & self.enable & 0x1F
+    }
+
+    pub fn highest(&self) -> Option<Interrupt> {
+        let pending = self.pending();
+        PRIORITY.iter().copied().find(|interrupt| pending & interrupt.bit() > 0)
+    }
+
+    //Upper 3 bits of IF aren't connected and read as 1
+    pub fn read_flags(&self) -> u8 {
+        self.flags | 0xE0
+    }
+
+    pub fn write_flags(&mut self, data: u8) {
+        self.flags = data & 0x1F;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_priority() {
+        let mut interrupts = Interrupts::new();
+        interrupts.request(Interrupt::Joypad);
+        interrupts.request(Interrupt::Timer);
+        assert_eq!(interrupts.highest(), None);
+        interrupts.enable = 0x1F;
+        assert_eq!(interrupts.highest(), Some(Interrupt::Timer));
+        interrupts.acknowledge(Interrupt::Timer);
+        assert_eq!(interrupts.highest(), Some(Interrupt::Joypad));
+        assert_eq!(interrupts.read_flags(), 0xF0);
+    }
+
+    #[test]
+    fn test_vectors() {
+        assert_eq!(Interrupt::VBlank.vector(), 0x40);
+        assert_eq!(Interrupt::LcdStat.vector(), 0x48);
+        assert_eq!(Interrupt::Timer.vector(), 0x50);
+        assert_eq!(Interrupt::Serial.vector(), 0x58);
+        assert_eq!(Interrupt::Joypad.vector(), 0x60);
+    }
+}
@@ -3,9 +3,14 @@ extern crate sdl2;
 //With sdl, textures are image data for gpu, surfaces are image data for cpu
 use sdl2::pixels::PixelFormatEnum;
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-//use std::time::Duration;
+use sdl2::keyboard::{Keycode, Scancode};
+use sdl2::controller::Axis;
+use std::time::Duration;
+use std::cell::Cell;
 use std::env;
+use std::path::Path;
+use std::process;
+use std::rc::Rc;
 
 use std::io;
 
@@ -13,6 +18,19 @@ mod cpu;
 mod register;
 mod memory;
 mod gpu;
+mod rom;
+mod header;
+mod cartridge;
+mod save;
+mod image;
+mod crc32;
+mod inflate;
+mod archive;
+mod patch;
+mod timer;
+mod interrupt;
+
+const USAGE: &str = "rusty [da|info] [debug] [--camera <image or directory>] [--tilt <script>] [--ir-loopback] [--headless <frames>] [--patch <ips/bps/ups>] [--entry <name>] <rom> [boot rom]";
 
 pub struct DebugMode {
     pub run: bool,  //Run until breakpoint
@@ -30,8 +48,17 @@ fn main() {
 
     let mut da: bool = false;
     let mut debug: bool = false;
-
-    for arg in args {
+    let mut info: bool = false;
+    let mut camera: Option<&Path> = None;
+    let mut tilt_script: Option<&Path> = None;
+    let mut ir_loopback: bool = false;
+    let mut headless: Option<u32> = None;
+    let mut patch_path: Option<&Path> = None;
+    let mut entry: Option<&str> = None;
+    let mut paths: Vec<&str> = Vec::new();
+
+    let mut arg_iter = args.iter().skip(1);
+    while let Some(arg) = arg_iter.next() {
         
         if arg == "da" {
             da = true;
@@ -39,18 +66,96 @@ fn main() {
         else if arg == "debug" {
             debug = true;
         }
+        else if arg == "info" {
+            info = true;
+        }
         else if arg == "help" {
-            println!("da - print rom disassembly to file, debug - run emulator in debug mode");
+            println!("usage: {}", USAGE);
+            println!("da - print rom disassembly to file, info - print cartridge header, debug - run emulator in debug mode");
+            println!("--camera - PNM or BMP image, or a directory of them, used as the Pocket Camera sensor");
+            println!("--entry - file to run from a .zip, by default the first .gb or .gbc");
+            println!("--patch - apply a patch at load, by default <rom>.ips, .bps or .ups is applied if it exists");
+            println!("--headless - run this many frames without a window or input, then write the save and exit");
+            println!("--ir-loopback - point the cartridge's IR LED back at its own receiver");
+            println!("--tilt - file of \"x y\" tilt values in g, one per accelerometer read, instead of IJKL or the analog stick");
+            return
+        }
+        else if arg == "--camera" {
+            match arg_iter.next() {
+                Some(path) => camera = Some(Path::new(path)),
+                None => {eprintln!("--camera needs an image file or directory"); process::exit(1)},
+            }
+        }
+        else if arg == "--tilt" {
+            match arg_iter.next() {
+                Some(path) => tilt_script = Some(Path::new(path)),
+                None => {eprintln!("--tilt needs a script file"); process::exit(1)},
+            }
+        }
+        else if arg == "--headless" {
+            match arg_iter.next().map(|frames| frames.parse()) {
+                Some(Ok(frames)) => headless = Some(frames),
+                _ => {eprintln!("--headless needs a number of frames"); process::exit(1)},
+            }
+        }
+        else if arg == "--ir-loopback" {
+            ir_loopback = true;
+        }
+        else if arg == "--patch" {
+            match arg_iter.next() {
+                Some(path) => patch_path = Some(Path::new(path)),
+                None => {eprintln!("--patch needs a patch file"); process::exit(1)},
+            }
         }
+        else if arg == "--entry" {
+            match arg_iter.next() {
+                Some(name) => entry = Some(name),
+                None => {eprintln!("--entry needs a file name"); process::exit(1)},
+            }
+        }
+        else {
+            paths.push(arg);
+        }
+
+    }
 
+    let rom_path = match paths.first() {
+        Some(path) => Path::new(*path),
+        None => {eprintln!("No ROM given. usage: {}", USAGE); process::exit(1)},
+    };
+    //For archives this is the unpacked rom's name, which the save and patch files follow
+    let (mut rom, rom_name) = match rom::read_rom(rom_path, entry) {
+        Ok(loaded) => loaded,
+        Err(error) => {eprintln!("{}", error); process::exit(1)},
+    };
+    //Soft patch in memory, the rom file on disk is left alone
+    if let Some(patch_path) = patch_path.map(Path::to_path_buf).or_else(|| patch::find_patch(&rom_name)) {
+        rom = match rom::patch_rom(&rom, &patch_path) {
+            Ok(patched) => patched,
+            Err(error) => {eprintln!("{}", error); process::exit(1)},
+        };
+        println!("Applied patch {}", patch_path.display());
     }
+    let boot_rom = match paths.get(1) {
+        Some(path) => match rom::read_boot_rom(Path::new(*path)) {
+            Ok(boot_rom) => Some(boot_rom),
+            Err(error) => {eprintln!("{}", error); process::exit(1)},
+        },
+        None => None,
+    };
 
-    if da {
-        disassembly();
+    if info {
+        rom_info(&rom);
+    }
+    else if da {
+        disassembly(&rom);
+    }
+    else if let Some(frames) = headless {
+        run_headless(&rom_name, &rom, boot_rom.as_deref(), camera, tilt_script, ir_loopback, frames);
     }
     else {
         loop {
-            let reset: bool = emulate(debug);
+            let reset: bool = emulate(debug, &rom_name, &rom, boot_rom.as_deref(), camera, tilt_script, ir_loopback);
             println!("{}", reset);
             if !reset {
                 return
@@ -59,8 +164,20 @@ fn main() {
     }
 }
 
-pub fn disassembly() {
-    let mut cpu = cpu::Cpu::new();
+pub fn rom_info(rom: &[u8]) {
+    let header = header::CartridgeHeader::parse(rom);
+    println!("{}", header);
+
+    if !header.header_checksum_valid() {
+        println!("Warning: header checksum mismatch, real hardware will refuse to boot this ROM.");
+    }
+    if !header.global_checksum_valid() {
+        println!("Warning: global checksum mismatch, ROM may be corrupt or patched.");
+    }
+}
+
+pub fn disassembly(rom: &[u8]) {
+    let mut cpu = cpu::Cpu::new(memory::Memory::new(rom, None));
     cpu.memory.memory_setup();
 
     cpu.print_disassembly();
@@ -139,9 +256,87 @@ pub fn get_debug_input(debug_mode: &mut DebugMode) {
     }
 }
 
-pub fn emulate(debug: bool) -> bool {
-    let mut cpu = cpu::Cpu::new();
-    cpu.memory.memory_setup();
+//Cpu with the cartridge's save, camera and IR hooked up, shared by the window and headless runs
+fn load_machine(rom_path: &Path, rom: &[u8], boot_rom: Option<&[u8]>, camera: Option<&Path>, ir_loopback: bool) -> (cpu::Cpu, Option<save::SaveFile>) {
+    let mut cpu = cpu::Cpu::new(memory::Memory::new(rom, boot_rom));
+    //Boot rom initializes the hardware itself
+    if !cpu.memory.bios_flag {
+        cpu.memory.memory_setup();
+    }
+
+    //Battery backed ram survives between runs in <rom>.sav
+    let save_file = if cpu.memory.header.cartridge_type.battery {
+        let mut save_file = save::SaveFile::new(rom_path);
+        if let Err(error) = save_file.load(&mut *cpu.memory.cartridge) {
+            println!("Could not load save file {}: {}", save_file.path.display(), error);
+        }
+        Some(save_file)
+    }
+    else {
+        None
+    };
+
+    if let Some(camera) = camera {
+        match cartridge::camera::ImageFiles::open(camera) {
+            Ok(files) => cpu.memory.cartridge.set_image_source(Box::new(files)),
+            Err(error) => println!("Could not load camera images: {}", error),
+        }
+    }
+
+    //Without a link partner the IR receiver only ever sees our own LED, or nothing
+    if ir_loopback {
+        cpu.memory.cartridge.set_infrared(Box::new(cartridge::infrared::InfraredPort::loopback()));
+    }
+
+    (cpu, save_file)
+}
+
+//No SDL at all, tilt only comes from the script and the joypad stays released
+//Hands the machine back afterwards so it can be inspected
+pub fn run_headless(rom_path: &Path, rom: &[u8], boot_rom: Option<&[u8]>, camera: Option<&Path>, tilt_script: Option<&Path>, ir_loopback: bool, frames: u32) -> cpu::Cpu {
+    let (mut cpu, mut save_file) = load_machine(rom_path, rom, boot_rom, camera, ir_loopback);
+    if let Some(path) = tilt_script {
+        match cartridge::mbc7::ScriptedTilt::load(path) {
+            Ok(script) => cpu.memory.cartridge.set_tilt_source(Box::new(script)),
+            Err(error) => println!("Could not load tilt script {}: {}", path.display(), error),
+        }
+    }
+
+    let mut frame = 0;
+    while frame < frames {
+        if cpu.memory.bios_flag && (cpu.registers.pc == 0x100) {cpu.memory.bios_flag = false;}
+        cpu.cycle();
+
+        //Only a button press wakes the cpu from STOP, which can't happen here
+        if cpu.stopped {
+            println!("Stopped at frame {}", frame);
+            break
+        }
+        if cpu.memory.vram.vblank_flag {
+            cpu.memory.vram.vblank_flag = false;
+            frame += 1;
+            //Nothing to rumble or play sound on
+            while cpu.memory.cartridge.poll_event().is_some() {}
+        }
+    }
+    write_save(&mut save_file, &mut cpu);
+    cpu
+}
+
+pub fn emulate(debug: bool, rom_path: &Path, rom: &[u8], boot_rom: Option<&[u8]>, camera: Option<&Path>, tilt_script: Option<&Path>, ir_loopback: bool) -> bool {
+    let (mut cpu, mut save_file) = load_machine(rom_path, rom, boot_rom, camera, ir_loopback);
+    let mut frames_since_flush: u32 = 0;
+
+    //Tilt for accelerometer carts, scripted or updated from the keyboard and analog stick every frame
+    let tilt = Rc::new(Cell::new((0.0, 0.0)));
+    match tilt_script {
+        Some(path) => match cartridge::mbc7::ScriptedTilt::load(path) {
+            Ok(script) => cpu.memory.cartridge.set_tilt_source(Box::new(script)),
+            Err(error) => println!("Could not load tilt script {}: {}", path.display(), error),
+        },
+        None => cpu.memory.cartridge.set_tilt_source(Box::new(cartridge::mbc7::LiveTilt { value: tilt.clone() })),
+    }
+
     let sdl = sdl2::init().unwrap();
     let video = sdl.video().unwrap();
     const GAME_WIDTH:u32 = 160;
@@ -165,6 +360,15 @@ pub fn emulate(debug: bool) -> bool {
     canvas.present();
     let mut event_pump = sdl.event_pump().unwrap();
 
+    //First attached game controller, used for cartridge rumble and tilt
+    //Optional, some setups can't start the controller subsystem at all
+    let controller_subsystem = sdl.game_controller().ok();
+    let mut controller = controller_subsystem.as_ref().and_then(|subsystem| {
+        (0..subsystem.num_joysticks().unwrap_or(0))
+            .find(|index| subsystem.is_game_controller(*index))
+            .and_then(|index| subsystem.open(index).ok())
+    });
+
     let mut debug_mode = DebugMode {
         run: false,
         step: false,
@@ -212,6 +416,7 @@ pub fn emulate(debug: bool) -> bool {
                 get_debug_input(&mut debug_mode);
             }
             else if debug_mode.reset {
+                write_save(&mut save_file, &mut cpu);
                 return true
             }
             else if debug_mode.step {
@@ -226,14 +431,29 @@ pub fn emulate(debug: bool) -> bool {
         }
 
 
-        cpu.memory.vram.render_mode_cycles += cpu.cycle() as u32;
-        //cpu.memory.vram.render_mode_cycles += 4;
-        cpu.memory.vram.step();
+        //The cpu clocks the lcd, timer and cartridge itself as it accesses memory
+        cpu.cycle();
         //println!("Serial SB: {}", cpu.memory.read_byte(0xFF01));
         //println!("Serial SC: {}", cpu.memory.read_byte(0xFF02));
 
-        if cpu.memory.vram.vblank_flag {
+        //The lcd doesn't run while stopped, keep polling input about once a frame to wake the cpu
+        if cpu.stopped {
+            std::thread::sleep(Duration::from_millis(16));
+        }
+
+        if cpu.memory.vram.vblank_flag || cpu.stopped {
             cpu.memory.vram.vblank_flag = false;
+
+            //Flush save ram about once a second so a crash doesn't lose progress
+            frames_since_flush += 1;
+            if frames_since_flush >= 60 {
+                frames_since_flush = 0;
+                if let Some(save_file) = &mut save_file {
+                    if let Err(error) = save_file.flush(&mut *cpu.memory.cartridge) {
+                        println!("Could not write save file {}: {}", save_file.path.display(), error);
+                    }
+                }
+            }
             //Pitch is 160 Pixels * 3 bytes per Pixel
             //println!("Scroll Value: {}", cpu.memory.vram.scroll_x);
             //cpu.memory.vram.scroll_x = cpu.memory.vram.scroll_x.wrapping_add(1);
@@ -241,8 +461,23 @@ pub fn emulate(debug: bool) -> bool {
             canvas.copy(&texture, None, None).unwrap();
             canvas.present();
 
+            while let Some(event) = cpu.memory.cartridge.poll_event() {
+                match event {
+                    cartridge::CartridgeEvent::Rumble(on) => {
+                        if let Some(controller) = &mut controller {
+                            //Games pulse the motor, each pulse restarts the effect
+                            let strength = if on {0xFFFF} else {0};
+                            controller.set_rumble(strength, strength, 1000).ok();
+                        }
+                    }
+                    //No audio output yet
+                    cartridge::CartridgeEvent::Tone(_) => {}
+                }
+            }
+
             //true - direction/bit 4 = 0
             let input_status = cpu.memory.input_status();
+            let buttons = cpu.memory.memory[0xFF00] & 0x0F;
 
             for event in event_pump.poll_iter() {
                 match event {
@@ -328,13 +563,80 @@ pub fn emulate(debug: bool) -> bool {
                     }
                 }
             }
+
+            //Joypad interrupt is requested when any input line goes from high to low
+            //Input only changes here, so it's checked once a frame after polling events
+            if buttons & !cpu.memory.memory[0xFF00] & 0x0F != 0 {
+                cpu.memory.interrupts.request(interrupt::Interrupt::Joypad);
+            }
+
+            //IJKL tilts, the left analog stick takes over when it's moved
+            let keys = event_pump.keyboard_state();
+            let key = |scancode| if keys.is_scancode_pressed(scancode) {1.0} else {0.0};
+            let mut tilt_x = key(Scancode::L) - key(Scancode::J);
+            let mut tilt_y = key(Scancode::K) - key(Scancode::I);
+            if let Some(controller) = &controller {
+                let stick_x = controller.axis(Axis::LeftX) as f32 / 32768.0;
+                let stick_y = controller.axis(Axis::LeftY) as f32 / 32768.0;
+                if stick_x.abs() > 0.1 || stick_y.abs() > 0.1 {
+                    tilt_x = stick_x;
+                    tilt_y = stick_y;
+                }
+            }
+            tilt.set((tilt_x, tilt_y));
         }
 
         //::std::thread::sleep(Duration::new(0, 1_000_000_000u32/1000000));
     }
+    write_save(&mut save_file, &mut cpu);
     return false;
 }
 
+pub fn write_save(save_file: &mut Option<save::SaveFile>, cpu: &mut cpu::Cpu) {
+    if let Some(save_file) = save_file {
+        if let Err(error) = save_file.write(&mut *cpu.memory.cartridge) {
+            println!("Could not write save file {}: {}", save_file.path.display(), error);
+        }
+    }
+}
+
 pub fn poll_input() {
     
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_headless_scripted_tilt() {
+        let dir = env::temp_dir().join(format!("rusty-headless-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let rom_path = dir.join("tilt.gb");
+        let script_path = dir.join("tilt.txt");
+        fs::write(&script_path, "0.5 -0.25\n").unwrap();
+
+        //MBC7 with battery, latches the accelerometer once and copies X to C000
+        let mut rom = vec![0; 0x8000];
+        rom[0x147] = 0x22;
+        let program = [
+            0x3E, 0x0A, 0xEA, 0x00, 0x00, //Enable ram
+            0x3E, 0x40, 0xEA, 0x00, 0x40,
+            0x3E, 0x55, 0xEA, 0x00, 0xA0, //Latch
+            0x3E, 0xAA, 0xEA, 0x10, 0xA0,
+            0xFA, 0x20, 0xA0, 0xEA, 0x00, 0xC0,
+            0xFA, 0x30, 0xA0, 0xEA, 0x01, 0xC0,
+            0x18, 0xFE,
+        ];
+        rom[0x100..0x100 + program.len()].copy_from_slice(&program);
+
+        let cpu = run_headless(&rom_path, &rom, None, None, Some(&script_path), false, 2);
+        let x = cpu.memory.read_byte(0xC000) as u16 | (cpu.memory.read_byte(0xC001) as u16) << 8;
+        assert_eq!(x, cartridge::mbc7::TILT_CENTER + (0.5 * cartridge::mbc7::TILT_RANGE) as u16);
+        //EEPROM save written on the way out
+        assert!(dir.join("tilt.sav").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,6 +1,8 @@
-use std::fs;
-use std::path::Path;
 use crate::gpu::Vram;
+use crate::timer::{self, Timer};
+use crate::interrupt::{Interrupt, Interrupts};
+use crate::header::{CartridgeHeader, CgbSupport};
+use crate::cartridge::{self, Cartridge};
 
     //Rom bank 0 -> 0000-3FFF
     //Rom bank 1 -> 4000-7FFF
@@ -15,51 +17,59 @@ use crate::gpu::Vram;
     //High RAM -> FF80-FFFE
     //Interrupt Enable Register -> FFFF
 pub struct Memory {
-    pub rom: [u8; 0x8000],
+    pub header: CartridgeHeader,
+    pub cartridge: Box<dyn Cartridge>,
     pub vram: Vram,
+    pub timer: Timer,
+    pub interrupts: Interrupts,
     pub memory: [u8; 65536],
     pub bios: [u8; 0x100],
     pub bios_flag: bool,
+    pub cgb_mode: bool, //Cartridge supports CGB, enables KEY1
+    pub double_speed: bool, //KEY1 bit 7
+    pub speed_switch: bool, //KEY1 bit 0, switch speed on the next STOP
+    speed_remainder: u32, //Leftover double speed cycle not yet passed to the lcd
+    dma_source: u16, //Start of the block being copied into OAM
+    dma_index: u16, //Next byte of the block to copy, the transfer is done at 0xA0
 }
 
-///home/porkchop/programming/rust/rustyroms/gb-test-roms/cpu_instrs/individual/07-jr,jp,call,ret,rst.gb
 impl Memory {
-    pub fn new() -> Memory {
+    //Build memory from the cartridge rom and an optional boot rom
+    //When a boot rom is supplied it is mapped over 0000-00FF until it jumps to 0x100
+    pub fn new(rom: &[u8], bios: Option<&[u8]>) -> Memory {
 
-        let bios_path = Path::new("/home/porkchop/programming/rust/rustyroms/gameboy.gb");
-        let bios_file = fs::read(bios_path).unwrap();
-        println!("Bios Length: {}", bios_file.len());
         let mut bios_buffer: [u8; 0x100] = [0; 0x100];
-
-        for (index, instruction) in bios_file.iter().enumerate() {
-            let data = *instruction as u8;
-            bios_buffer[index] = data;
+        if let Some(bios) = bios {
+            for (index, instruction) in bios.iter().take(0x100).enumerate() {
+                bios_buffer[index] = *instruction;
+            }
         }
 
-        let path = Path::new("/home/porkchop/programming/rust/rustyroms/gb-test-roms/cpu_instrs/individual/02-interrupts.gb");
-        let file = fs::read(path).unwrap();
-        println!("File Length: {}", file.len());
-        let mut buffer: [u8; 65536] = [0; 65536];
-        
-        for (index,instruction) in file.iter().enumerate() {
-            let data = *instruction as u8;
-            buffer[index] = data;
-        }
-        println!("Cartridge Type: {}", buffer[0x147]);
-        println!("ROM Size: {}", buffer[0x148]);
-        println!("RAM Size: {}", buffer[0x149]);
+        let header = CartridgeHeader::parse(rom);
+        let cartridge = cartridge::new_cartridge(rom.to_vec(), &header);
+        let cgb_mode = header.cgb != CgbSupport::None;
         Memory {
-            rom: [1u8; 0x8000],
+            header,
+            cartridge,
             vram: Vram::new(),
-            memory: buffer,
+            timer: Timer::new(),
+            interrupts: Interrupts::new(),
+            memory: [0; 65536],
             bios: bios_buffer,
-            bios_flag: false,
+            bios_flag: bios.is_some(),
+            cgb_mode,
+            double_speed: false,
+            speed_switch: false,
+            speed_remainder: 0,
+            dma_source: 0,
+            dma_index: 0xA0,
         }
     }
 
     //Initialize registers to post bootrom values
     pub fn memory_setup(&mut self) {
         self.write_byte(0xFF00, 0xFF); //Joypad Input Register
+        self.timer.divider = timer::DIV_AFTER_BOOT; //DIV, writing FF04 would clear it
         self.write_byte(0xFF05, 0x00); //TIMA
         self.write_byte(0xFF06, 0x00); //TMA
         self.write_byte(0xFF07, 0x00); //TAC
@@ -95,35 +105,36 @@ impl Memory {
 
 
     pub fn read_byte(&self, address: u16) -> u8 {
+        if self.dma_blocked(address) {
+            return 0xFF
+        }
+        self.bus_read(address)
+    }
+
+    //Read without the restrictions of a running DMA transfer
+    fn bus_read(&self, address: u16) -> u8 {
 
         if self.bios_flag && (address < 0x100) {
             return self.bios[address as usize]
         }
 
         match address {
-            0x0000..=0x7FFF => self.memory[address as usize],
+            0x0000..=0x7FFF => self.cartridge.read_rom(address),
             0x8000..=0x9FFF => self.vram.read_byte(address),
-            0xFF0F => {
-                let mut data: u8 = 0xC0;
-                if self.vram.vblank_int_request {
-                    data |= 1 << 0;
-                }
-                else {
-                    data &= !(1 << 0);
-                }
-                if self.vram.lcd_stat_int_request {
-                    data |= 1 << 1;
-                }
-                else {
-                    data &= !(1 << 1);
-                }
-                data
-            }
+            0xA000..=0xBFFF => self.cartridge.read_ram(address),
+            0xFE00..=0xFE9F => self.vram.read_oam(address),
+            0xFF04..=0xFF07 => self.timer.read_byte(address),
+            0xFF0F => self.interrupts.read_flags(),
+            0xFF41 => self.vram.read_stat(),
             0xFF42 => self.vram.scroll_y,
             0xFF43 => self.vram.scroll_x,
             0xFF44 => self.vram.scan_row,
+            0xFF45 => self.vram.lyc,
             0xFF4A => self.vram.window_y,
             0xFF4B => self.vram.window_x,
+            0xFF4D if self.cgb_mode => 0x7E | (self.double_speed as u8) << 7 | self.speed_switch as u8,
+            0xFF4D => 0xFF,
+            0xFFFF => self.interrupts.enable,
             _ => self.memory[address as usize],
 
         }
@@ -133,16 +144,25 @@ impl Memory {
     pub fn read_word(&self, address: u16) -> u16 {
         
         let lower: u8 = self.read_byte(address);
-        let upper: u8 = self.read_byte(address + 1);
+        let upper: u8 = self.read_byte(address.wrapping_add(1));
 
         ((upper as u16) << 8) | (lower as u16)
     }
 
     pub fn write_byte(&mut self, address: u16, data: u8) {
+        if self.dma_blocked(address) {
+            return
+        }
+        match address {
+            //Writes to rom go to the bank controller, rom itself never changes
+            0x0000..=0x7FFF => {self.cartridge.write_rom(address, data); return},
+            0xA000..=0xBFFF => {self.cartridge.write_ram(address, data); return},
+            _ => (),
+        }
         self.memory[address as usize] = data;
         match address {
-            0x0000..=0x7FFF => self.memory[address as usize] = data,
             0x8000..=0x9FFF => self.vram.write_byte(address, data),
+            0xFE00..=0xFE9F => self.vram.write_oam(address, data),
             0xFF00 => {self.memory[0xFF00] |= 0xCF} //Reset input buttons to unpressed state when input state changes
             //Temporary for Blaarg's Cpu tests
             0xFF01 => {   //Serial Transfer Control
@@ -153,47 +173,99 @@ impl Memory {
                     print!("{:#04X}", result);
                 }*/
             }
-            0xFF0F => {
-                if data & 0x01 > 0 {
-                    self.vram.vblank_int_request = true;
-                }
-                else {
-                    self.vram.vblank_int_request = false;
-                }
-                if data & 0x02 > 0 {
-                    self.vram.lcd_stat_int_request = true;
-                }
-                else {
-                    self.vram.lcd_stat_int_request = false;
-                }
-                //Fix interrupts
-            },
+            //No link cable, a transfer on the internal clock finishes straight away and shifts in 0xFF
+            0xFF02 if data & 0x81 == 0x81 => {
+                self.memory[0xFF01] = 0xFF;
+                self.memory[0xFF02] = data & 0x7F;
+                self.interrupts.request(Interrupt::Serial);
+            }
+            0xFF04..=0xFF07 => self.timer.write_byte(address, data),
+            0xFF0F => self.interrupts.write_flags(data),
             0xFF42 => self.vram.scroll_y = data,
             0xFF43 => self.vram.scroll_x = data,
             0xFF44 => self.vram.scan_row = 0, //Writing to this register should always reset the row to zero
             0xFF4A => self.vram.window_y = data,
             0xFF4B => self.vram.window_x = data,
             0xFF40 => self.update_lcd_control(),
-            0xFF45 => self.vram.lcd_stat = data,
-            0xFFFF => {
-                if data & 0x01 > 0 {
-                    self.vram.vblank_int_enable = true;
-                }
-                else {
-                    self.vram.vblank_int_enable = false;
-                }
-                if data & 0x02 > 0 {
-                    self.vram.lcd_stat_int_enable = true;
-                }
-                else {
-                    self.vram.lcd_stat_int_enable = false;
-                }
+            0xFF41 => self.vram.write_stat(data, &mut self.interrupts),
+            0xFF45 => self.vram.write_lyc(data, &mut self.interrupts),
+            //Writing again while a transfer is running restarts it from the new source
+            0xFF46 => {
+                self.dma_source = (data as u16) << 8;
+                self.dma_index = 0;
             }
+            0xFF47 => self.vram.background_palette = data,
+            0xFF48 => self.vram.sprite_palette_0 = data,
+            0xFF49 => self.vram.sprite_palette_1 = data,
+            0xFF4D if self.cgb_mode => self.speed_switch = data & 0x01 > 0,
+            0xFFFF => self.interrupts.enable = data,
             _ => (),
         }
 
     }
 
+    //Run everything clocked alongside the cpu for a number of M-cycles
+    pub fn tick(&mut self, cycles: u32) {
+        //In double speed the cpu and timer run twice as fast as the lcd and cartridge
+        let slow_cycles = if self.double_speed {
+            let total = cycles + self.speed_remainder;
+            self.speed_remainder = total & 1;
+            total / 2
+        }
+        else {
+            cycles
+        };
+        self.vram.render_mode_cycles += slow_cycles;
+        self.vram.step(&mut self.interrupts);
+        self.timer.tick(cycles, &mut self.interrupts);
+        self.cartridge.tick(slow_cycles);
+        for _ in 0..cycles {
+            self.dma_step();
+        }
+    }
+
+    //OAM DMA copies one byte a M-cycle, 160 M-cycles in all
+    fn dma_step(&mut self) {
+        if !self.dma_active() {
+            return
+        }
+        //Sources past work ram read from its echo
+        let mut address = self.dma_source + self.dma_index;
+        if address >= 0xE000 {
+            address -= 0x2000;
+        }
+        let data = self.bus_read(address);
+        self.vram.write_oam(0xFE00 + self.dma_index, data);
+        self.dma_index += 1;
+    }
+
+    pub fn dma_active(&self) -> bool {
+        self.dma_index < 0xA0
+    }
+
+    //The transfer holds the buses to rom, ram, vram and OAM, the cpu only gets 0xFF00 and up
+    //which leaves it with the io registers and high ram to wait in
+    fn dma_blocked(&self, address: u16) -> bool {
+        self.dma_active() && address < 0xFF00
+    }
+
+    //STOP resets DIV, on CGB it switches speed instead of stopping when KEY1 is armed
+    //Returns true if the cpu should stay stopped
+    pub fn stop(&mut self) -> bool {
+        self.write_byte(0xFF04, 0);
+        if self.cgb_mode && self.speed_switch {
+            self.speed_switch = false;
+            self.double_speed = !self.double_speed;
+            return false
+        }
+        true
+    }
+
+    //True when any of the selected joypad lines are low
+    pub fn joypad_pressed(&self) -> bool {
+        self.memory[0xFF00] & 0x0F != 0x0F
+    }
+
     //returns true if bit 4 is zero/input is set to direction
     pub fn input_status(&mut self) -> bool {
         let input_reg = self.read_byte(0xFF00);
@@ -220,7 +292,7 @@ impl Memory {
         let lower: u8 = (data & 0x00FF) as u8;
         
         self.write_byte(address, lower);
-        self.write_byte(address+1, upper);
+        self.write_byte(address.wrapping_add(1), upper);
         //println!("Wrote {:#x} to address {:#x}", lower, address);
         //println!("Wrote {:#x} to address {:#x}", upper, address+1);
 
@@ -235,29 +307,125 @@ mod tests {
 
     #[test]
     fn test_read_word() {
-        let mut memory = Memory::new();
-        memory.write_byte(0x2000, 0xBA);
-        memory.write_byte(0x2001, 0xDC);
-        assert_eq!(memory.read_word(0x2000), 0xDCBA);
+        let mut memory = Memory::new(&[0; 0x8000], None);
+        memory.write_byte(0xC000, 0xBA);
+        memory.write_byte(0xC001, 0xDC);
+        assert_eq!(memory.read_word(0xC000), 0xDCBA);
     }
 
     #[test]
     fn test_write_word() {
-        let mut memory = Memory::new();
-        memory.write_word(0x6FF0, 0xDCBA);
-        assert_eq!(memory.memory[0x6FF0], 0xBA);
-        assert_eq!(memory.memory[0x6FF1], 0xDC);
+        let mut memory = Memory::new(&[0; 0x8000], None);
+        memory.write_word(0xCFF0, 0xDCBA);
+        assert_eq!(memory.memory[0xCFF0], 0xBA);
+        assert_eq!(memory.memory[0xCFF1], 0xDC);
+    }
+
+    #[test]
+    fn test_rom_write_selects_bank() {
+        let mut rom = vec![0u8; 0x10000];
+        rom[0x147] = 0x01;
+        rom[0x4000] = 0x11;
+        rom[0xC000] = 0x33;
+        let mut memory = Memory::new(&rom, None);
+        assert_eq!(memory.read_byte(0x4000), 0x11);
+        memory.write_byte(0x2000, 0x03);
+        assert_eq!(memory.read_byte(0x4000), 0x33);
+        assert_eq!(memory.read_byte(0x2000), 0x00);
     }
 
     #[test]
     fn test_lcd_control_update() {
-        let mut memory = Memory::new();
+        let mut memory = Memory::new(&[0; 0x8000], None);
         memory.write_byte(0xFF40, 0x82);
         assert_eq!(memory.vram.lcd_control.display, true);
         assert_eq!(memory.vram.lcd_control.window_map, false);
         assert_eq!(memory.vram.lcd_control.sprites, true);
     }
 
+    #[test]
+    fn test_lcd_status_registers() {
+        let mut memory = Memory::new(&[0; 0x8000], None);
+        memory.write_byte(0xFF45, 0x42);
+        assert_eq!(memory.read_byte(0xFF45), 0x42);
+        memory.write_byte(0xFF41, 0xFF);
+        assert_eq!(memory.read_byte(0xFF41), 0xF8);
+        assert_eq!(memory.interrupts.flags, 0);
+    }
+
+    #[test]
+    fn test_oam_dma() {
+        let mut memory = Memory::new(&[0; 0x8000], None);
+        for offset in 0..0xA0 {
+            memory.write_byte(0xC100 + offset, offset as u8);
+        }
+        memory.write_byte(0xFF80, 0x12);
+        memory.write_byte(0xFF46, 0xC1);
+
+        //Only high memory is reachable while the transfer runs
+        memory.tick(80);
+        assert!(memory.dma_active());
+        assert_eq!(memory.read_byte(0xC100), 0xFF);
+        assert_eq!(memory.read_byte(0xFE00), 0xFF);
+        assert_eq!(memory.read_byte(0xFF80), 0x12);
+        memory.write_byte(0xC100, 0x55);
+        assert_eq!(memory.vram.read_oam(0xFE4F), 0x4F);
+        assert_eq!(memory.vram.read_oam(0xFE50), 0x00);
+
+        memory.tick(80);
+        assert!(!memory.dma_active());
+        assert_eq!(memory.read_byte(0xC100), 0x00);
+        assert_eq!(memory.read_byte(0xFE9F), 0x9F);
+    }
+
+    #[test]
+    fn test_oam_dma_restart() {
+        let mut memory = Memory::new(&[0; 0x8000], None);
+        for offset in 0..0xA0 {
+            memory.write_byte(0xC100 + offset, 0x11);
+            memory.write_byte(0xC200 + offset, 0x22);
+        }
+        memory.write_byte(0xFF46, 0xC1);
+        memory.tick(100);
+        memory.write_byte(0xFF46, 0xC2);
+        memory.tick(159);
+        assert!(memory.dma_active());
+        memory.tick(1);
+        assert!(!memory.dma_active());
+        assert_eq!(memory.vram.read_oam(0xFE00), 0x22);
+        assert_eq!(memory.vram.read_oam(0xFE9F), 0x22);
+    }
+
+    #[test]
+    fn test_timer_interrupt_flag() {
+        let mut memory = Memory::new(&[0; 0x8000], None);
+        memory.write_byte(0xFF06, 0x80);
+        memory.write_byte(0xFF05, 0xFF);
+        memory.write_byte(0xFF07, 0x05);
+        memory.tick(5);
+        assert_eq!(memory.read_byte(0xFF05), 0x80);
+        assert_eq!(memory.read_byte(0xFF0F), 0xE4);
+        memory.write_byte(0xFF0F, 0x00);
+        assert_eq!(memory.interrupts.flags, 0);
+    }
+
+    #[test]
+    fn test_speed_switch() {
+        let mut memory = Memory::new(&[0; 0x8000], None);
+        memory.write_byte(0xFF4D, 0x01);
+        assert_eq!(memory.read_byte(0xFF4D), 0xFF);
+        assert!(memory.stop());
+
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x143] = 0x80;
+        let mut memory = Memory::new(&rom, None);
+        memory.write_byte(0xFF4D, 0x01);
+        assert_eq!(memory.read_byte(0xFF4D), 0x7F);
+        assert!(!memory.stop());
+        assert_eq!(memory.read_byte(0xFF4D), 0xFE);
+        assert!(memory.stop());
+    }
+
 
 
 
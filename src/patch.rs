@@ -0,0 +1,416 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use crate::crc32::crc32;
+use crate::rom::ROM_MAX_SIZE;
+
+//Soft patches applied to the rom in memory at load time
+//IPS - "PATCH", records of 24 bit offset, 16 bit size and data (size 0 is a run of one byte), "EOF"
+//UPS - "UPS1", sizes, then runs of bytes to skip followed by bytes XORed in, ending in a 0
+//BPS - "BPS1", sizes and metadata, then commands copying from the source, patch or target
+//UPS and BPS end with CRC32s of the source, target and patch, which are all checked
+pub const PATCH_EXTENSIONS: [&str; 3] = ["ips", "bps", "ups"];
+
+#[derive(Debug, PartialEq)]
+pub enum PatchError {
+    UnknownFormat,
+    Malformed(&'static str),
+    SourceSize { expected: usize, actual: usize },
+    TargetSize { size: usize, limit: usize },
+    SourceChecksum { expected: u32, actual: u32 },
+    TargetChecksum { expected: u32, actual: u32 },
+    PatchChecksum { expected: u32, actual: u32 },
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatchError::UnknownFormat => write!(f, "not an IPS, BPS or UPS patch"),
+            PatchError::Malformed(reason) => write!(f, "patch is corrupt: {}", reason),
+            PatchError::SourceSize { expected, actual } => {
+                write!(f, "patch is for a different ROM: expected {} bytes, ROM is {} bytes", expected, actual)
+            }
+            PatchError::TargetSize { size, limit } => {
+                write!(f, "patched ROM would be {} bytes, the limit is {}", size, limit)
+            }
+            PatchError::SourceChecksum { expected, actual } => {
+                write!(f, "patch is for a different ROM: expected CRC32 {:08X}, ROM is {:08X}", expected, actual)
+            }
+            PatchError::TargetChecksum { expected, actual } => {
+                write!(f, "patched ROM has CRC32 {:08X}, patch expected {:08X}", actual, expected)
+            }
+            PatchError::PatchChecksum { expected, actual } => {
+                write!(f, "patch file is damaged: expected CRC32 {:08X}, file is {:08X}", expected, actual)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+//Patch next to the rom with the same name, e.g. game.gb and game.ips
+pub fn find_patch(rom_path: &Path) -> Option<PathBuf> {
+    PATCH_EXTENSIONS.iter().map(|extension| rom_path.with_extension(extension)).find(|path| path.is_file())
+}
+
+//Pick the format from the magic bytes
+pub fn apply_patch(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    match patch.get(0..5) {
+        Some(b"PATCH") => apply_ips(rom, patch),
+        _ => match patch.get(0..4) {
+            Some(b"BPS1") => apply_bps(rom, patch),
+            Some(b"UPS1") => apply_ups(rom, patch),
+            _ => Err(PatchError::UnknownFormat),
+        },
+    }
+}
+
+pub fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let mut reader = PatchReader { data: patch, position: 5 };
+    let mut target = rom.to_vec();
+    loop {
+        let offset_bytes = reader.bytes(3)?;
+        if offset_bytes == b"EOF" {
+            break
+        }
+        let offset = (offset_bytes[0] as usize) << 16 | (offset_bytes[1] as usize) << 8 | offset_bytes[2] as usize;
+        let size = reader.u16_be()? as usize;
+        let (length, data) = if size == 0 {
+            let length = reader.u16_be()? as usize;
+            (length, None)
+        }
+        else {
+            (size, Some(reader.bytes(size)?))
+        };
+        if target.len() < offset + length {
+            check_target_size(offset + length)?;
+            target.resize(offset + length, 0);
+        }
+        match data {
+            Some(data) => target[offset..offset + length].copy_from_slice(data),
+            None => {
+                let value = reader.byte()?;
+                target[offset..offset + length].iter_mut().for_each(|byte| *byte = value);
+            }
+        }
+    }
+    //Optional truncation length after EOF
+    if let Ok(length) = reader.bytes(3) {
+        target.truncate((length[0] as usize) << 16 | (length[1] as usize) << 8 | length[2] as usize);
+    }
+    Ok(target)
+}
+
+pub fn apply_ups(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let target_checksum = check_footer(rom, patch)?;
+    let mut reader = PatchReader { data: &patch[..patch.len() - 12], position: 4 };
+    let source_size = reader.varint()?;
+    let target_size = reader.varint()?;
+    check_source_size(rom, source_size)?;
+    check_target_size(target_size)?;
+
+    let mut target = rom.to_vec();
+    target.resize(target_size, 0);
+    let mut position: usize = 0;
+    while !reader.done() {
+        position = position.checked_add(reader.varint()?).ok_or(PatchError::Malformed("offset too large"))?;
+        loop {
+            let value = reader.byte()?;
+            if let Some(byte) = target.get_mut(position) {
+                *byte ^= value;
+            }
+            position = position.saturating_add(1);
+            if value == 0 {
+                break
+            }
+        }
+    }
+    check_target(&target, target_checksum)?;
+    Ok(target)
+}
+
+pub fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let target_checksum = check_footer(rom, patch)?;
+    let mut reader = PatchReader { data: &patch[..patch.len() - 12], position: 4 };
+    let source_size = reader.varint()?;
+    let target_size = reader.varint()?;
+    let metadata_size = reader.varint()?;
+    reader.bytes(metadata_size)?;
+    check_source_size(rom, source_size)?;
+    check_target_size(target_size)?;
+
+    let mut target: Vec<u8> = Vec::with_capacity(target_size);
+    let mut source_offset: i64 = 0;
+    let mut target_offset: i64 = 0;
+    while !reader.done() {
+        let command = reader.varint()?;
+        let length = (command >> 2) + 1;
+        //Never write past the size in the header, which has already been checked against the limit
+        if length > target_size - target.len() {
+            return Err(PatchError::Malformed("output size doesn't match the header"))
+        }
+        match command & 0x03 {
+            //SourceRead - same bytes as the source at the current output position
+            0 => {
+                let start = target.len();
+                target.extend_from_slice(rom.get(start..start + length).ok_or(PatchError::Malformed("source read out of range"))?);
+            }
+            //TargetRead - bytes straight from the patch
+            1 => target.extend_from_slice(reader.bytes(length)?),
+            //SourceCopy - bytes from anywhere in the source
+            2 => {
+                source_offset = source_offset.checked_add(reader.signed_varint()?).ok_or(PatchError::Malformed("source copy out of range"))?;
+                let start = usize::try_from(source_offset).map_err(|_| PatchError::Malformed("source copy out of range"))?;
+                let range = start.checked_add(length).and_then(|end| rom.get(start..end));
+                target.extend_from_slice(range.ok_or(PatchError::Malformed("source copy out of range"))?);
+                source_offset += length as i64;
+            }
+            //TargetCopy - bytes already written, byte by byte since the ranges can overlap
+            _ => {
+                target_offset = target_offset.checked_add(reader.signed_varint()?).ok_or(PatchError::Malformed("target copy out of range"))?;
+                for _ in 0..length {
+                    let byte = usize::try_from(target_offset).ok().and_then(|offset| target.get(offset).copied())
+                        .ok_or(PatchError::Malformed("target copy out of range"))?;
+                    target.push(byte);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+    if target.len() != target_size {
+        return Err(PatchError::Malformed("output size doesn't match the header"))
+    }
+    check_target(&target, target_checksum)?;
+    Ok(target)
+}
+
+//UPS and BPS footer is source, target and patch CRC32, little endian
+//The patch CRC covers everything before itself, returns the target CRC to check once it's built
+fn check_footer(rom: &[u8], patch: &[u8]) -> Result<u32, PatchError> {
+    if patch.len() < 16 {
+        return Err(PatchError::Malformed("too short"))
+    }
+    let footer = &patch[patch.len() - 12..];
+    let word = |index: usize| u32::from_le_bytes([footer[index], footer[index + 1], footer[index + 2], footer[index + 3]]);
+    let (source, target, expected) = (word(0), word(4), word(8));
+
+    let actual = crc32(&patch[..patch.len() - 4]);
+    if actual != expected {
+        return Err(PatchError::PatchChecksum { expected, actual })
+    }
+    let actual = crc32(rom);
+    if actual != source {
+        return Err(PatchError::SourceChecksum { expected: source, actual })
+    }
+    Ok(target)
+}
+
+//A matching CRC with a different size can't happen for a real rom, but report the size rather than equal CRCs
+fn check_source_size(rom: &[u8], expected: usize) -> Result<(), PatchError> {
+    if rom.len() != expected {
+        return Err(PatchError::SourceSize { expected, actual: rom.len() })
+    }
+    Ok(())
+}
+
+//Sizes come from the patch, so check them before allocating anything
+fn check_target_size(size: usize) -> Result<(), PatchError> {
+    if size > ROM_MAX_SIZE {
+        return Err(PatchError::TargetSize { size, limit: ROM_MAX_SIZE })
+    }
+    Ok(())
+}
+
+fn check_target(target: &[u8], expected: u32) -> Result<(), PatchError> {
+    let actual = crc32(target);
+    if actual != expected {
+        return Err(PatchError::TargetChecksum { expected, actual })
+    }
+    Ok(())
+}
+
+struct PatchReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> PatchReader<'a> {
+    fn done(&self) -> bool {
+        self.position >= self.data.len()
+    }
+
+    fn byte(&mut self) -> Result<u8, PatchError> {
+        let byte = *self.data.get(self.position).ok_or(PatchError::Malformed("unexpected end of patch"))?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], PatchError> {
+        let end = self.position.checked_add(length).ok_or(PatchError::Malformed("unexpected end of patch"))?;
+        let bytes = self.data.get(self.position..end).ok_or(PatchError::Malformed("unexpected end of patch"))?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn u16_be(&mut self) -> Result<u16, PatchError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    //Variable length number shared by UPS and BPS, 7 bits per byte with the high bit marking the last byte
+    //Each continuation also adds one so every value has a single encoding
+    fn varint(&mut self) -> Result<usize, PatchError> {
+        let mut value: usize = 0;
+        let mut shift: usize = 1;
+        loop {
+            let byte = self.byte()?;
+            let part = ((byte & 0x7F) as usize).checked_mul(shift).ok_or(PatchError::Malformed("number too large"))?;
+            value = value.checked_add(part).ok_or(PatchError::Malformed("number too large"))?;
+            if byte & 0x80 != 0 {
+                return Ok(value)
+            }
+            shift = shift.checked_mul(0x80).ok_or(PatchError::Malformed("number too large"))?;
+            value = value.checked_add(shift).ok_or(PatchError::Malformed("number too large"))?;
+        }
+    }
+
+    //BPS copy offsets, bit 0 is the sign
+    fn signed_varint(&mut self) -> Result<i64, PatchError> {
+        let value = self.varint()? as i64;
+        Ok(if value & 1 != 0 {-(value >> 1)} else {value >> 1})
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn varint(mut value: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                data.push(byte | 0x80);
+                return data
+            }
+            data.push(byte);
+            value -= 1;
+        }
+    }
+
+    fn with_footer(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+        patch.extend_from_slice(&crc32(source).to_le_bytes());
+        patch.extend_from_slice(&crc32(target).to_le_bytes());
+        let patch_crc = crc32(&patch);
+        patch.extend_from_slice(&patch_crc.to_le_bytes());
+        patch
+    }
+
+    #[test]
+    fn test_varint_round_trip() {
+        for value in [0, 1, 0x7F, 0x80, 0x4000, 0x12345].iter() {
+            let encoded = varint(*value);
+            let mut reader = PatchReader { data: &encoded, position: 0 };
+            assert_eq!(reader.varint().unwrap(), *value);
+        }
+    }
+
+    #[test]
+    fn test_varint_too_large() {
+        let mut encoded = vec![0x7F; 12];
+        encoded.push(0xFF);
+        let mut reader = PatchReader { data: &encoded, position: 0 };
+        assert_eq!(reader.varint(), Err(PatchError::Malformed("number too large")));
+
+        //Last byte alone pushes the value over
+        let mut encoded = vec![0x00; 9];
+        encoded.push(0xFF);
+        let mut reader = PatchReader { data: &encoded, position: 0 };
+        assert_eq!(reader.varint(), Err(PatchError::Malformed("number too large")));
+    }
+
+    #[test]
+    fn test_ips() {
+        let mut patch = b"PATCH".to_vec();
+        patch.extend_from_slice(&[0x00, 0x00, 0x01, 0x00, 0x02, 0xAA, 0xBB]);
+        //Run of 3 x 0xCC at offset 6, past the end of the rom
+        patch.extend_from_slice(&[0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x03, 0xCC]);
+        patch.extend_from_slice(b"EOF");
+        let target = apply_patch(&[0; 4], &patch).unwrap();
+        assert_eq!(target, vec![0x00, 0xAA, 0xBB, 0x00, 0x00, 0x00, 0xCC, 0xCC, 0xCC]);
+
+        //Truncation after EOF
+        patch.extend_from_slice(&[0x00, 0x00, 0x02]);
+        assert_eq!(apply_patch(&[0; 4], &patch).unwrap(), vec![0x00, 0xAA]);
+    }
+
+    #[test]
+    fn test_ups() {
+        let source = vec![1, 2, 3, 4];
+        let target = vec![1, 7, 3, 4, 9];
+        let mut patch = b"UPS1".to_vec();
+        patch.extend(varint(4));
+        patch.extend(varint(5));
+        //Skip 1, XOR 2 -> 7, end of run
+        patch.extend(varint(1));
+        patch.extend_from_slice(&[2 ^ 7, 0x00]);
+        //Skip 1 more to offset 4, XOR 0 -> 9
+        patch.extend(varint(1));
+        patch.extend_from_slice(&[9, 0x00]);
+        let patch = with_footer(patch, &source, &target);
+        assert_eq!(apply_patch(&source, &patch).unwrap(), target);
+
+        //Different source rom is refused
+        assert!(matches!(apply_patch(&[1, 2, 3, 5], &patch), Err(PatchError::SourceChecksum { .. })));
+
+        //Header size that disagrees with a matching rom is reported as a size mismatch
+        let mut wrong_size = b"UPS1".to_vec();
+        wrong_size.extend(varint(3));
+        wrong_size.extend(varint(5));
+        let wrong_size = with_footer(wrong_size, &source, &target);
+        assert_eq!(apply_patch(&source, &wrong_size), Err(PatchError::SourceSize { expected: 3, actual: 4 }));
+
+        //Target sizes past the rom limit are refused before anything is allocated
+        let mut huge = b"UPS1".to_vec();
+        huge.extend(varint(4));
+        huge.extend(varint(usize::MAX >> 8));
+        let huge = with_footer(huge, &source, &target);
+        assert_eq!(apply_patch(&source, &huge), Err(PatchError::TargetSize { size: usize::MAX >> 8, limit: ROM_MAX_SIZE }));
+    }
+
+    #[test]
+    fn test_bps() {
+        let source = b"ABCDEFGH".to_vec();
+        let target = b"ABCDxyGHABCD".to_vec();
+        let mut patch = b"BPS1".to_vec();
+        patch.extend(varint(source.len()));
+        patch.extend(varint(target.len()));
+        patch.extend(varint(0));
+        //SourceRead 4, TargetRead "xy", SourceCopy 2 from offset 6, TargetCopy 4 from offset 0
+        patch.extend(varint(3 << 2));
+        patch.extend(varint((1 << 2) | 1));
+        patch.extend_from_slice(b"xy");
+        patch.extend(varint((1 << 2) | 2));
+        patch.extend(varint(6 << 1));
+        patch.extend(varint((3 << 2) | 3));
+        patch.extend(varint(0));
+        let patch = with_footer(patch, &source, &target);
+        assert_eq!(apply_patch(&source, &patch).unwrap(), target);
+
+        //A damaged patch is caught by its own checksum
+        let mut damaged = patch.clone();
+        damaged[8] ^= 0x01;
+        assert!(matches!(apply_patch(&source, &damaged), Err(PatchError::PatchChecksum { .. })));
+
+        //Commands that write past the header size are refused
+        let mut long = b"BPS1".to_vec();
+        long.extend(varint(source.len()));
+        long.extend(varint(2));
+        long.extend(varint(0));
+        long.extend(varint(usize::MAX >> 8));
+        let long = with_footer(long, &source, &target);
+        assert_eq!(apply_patch(&source, &long), Err(PatchError::Malformed("output size doesn't match the header")));
+    }
+}
@@ -32,9 +32,10 @@ impl Registers {
         ((self.a as u16) << 8) | self.f as u16
     }
 
+    //Lower 4 bits of F always read as zero
     pub fn set_af(&mut self, data: u16) {
         self.a = ((data & 0xFF00) >> 8) as u8;
-        self.f = (data & 0x00FF) as u8;
+        self.f = (data & 0x00F0) as u8;
     }
 
     pub fn bc(&self) -> u16 {
@@ -0,0 +1,146 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::archive::{self, ArchiveError, Container};
+use crate::patch::{self, PatchError};
+
+//Smallest cartridge is 32 KiB (two 16 KiB banks), largest is MBC5 at 8 MiB
+pub const ROM_MIN_SIZE: usize = 0x8000;
+pub const ROM_MAX_SIZE: usize = 0x80_0000;
+
+//DMG boot rom is mapped over 0000-00FF
+pub const BOOT_ROM_SIZE: usize = 0x100;
+
+#[derive(Debug)]
+pub enum RomError {
+    Io(PathBuf, io::Error),
+    Truncated { path: PathBuf, length: usize, expected: usize },
+    Oversized { path: PathBuf, length: usize, maximum: usize },
+    Patch(PathBuf, PatchError),
+    Archive(PathBuf, ArchiveError),
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomError::Io(path, error) => write!(f, "Could not read {}: {}", path.display(), error),
+            RomError::Truncated { path, length, expected } => {
+                write!(f, "{} is truncated: {} bytes, expected at least {}", path.display(), length, expected)
+            }
+            RomError::Oversized { path, length, maximum } => {
+                write!(f, "{} is too large: {} bytes, maximum is {}", path.display(), length, maximum)
+            }
+            RomError::Patch(path, error) => write!(f, "Could not apply {}: {}", path.display(), error),
+            RomError::Archive(path, error) => write!(f, "Could not open {}: {}", path.display(), error),
+        }
+    }
+}
+
+impl std::error::Error for RomError {}
+
+//Read a cartridge rom and make sure its size is something a cartridge could hold
+//.gz and .zip files are unpacked, entry picks which file to use from a zip
+//Also returns the path the rom would have been unpacked to, save and patch files are named after it
+pub fn read_rom(path: &Path, entry: Option<&str>) -> Result<(Vec<u8>, PathBuf), RomError> {
+    let data = fs::read(path).map_err(|error| RomError::Io(path.to_path_buf(), error))?;
+    let (data, container) = archive::extract_rom(data, entry, ROM_MAX_SIZE).map_err(|error| RomError::Archive(path.to_path_buf(), error))?;
+    check_size(path, data.len(), ROM_MIN_SIZE, ROM_MAX_SIZE)?;
+    Ok((data, unpacked_path(path, container)))
+}
+
+//game.gb.gz unpacks next to itself as game.gb, zip entries keep only their file name
+fn unpacked_path(path: &Path, container: Container) -> PathBuf {
+    match container {
+        Container::Plain => path.to_path_buf(),
+        Container::Gzip => path.with_extension(""),
+        Container::Zip(name) => match Path::new(&name).file_name() {
+            Some(file_name) => path.with_file_name(file_name),
+            None => path.to_path_buf(),
+        },
+    }
+}
+
+//Apply an IPS, BPS or UPS patch file to a rom, the result has to be a valid rom size as well
+pub fn patch_rom(rom: &[u8], patch_path: &Path) -> Result<Vec<u8>, RomError> {
+    let data = fs::read(patch_path).map_err(|error| RomError::Io(patch_path.to_path_buf(), error))?;
+    let patched = patch::apply_patch(rom, &data).map_err(|error| RomError::Patch(patch_path.to_path_buf(), error))?;
+    check_size(patch_path, patched.len(), ROM_MIN_SIZE, ROM_MAX_SIZE)?;
+    Ok(patched)
+}
+
+//Read a boot rom, which must be exactly 256 bytes
+pub fn read_boot_rom(path: &Path) -> Result<Vec<u8>, RomError> {
+    let data = fs::read(path).map_err(|error| RomError::Io(path.to_path_buf(), error))?;
+    check_size(path, data.len(), BOOT_ROM_SIZE, BOOT_ROM_SIZE)?;
+    Ok(data)
+}
+
+fn check_size(path: &Path, length: usize, minimum: usize, maximum: usize) -> Result<(), RomError> {
+    if length < minimum {
+        return Err(RomError::Truncated { path: path.to_path_buf(), length, expected: minimum });
+    }
+    if length > maximum {
+        return Err(RomError::Oversized { path: path.to_path_buf(), length, maximum });
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_size() {
+        let path = Path::new("test.gb");
+        assert!(check_size(path, ROM_MIN_SIZE, ROM_MIN_SIZE, ROM_MAX_SIZE).is_ok());
+        assert!(matches!(check_size(path, 0x150, ROM_MIN_SIZE, ROM_MAX_SIZE), Err(RomError::Truncated { .. })));
+        assert!(matches!(check_size(path, ROM_MAX_SIZE + 1, ROM_MIN_SIZE, ROM_MAX_SIZE), Err(RomError::Oversized { .. })));
+    }
+
+    #[test]
+    fn test_patch_rom() {
+        let path = std::env::temp_dir().join(format!("rusty-patch-{}.ips", std::process::id()));
+        let mut ips = b"PATCH".to_vec();
+        ips.extend_from_slice(&[0x00, 0x01, 0x34, 0x00, 0x01, b'Z']);
+        ips.extend_from_slice(b"EOF");
+        fs::write(&path, &ips).unwrap();
+        let patched = patch_rom(&[0; ROM_MIN_SIZE], &path).unwrap();
+        assert_eq!(patched[0x134], b'Z');
+
+        fs::write(&path, b"not a patch").unwrap();
+        let error = patch_rom(&[0; ROM_MIN_SIZE], &path).unwrap_err();
+        assert!(matches!(error, RomError::Patch(_, PatchError::UnknownFormat)));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_unpacked_path() {
+        let dir = Path::new("roms");
+        assert_eq!(unpacked_path(&dir.join("game.gb"), Container::Plain), dir.join("game.gb"));
+        assert_eq!(unpacked_path(&dir.join("game.gb.gz"), Container::Gzip), dir.join("game.gb"));
+        assert_eq!(unpacked_path(&dir.join("pack.zip"), Container::Zip("games/red.gb".to_string())), dir.join("red.gb"));
+    }
+
+    #[test]
+    fn test_zip_entries_get_their_own_names() {
+        let path = std::env::temp_dir().join(format!("rusty-pack-{}.zip", std::process::id()));
+        let (red, blue) = (vec![0x11; ROM_MIN_SIZE], vec![0x22; ROM_MIN_SIZE]);
+        fs::write(&path, archive::zip(&[("red.gb", 0, red.clone(), &red), ("blue.gb", 0, blue.clone(), &blue)])).unwrap();
+
+        let (rom, name) = read_rom(&path, Some("blue.gb")).unwrap();
+        assert_eq!(rom, blue);
+        assert_eq!(name, path.with_file_name("blue.gb"));
+        let (rom, name) = read_rom(&path, None).unwrap();
+        assert_eq!(rom, red);
+        assert_eq!(name, path.with_file_name("red.gb"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_missing_rom() {
+        let result = read_rom(Path::new("/nonexistent/rom.gb"), None);
+        assert!(matches!(result, Err(RomError::Io(..))));
+    }
+}
@@ -0,0 +1,163 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::cartridge::Cartridge;
+
+//Battery backed ram is kept in <rom>.sav, in the same raw layout other emulators use:
+//external ram byte for byte, followed by the rtc block for carts with a clock
+pub struct SaveFile {
+    pub path: PathBuf,
+    last_ram: Vec<u8>,
+    last_rtc: Option<Vec<u8>>, //Clock registers as of the last write, without the timestamp
+}
+
+//Both rtc save blocks end with the 64 bit time they were saved at
+const RTC_TIMESTAMP_SIZE: usize = 8;
+
+//Clock registers from the rtc block, the timestamp changes on every save so it's left out
+fn rtc_registers(cartridge: &mut dyn Cartridge) -> Option<Vec<u8>> {
+    cartridge.save_rtc().map(|mut rtc| {
+        rtc.truncate(rtc.len().saturating_sub(RTC_TIMESTAMP_SIZE));
+        rtc
+    })
+}
+
+impl SaveFile {
+    pub fn new(rom_path: &Path) -> SaveFile {
+        SaveFile {
+            path: rom_path.with_extension("sav"),
+            last_ram: Vec::new(),
+            last_rtc: None,
+        }
+    }
+
+    //Load ram and clock state into the cartridge, a missing save file is not an error
+    pub fn load(&mut self, cartridge: &mut dyn Cartridge) -> io::Result<()> {
+        match fs::read(&self.path) {
+            Ok(data) => {
+                let ram_length = cartridge.ram().len().min(data.len());
+                cartridge.load_ram(&data[..ram_length]);
+                if data.len() > ram_length {
+                    cartridge.load_rtc(&data[ram_length..]);
+                }
+            }
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => (),
+            Err(error) => return Err(error),
+        }
+        self.last_ram = cartridge.ram().to_vec();
+        self.last_rtc = rtc_registers(cartridge);
+        Ok(())
+    }
+
+    //Write the save file if ram or the clock registers have changed since the last write
+    //A running clock counts as a change, so carts with one are written every flush
+    pub fn flush(&mut self, cartridge: &mut dyn Cartridge) -> io::Result<()> {
+        if cartridge.ram() == &self.last_ram[..] && rtc_registers(cartridge) == self.last_rtc {
+            return Ok(())
+        }
+        self.write(cartridge)
+    }
+
+    //Always write, used on exit so the clock timestamp is current
+    pub fn write(&mut self, cartridge: &mut dyn Cartridge) -> io::Result<()> {
+        let mut data = cartridge.ram().to_vec();
+        let rtc = cartridge.save_rtc();
+        if let Some(rtc) = &rtc {
+            data.extend_from_slice(rtc);
+        }
+
+        //Write to a temporary file first so a crash mid-write can't corrupt the existing save
+        let temp_path = self.path.with_extension("sav.tmp");
+        fs::write(&temp_path, &data)?;
+        fs::rename(&temp_path, &self.path)?;
+
+        self.last_ram = cartridge.ram().to_vec();
+        self.last_rtc = rtc.map(|mut rtc| {
+            rtc.truncate(rtc.len().saturating_sub(RTC_TIMESTAMP_SIZE));
+            rtc
+        });
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::RomOnly;
+    use crate::cartridge::mbc3::Mbc3;
+    use crate::cartridge::rtc::{FakeClock, RTC_SAVE_SIZE};
+    use std::cell::Cell;
+    use std::env;
+    use std::rc::Rc;
+
+    fn temp_rom_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("rusty-{}-{}.gb", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_save_round_trip() {
+        let rom_path = temp_rom_path("round-trip");
+        let mut cartridge = RomOnly::new(vec![0; 0x8000], 0x2000);
+        cartridge.write_ram(0xA010, 0x77);
+
+        let mut save = SaveFile::new(&rom_path);
+        save.write(&mut cartridge).unwrap();
+        assert_eq!(fs::read(&save.path).unwrap().len(), 0x2000);
+
+        let mut loaded = RomOnly::new(vec![0; 0x8000], 0x2000);
+        SaveFile::new(&rom_path).load(&mut loaded).unwrap();
+        assert_eq!(loaded.read_ram(0xA010), 0x77);
+        fs::remove_file(&save.path).unwrap();
+    }
+
+    #[test]
+    fn test_flush_only_when_changed() {
+        let rom_path = temp_rom_path("flush");
+        let mut cartridge = RomOnly::new(vec![0; 0x8000], 0x2000);
+        let mut save = SaveFile::new(&rom_path);
+        save.load(&mut cartridge).unwrap();
+
+        save.flush(&mut cartridge).unwrap();
+        assert!(!save.path.exists());
+        cartridge.write_ram(0xA000, 0x01);
+        save.flush(&mut cartridge).unwrap();
+        assert!(save.path.exists());
+        fs::remove_file(&save.path).unwrap();
+    }
+
+    #[test]
+    fn test_rtc_appended_to_save() {
+        let rom_path = temp_rom_path("rtc");
+        let time = Rc::new(Cell::new(1000));
+        let mut cartridge = Mbc3::new(vec![0; 0x8000], 0x2000, Some(Box::new(FakeClock { time: time.clone() })));
+        let mut save = SaveFile::new(&rom_path);
+        save.write(&mut cartridge).unwrap();
+        assert_eq!(fs::read(&save.path).unwrap().len(), 0x2000 + RTC_SAVE_SIZE);
+        fs::remove_file(&save.path).unwrap();
+    }
+
+    #[test]
+    fn test_flush_rtc_without_ram() {
+        let rom_path = temp_rom_path("rtc-flush");
+        let time = Rc::new(Cell::new(1000));
+        let mut cartridge = Mbc3::new(vec![0; 0x8000], 0, Some(Box::new(FakeClock { time: time.clone() })));
+        let mut save = SaveFile::new(&rom_path);
+        save.load(&mut cartridge).unwrap();
+
+        //Only the timestamp would differ, nothing to write
+        save.flush(&mut cartridge).unwrap();
+        assert!(!save.path.exists());
+
+        //Game sets the seconds register
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_rom(0x4000, 0x08);
+        cartridge.write_ram(0xA000, 30);
+        save.flush(&mut cartridge).unwrap();
+        assert_eq!(fs::read(&save.path).unwrap().len(), RTC_SAVE_SIZE);
+        fs::remove_file(&save.path).unwrap();
+
+        save.flush(&mut cartridge).unwrap();
+        assert!(!save.path.exists());
+    }
+}
@@ -0,0 +1,176 @@
+//Timer registers
+//DIV  -> FF04 upper 8 bits of a 16 bit counter that runs every T-cycle
+//TIMA -> FF05 counts up on falling edges of one divider bit, selected by TAC
+//TMA  -> FF06 loaded into TIMA after it overflows
+//TAC  -> FF07 bit 2 enables TIMA, bits 0-1 select the divider bit
+
+use crate::interrupt::{Interrupt, Interrupts};
+
+//Divider bit watched for each TAC clock select, 4096 Hz, 262144 Hz, 65536 Hz and 16384 Hz
+const TAC_BITS: [u16; 4] = [9, 3, 5, 7];
+
+//Divider value left by the DMG boot rom
+pub const DIV_AFTER_BOOT: u16 = 0xABCC;
+
+pub struct Timer {
+    pub divider: u16,
+    pub tima: u8,
+    pub tma: u8,
+    pub tac: u8,
+    //TIMA reads 0 for one M-cycle after overflowing, then TMA is loaded and the interrupt requested
+    overflow: bool,
+    //M-cycle the reload happened in, TIMA writes are ignored and TMA writes go through to TIMA
+    reloading: bool,
+}
+
+impl Timer {
+    pub fn new() -> Timer {
+        Timer {
+            divider: 0,
+            tima: 0,
+            tma: 0,
+            tac: 0,
+            overflow: false,
+            reloading: false,
+        }
+    }
+
+    //Run the timer for a number of M-cycles
+    pub fn tick(&mut self, cycles: u32, interrupts: &mut Interrupts) {
+        for _ in 0..cycles {
+            self.reloading = false;
+            if self.overflow {
+                self.overflow = false;
+                self.reloading = true;
+                self.tima = self.tma;
+                interrupts.request(Interrupt::Timer);
+            }
+            let before = self.signal();
+            self.divider = self.divider.wrapping_add(4);
+            if before && !self.signal() {
+                self.increment();
+            }
+        }
+    }
+
+    pub fn read_byte(&self, address: u16) -> u8 {
+        match address {
+            0xFF04 => (self.divider >> 8) as u8,
+            0xFF05 => self.tima,
+            0xFF06 => self.tma,
+            _ => self.tac | 0xF8,
+        }
+    }
+
+    pub fn write_byte(&mut self, address: u16, data: u8) {
+        let before = self.signal();
+        match address {
+            //Any write clears the whole divider, which can look like a falling edge to TIMA
+            0xFF04 => self.divider = 0,
+            0xFF05 => {
+                if !self.reloading {
+                    self.tima = data;
+                    //Writing during the delay cancels the reload and the interrupt
+                    self.overflow = false;
+                }
+            }
+            0xFF06 => {
+                self.tma = data;
+                if self.reloading {
+                    self.tima = data;
+                }
+            }
+            //Turning the timer off or switching bits can also trigger an increment
+            _ => self.tac = data & 0x07,
+        }
+        if before && !self.signal() {
+            self.increment();
+        }
+    }
+
+    //Timer enable ANDed with the selected divider bit, TIMA counts when this goes from 1 to 0
+    fn signal(&self) -> bool {
+        let bit = TAC_BITS[(self.tac & 0x03) as usize];
+        (self.tac & 0x04) > 0 && (self.divider >> bit) & 1 == 1
+    }
+
+    fn increment(&mut self) {
+        let (tima, overflow) = self.tima.overflowing_add(1);
+        self.tima = tima;
+        if overflow {
+            self.overflow = true;
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tick(timer: &mut Timer, cycles: u32) -> u8 {
+        let mut interrupts = Interrupts::new();
+        timer.tick(cycles, &mut interrupts);
+        interrupts.flags
+    }
+
+    #[test]
+    fn test_divider() {
+        let mut timer = Timer::new();
+        tick(&mut timer, 64);
+        assert_eq!(timer.read_byte(0xFF04), 1);
+        timer.write_byte(0xFF04, 0x55);
+        assert_eq!(timer.read_byte(0xFF04), 0);
+        assert_eq!(timer.read_byte(0xFF07), 0xF8);
+    }
+
+    #[test]
+    fn test_tima_frequency() {
+        let mut timer = Timer::new();
+        timer.write_byte(0xFF07, 0x05);
+        tick(&mut timer, 4 * 10);
+        assert_eq!(timer.tima, 10);
+
+        let mut timer = Timer::new();
+        timer.write_byte(0xFF07, 0x04);
+        tick(&mut timer, 256 * 3);
+        assert_eq!(timer.tima, 3);
+    }
+
+    #[test]
+    fn test_overflow_reload() {
+        let mut timer = Timer::new();
+        timer.write_byte(0xFF06, 0xF0);
+        timer.write_byte(0xFF05, 0xFF);
+        timer.write_byte(0xFF07, 0x05);
+        assert_eq!(tick(&mut timer, 4), 0);
+        assert_eq!(timer.read_byte(0xFF05), 0x00);
+        assert_eq!(tick(&mut timer, 1), Interrupt::Timer.bit());
+        assert_eq!(timer.read_byte(0xFF05), 0xF0);
+
+        //Writing TIMA in the delay cancels the reload
+        let mut timer = Timer::new();
+        timer.write_byte(0xFF05, 0xFF);
+        timer.write_byte(0xFF07, 0x05);
+        tick(&mut timer, 4);
+        timer.write_byte(0xFF05, 0x12);
+        assert_eq!(tick(&mut timer, 1), 0);
+        assert_eq!(timer.tima, 0x12);
+    }
+
+    #[test]
+    fn test_write_glitches() {
+        //Resetting DIV while the selected bit is set counts as a falling edge
+        let mut timer = Timer::new();
+        timer.write_byte(0xFF07, 0x05);
+        tick(&mut timer, 2);
+        assert_eq!(timer.tima, 0);
+        timer.write_byte(0xFF04, 0);
+        assert_eq!(timer.tima, 1);
+
+        //So does disabling the timer
+        tick(&mut timer, 2);
+        timer.write_byte(0xFF07, 0x01);
+        assert_eq!(timer.tima, 2);
+    }
+}